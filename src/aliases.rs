@@ -0,0 +1,172 @@
+//! Alternative names of fragments, e.g. `NC_000001.11`, `chr1` and `1`.
+//!
+//! Aliases are read either from an NCBI assembly report, or from a tabulated
+//! file whose first column is a name, the second one the name to rename it
+//! to, and the optional following ones other aliases.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::structs::*;
+
+/// The columns of an NCBI assembly report fragments may be renamed after
+pub const NAMINGS: [&str; 4] = ["ucsc", "refseq", "genbank", "sequence-name"];
+
+/// The header of the columns matching `NAMINGS` in NCBI assembly reports;
+/// `Assigned-Molecule` is left out, as unlocalized scaffolds share it with
+/// their chromosome
+const REPORT_COLUMNS: [&str; 4] = [
+    "UCSC-style-name",
+    "RefSeq-Accn",
+    "GenBank-Accn",
+    "Sequence-Name",
+];
+
+#[derive(Default, Debug)]
+pub struct Aliases {
+    /// The names of each fragment, the first one being the one it is renamed
+    /// to
+    groups: Vec<Vec<String>>,
+    /// The group of each name
+    names: HashMap<String, usize>,
+}
+impl Aliases {
+    /// Read the aliases of `filename`; if it is an NCBI assembly report,
+    /// fragments are renamed after its `naming` column, one of `NAMINGS`
+    pub fn from_file(filename: &str, naming: &str) -> Result<Aliases> {
+        let naming = NAMINGS
+            .iter()
+            .position(|n| *n == naming)
+            .ok_or_else(|| anyhow!("Unknown naming `{}`", naming))?;
+        let f = File::open(filename).with_context(|| format!("Unable to open `{}`", filename))?;
+
+        let mut aliases = Aliases::default();
+        // The indices of the `REPORT_COLUMNS`, once the header of an
+        // assembly report has been met
+        let mut report: Option<Vec<usize>> = None;
+        for (i, line) in BufReader::new(f).lines().enumerate() {
+            let line = line.with_context(|| format!("Unable to read `{}`", filename))?;
+            if line.starts_with("# Sequence-Name") {
+                let header = line[2..].split('\t').map(str::trim).collect::<Vec<_>>();
+                report = Some(
+                    REPORT_COLUMNS
+                        .iter()
+                        .map(|c| {
+                            header.iter().position(|h| h == c).ok_or_else(|| {
+                                anyhow!("{}:L{}: no `{}` column found", filename, i + 1, c)
+                            })
+                        })
+                        .collect::<Result<_>>()?,
+                );
+                continue;
+            }
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields = line.split('\t').map(str::trim).collect::<Vec<_>>();
+            let names = if let Some(columns) = report.as_ref() {
+                let column = |i: usize| fields.get(i).copied().filter(|n| *n != "na");
+                // Fall back on the sequence name when the chosen one is missing
+                let to = column(columns[naming])
+                    .or_else(|| column(columns[3]))
+                    .ok_or_else(|| {
+                        anyhow!("{}:L{} `{}`: no sequence name found", filename, i + 1, line)
+                    })?;
+                std::iter::once(to)
+                    .chain(columns.iter().filter_map(|&c| column(c)))
+                    .collect::<Vec<_>>()
+            } else {
+                if fields.len() < 2 {
+                    return Err(anyhow!(
+                        "{}:L{} `{}`: expected at least two columns, found {}",
+                        filename,
+                        i + 1,
+                        line,
+                        fields.len()
+                    ));
+                }
+                std::iter::once(fields[1])
+                    .chain(std::iter::once(fields[0]))
+                    .chain(fields[2..].iter().copied())
+                    .filter(|n| !n.is_empty())
+                    .collect::<Vec<_>>()
+            };
+            aliases
+                .add(&names)
+                .with_context(|| format!("{}:L{} `{}`", filename, i + 1, line))?;
+        }
+
+        Ok(aliases)
+    }
+
+    /// Register `names` as aliases of a single fragment, to be renamed to
+    /// the first of them
+    pub fn add<T: AsRef<str>>(&mut self, names: &[T]) -> Result<()> {
+        let to = names[0].as_ref();
+        let known = names
+            .iter()
+            .find_map(|n| self.names.get(n.as_ref()).map(|&group| (n.as_ref(), group)));
+        let group = match known {
+            Some((name, group)) => {
+                if self.groups[group][0] != to {
+                    return Err(anyhow!(
+                        "`{}` is already an alias of `{}`",
+                        name,
+                        self.groups[group][0]
+                    ));
+                }
+                group
+            }
+            None => {
+                self.groups.push(Vec::new());
+                self.groups.len() - 1
+            }
+        };
+
+        for name in names.iter().map(|n| n.as_ref()) {
+            match self.names.get(name) {
+                Some(&other) if other != group => {
+                    return Err(anyhow!(
+                        "`{}` is already an alias of `{}`",
+                        name,
+                        self.groups[other][0]
+                    ))
+                }
+                Some(_) => {}
+                None => {
+                    self.names.insert(name.to_owned(), group);
+                    self.groups[group].push(name.to_owned());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The name `name` should be renamed to, if it has any alias
+    pub fn canonical(&self, name: &str) -> Option<&str> {
+        self.names
+            .get(name)
+            .map(|&group| self.groups[group][0].as_str())
+    }
+
+    /// All the names of the fragment `name`, including itself
+    pub fn aliases<'a>(&'a self, name: &'a str) -> Vec<&'a str> {
+        match self.names.get(name) {
+            Some(&group) => self.groups[group].iter().map(|n| n.as_str()).collect(),
+            None => vec![name],
+        }
+    }
+
+    /// The fragment of `strand` known as `name` or as one of its aliases
+    pub fn resolve<'a>(&self, strand: &'a StrandResult, name: &str) -> Option<&'a Start> {
+        strand
+            .find_chr(name)
+            .or_else(|| self.aliases(name).iter().find_map(|n| strand.find_chr(n)))
+    }
+}
@@ -53,6 +53,14 @@ enum Operation {
     },
 }
 
+/// Look for the duplications originating from `needle`, starting at
+/// `needle_offset` in `strand`.
+///
+/// Matches are normally only looked for downstream of the probes, as upstream
+/// ones will be found when probing from their own position. When only a
+/// subset of `strand` is probed, `probed` should list its sorted `(start,
+/// length)` intervals, so that upstream matches laying outside of them are
+/// kept.
 #[allow(clippy::too_many_arguments)]
 pub fn search_duplications(
     needle: &[u8],
@@ -61,12 +69,20 @@ pub fn search_duplications(
     sa: &[SAIdx],
     searcher: &Searcher,
     progress: &AtomicUsize,
-    settings: RunSettings,
+    settings: &RunSettings,
+    probed: Option<&[(usize, usize)]>,
 ) -> Vec<ProtoSDsFamily> {
-    fn try_extend_arms(arms: &[Arm], m: &Segment, e: i64, i: usize, ps: usize) -> Operation {
+    fn try_extend_arms(
+        arms: &[Arm],
+        m: &Segment,
+        e: i64,
+        i: usize,
+        ps: usize,
+        relative_gap: f64,
+    ) -> Operation {
         for (j, a) in arms.iter().enumerate() {
             if a.active
-                && d_ss(&a.right, m) < cmp::max(e, (0.1 * a.left.len() as f64) as i64)
+                && d_ss(&a.right, m) < cmp::max(e, (relative_gap * a.left.len() as f64) as i64)
                 && m.end > a.right.end
             {
                 return Operation::ExtendArm {
@@ -101,15 +117,21 @@ pub fn search_duplications(
             continue;
         }
         let matches: Vec<Segment> = searcher
-            .search(strand, sa, &needle[i..i + settings.probe_size])
+            .search_ambiguous(
+                strand,
+                sa,
+                &needle[i..i + settings.probe_size],
+                settings.ambiguity,
+            )
             .into_iter()
             .filter(|m| m.start != i)
             .filter(|m| {
-                if !settings.reverse {
+                let downstream = if !settings.reverse {
                     m.start > i + needle_offset
                 } else {
                     m.start >= needle_offset + needle.len() - i
-                }
+                };
+                downstream || probed.is_some_and(|probed| !is_probed(probed, m.start))
             })
             .collect();
         if matches.len() > settings.max_cardinality {
@@ -129,6 +151,7 @@ pub fn search_duplications(
                     i64::from(settings.max_gap_size),
                     i,
                     settings.probe_size,
+                    settings.relative_gap,
                 )
             })
             .collect::<Vec<_>>();
@@ -170,7 +193,7 @@ pub fn search_duplications(
             }
         });
 
-        if arms.len() > 200 {
+        if arms.len() > settings.max_arms {
             arms.retain(|a| {
                 a.active
                     || a.left.len() >= settings.min_duplication_length
@@ -180,29 +203,44 @@ pub fn search_duplications(
 
         // Check if there are still extending arms
         if !arms.is_empty() && arms.iter().all(|a| !a.active) {
-            let family: ProtoSDsFamily = arms
-                .iter()
-                .filter(|a| a.right.len() >= settings.min_duplication_length)
-                .map(|a| ProtoSD {
-                    left: a.left.start,
-                    right: a.right.start,
-                    left_length: a.left.len(),
-                    right_length: a.right.len(),
-                    identity: 0.,
-                    reversed: false,
-                    complemented: false,
-                })
-                .collect();
-            if !family.is_empty() {
-                r.push(family);
-            }
+            r.extend(close_family(&arms, settings));
             arms.clear();
         }
     }
+    // When only a subset of the strand is probed, the arms still extending
+    // at the end of the needle belong to duplications that will not be
+    // probed from anywhere else
+    if probed.is_some() {
+        r.extend(close_family(&arms, settings));
+    }
 
     r
 }
 
+/// The family formed by the long enough `arms`, if any
+fn close_family(arms: &[Arm], settings: &RunSettings) -> Option<ProtoSDsFamily> {
+    let family: ProtoSDsFamily = arms
+        .iter()
+        .filter(|a| a.right.len() >= settings.min_duplication_length)
+        .map(|a| ProtoSD {
+            left: a.left.start,
+            right: a.right.start,
+            left_length: a.left.len(),
+            right_length: a.right.len(),
+            identity: 0.,
+            reversed: false,
+            complemented: false,
+        })
+        .collect();
+    (!family.is_empty()).then_some(family)
+}
+
+/// Returns true if `pos` lays in one of the sorted `(start, length)` intervals
+fn is_probed(probed: &[(usize, usize)], pos: usize) -> bool {
+    let i = probed.partition_point(|&(start, _)| start <= pos);
+    i > 0 && pos < probed[i - 1].0 + probed[i - 1].1
+}
+
 /// Returns the minimal distance between two segments
 fn d_ss(a: &Segment, m: &Segment) -> i64 {
     if (m.start >= a.start && m.start <= a.end) || (m.end >= a.start && m.end <= a.end) {
@@ -0,0 +1,125 @@
+use std::{fs::File, io::BufWriter};
+
+use anyhow::{bail, Context, Result};
+use clap::*;
+use log::*;
+
+use asgart::{
+    compare::{self, Comparison},
+    exporters::{Exporter, JSONExporter},
+    structs::RunResult,
+};
+
+#[derive(Parser)]
+#[command(
+    name = "ASGART compare",
+    version,
+    author,
+    about = "asgart-compare matches the duplications of two ASGART files (either JSON or binary) and reports the recall and precision of the second one against the first one."
+)]
+struct Args {
+    #[arg()]
+    /// The file taken as a reference, e.g. a curated catalogue
+    reference: String,
+
+    #[arg()]
+    /// The file to assess against the reference
+    query: String,
+
+    #[arg(long, default_value = "0.5")]
+    /// Minimal proportion of the length of both arms of two duplicons that
+    /// must overlap for them to match
+    min_overlap: f64,
+
+    #[arg(long)]
+    /// Do not report the figures for each fragment
+    no_fragments: bool,
+
+    #[arg(short = 'o', long)]
+    /// If set, write the reference duplicons missing from the query to
+    /// PREFIX.missed.json and the query duplicons missing from the reference
+    /// to PREFIX.extra.json
+    unmatched: Option<String>,
+}
+
+fn write_result(result: &RunResult, filename: &str) -> Result<()> {
+    let mut out = BufWriter::new(
+        File::create(filename).with_context(|| format!("Unable to create `{}`", filename))?,
+    );
+    JSONExporter.save(result, &mut out)?;
+    info!(
+        "{} duplicons written to {}",
+        result.families.iter().flatten().count(),
+        filename
+    );
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    simple_logger::SimpleLogger::new()
+        .with_level(LevelFilter::Info)
+        .with_colors(true)
+        .init()
+        .context("failed to initialize simple_logger")?;
+
+    let args = Args::parse();
+    if !(args.min_overlap > 0.0 && args.min_overlap <= 1.0) {
+        bail!("--min-overlap should be in ]0, 1]");
+    }
+
+    let reference = RunResult::from_file(&args.reference)?;
+    let query = RunResult::from_file(&args.query)?;
+    let comparison = Comparison::new(&reference, &query, args.min_overlap);
+
+    let found = comparison.reference.iter().filter(|(_, m)| *m).count();
+    let correct = comparison.query.iter().filter(|(_, m)| *m).count();
+    println!(
+        "Recall:    {:.4} ({}/{} reference duplicons found)",
+        comparison.recall(),
+        found,
+        comparison.reference.len()
+    );
+    println!(
+        "Precision: {:.4} ({}/{} query duplicons in the reference)",
+        comparison.precision(),
+        correct,
+        comparison.query.len()
+    );
+
+    if !args.no_fragments {
+        println!();
+        println!(
+            "{:<24} {:>10} {:>10} {:>8} {:>10} {:>10} {:>9}",
+            "Fragment", "Reference", "Found", "Recall", "Query", "Correct", "Precision"
+        );
+        for (name, f) in comparison.per_fragment() {
+            println!(
+                "{:<24} {:>10} {:>10} {:>8.4} {:>10} {:>10} {:>9.4}",
+                name,
+                f.reference,
+                f.found,
+                f.recall(),
+                f.query,
+                f.correct,
+                f.precision()
+            );
+        }
+    }
+
+    if let Some(prefix) = args.unmatched.as_ref() {
+        // Both sides of the comparison list the duplicons in the order of
+        // their families
+        let mut missed = comparison.reference.iter().map(|(_, matched)| !matched);
+        write_result(
+            &compare::filter_result(&reference, |_| missed.next().unwrap_or(false)),
+            &format!("{}.missed.json", prefix),
+        )?;
+        let mut extra = comparison.query.iter().map(|(_, matched)| !matched);
+        write_result(
+            &compare::filter_result(&query, |_| extra.next().unwrap_or(false)),
+            &format!("{}.extra.json", prefix),
+        )?;
+    }
+
+    Ok(())
+}
@@ -10,7 +10,8 @@ use clap::*;
 use log::*;
 
 use asgart::{
-    exporters::{Exporter, JSONExporter},
+    binary,
+    exporters::{BinaryExporter, Exporter, JSONExporter},
     utils,
 };
 
@@ -33,11 +34,11 @@ fn read_fasta(filename: &str) -> Result<Vec<u8>> {
     name = "ASGART extract",
     version,
     author,
-    about = "asgart-extract pulls out duplication families from an ASGART JSON file into a serie of FASTA files, one per family."
+    about = "asgart-extract pulls out duplication families from an ASGART result file into a serie of FASTA files, one per family."
 )]
 struct Args {
     #[arg()]
-    /// The JSON file to process
+    /// The result file to process
     input: String,
 
     #[arg(short = 'l', long)]
@@ -45,7 +46,7 @@ struct Args {
     locations: Option<Vec<String>>,
 
     #[arg(short = 'I', long)]
-    /// Write the sequences directly into the input JSON files
+    /// Write the sequences directly into the input result file
     in_place: bool,
 
     #[arg(short = 'D', long)]
@@ -67,9 +68,9 @@ fn main() -> Result<()> {
     let args = Args::parse();
 
     if !args.in_place && !args.dump {
-        return Err(anyhow::Error::msg(format!(
-            "Please specify at least one of `--in-place` or `--dump`; see --help for more details"
-        )));
+        return Err(anyhow::Error::msg(
+            "Please specify at least one of `--in-place` or `--dump`; see --help for more details",
+        ));
     }
     let destination = format!("{}/", args.destination.unwrap_or("./".to_string()));
     if !Path::new(&destination).is_dir() {
@@ -81,7 +82,10 @@ fn main() -> Result<()> {
     let locations = args.locations.unwrap_or(vec![".".to_owned()]);
 
     info!("Reading {}...", &args.input);
-    let mut result = asgart::structs::RunResult::from_files(&[args.input.clone()])?;
+    let input = &args.input;
+    let input_is_binary =
+        binary::sniff(File::open(input).with_context(|| format!("Unable to open `{}`", input))?)?.0;
+    let mut result = asgart::structs::RunResult::from_files(std::slice::from_ref(&args.input))?;
     info!("Done.");
 
     let strands_files = result
@@ -137,7 +141,16 @@ fn main() -> Result<()> {
                 sd.right_seq = Some(String::from_utf8(right_seq).unwrap());
             })
         });
-        JSONExporter.save(&result, &mut File::create(&args.input).unwrap())?
+        // Keep the original format of the input file
+        let mut exporter = if input_is_binary {
+            Box::new(BinaryExporter::new()) as Box<dyn Exporter>
+        } else {
+            Box::new(JSONExporter) as Box<dyn Exporter>
+        };
+        exporter.save(
+            &result,
+            &mut std::io::BufWriter::new(File::create(&args.input).unwrap()),
+        )?
     }
     if args.dump {
         for (i, family) in result.families.iter().enumerate() {
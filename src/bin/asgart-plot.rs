@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::File,
     io::{prelude::*, BufReader},
     path::Path,
@@ -10,209 +10,191 @@ use clap::*;
 use regex::Regex;
 
 use asgart::{
+    aliases::{self, Aliases},
+    importers,
+    index::{DupliconId, RunResultIndex},
     plot::{
         chord_plot::ChordPlotter, circos_plot::CircosPlotter, colorizers::*,
         flat_plot::FlatPlotter, genome_plot::GenomePlotter, rosary_plot::RosaryPlotter, *,
     },
+    regions::{self, Region},
     structs::*,
 };
 
-fn filter_families_in_features(
-    result: &mut RunResult,
-    features_families: &[Vec<Feature>],
+/// The duplicons having an arm within `threshold` bp of `position`
+fn duplicons_near(
+    index: &RunResultIndex,
+    position: &FeaturePosition,
     threshold: usize,
-) {
-    fn _overlap((xstart, xlen): (usize, usize), (ystart, ylen): (usize, usize)) -> bool {
-        let xend = xstart + xlen;
-        let yend = ystart + ylen;
+) -> Result<Vec<DupliconId>> {
+    match *position {
+        FeaturePosition::Relative {
+            ref chr,
+            start,
+            length,
+        } => {
+            if !index.has_fragment(chr) {
+                return Err(anyhow!("Unable to find fragment `{}`", chr));
+            }
+            Ok(index.duplicons_overlapping(
+                chr,
+                start.saturating_sub(threshold),
+                start + length + threshold,
+            ))
+        }
+        FeaturePosition::Absolute { start, length } => Ok(index.duplicons_overlapping_global(
+            start.saturating_sub(threshold),
+            start + length + threshold,
+        )),
+    }
+}
 
-        (xstart >= ystart && xstart <= yend) || (ystart >= xstart && ystart <= xend)
+/// The duplicons having an arm within `threshold` bp of any of the features
+fn duplicons_near_features(
+    index: &RunResultIndex,
+    features_families: &[Vec<Feature>],
+    threshold: usize,
+) -> Result<HashSet<DupliconId>> {
+    let mut near = HashSet::new();
+    for position in features_families
+        .iter()
+        .flatten()
+        .flat_map(|feature| feature.positions.iter())
+    {
+        near.extend(duplicons_near(index, position, threshold)?);
     }
+    Ok(near)
+}
 
-    result.families = result
-        .families
-        .clone()
+fn filter_families_in_features(
+    result: &mut RunResult,
+    features_families: &[Vec<Feature>],
+    threshold: usize,
+) -> Result<()> {
+    let index = RunResultIndex::new(result);
+    let to_keep = duplicons_near_features(&index, features_families, threshold)?
         .into_iter()
-        .filter(|family| {
-            family.iter().any(|sd| {
-                features_families.iter().any(|feature_family| {
-                    feature_family.iter().any(|feature| {
-                        for position in &feature.positions {
-                            let (start, length) = match *position {
-                                FeaturePosition::Relative {
-                                    ref chr,
-                                    start,
-                                    length,
-                                } => {
-                                    let chr = result
-                                        .strand
-                                        .find_chr(&chr)
-                                        .expect(&format!("Unable to find fragment `{}`", chr));
-                                    (chr.position + start, length)
-                                }
-                                FeaturePosition::Absolute { start, length } => (start, length),
-                            };
-                            if _overlap(sd.left_part(), (start - threshold, length + 2 * threshold))
-                                || _overlap(
-                                    sd.right_part(),
-                                    (start - threshold, length + 2 * threshold),
-                                )
-                            {
-                                return true;
-                            }
-                        }
-                        false
-                    })
-                })
-            })
-        })
-        .collect();
+        .map(|id| id.family)
+        .collect::<HashSet<_>>();
+
+    let mut i = 0;
+    result.families.retain(|_| {
+        i += 1;
+        to_keep.contains(&(i - 1))
+    });
+    Ok(())
 }
 
 fn filter_duplicons_in_features(
     result: &mut RunResult,
     features_families: &[Vec<Feature>],
     threshold: usize,
-) {
-    fn _overlap((xstart, xlen): (usize, usize), (ystart, ylen): (usize, usize)) -> bool {
-        let xend = xstart + xlen;
-        let yend = ystart + ylen;
-
-        (xstart >= ystart && xstart <= yend) || (ystart >= xstart && ystart <= xend)
-    }
-
-    let mut families = result.families.clone();
-    families.iter_mut().for_each(|family| {
-        family.retain(|sd| {
-            features_families.iter().any(|feature_family| {
-                feature_family.iter().any(|feature| {
-                    for position in &feature.positions {
-                        let (start, length) = match *position {
-                            FeaturePosition::Relative {
-                                ref chr,
-                                start,
-                                length,
-                            } => {
-                                let chr = result
-                                    .strand
-                                    .find_chr(&chr)
-                                    .expect(&format!("Unable to find fragment `{}`", chr));
-                                (chr.position + start, length)
-                            }
-                            FeaturePosition::Absolute { start, length } => (start, length),
-                        };
-                        if _overlap(sd.left_part(), (start - threshold, length + 2 * threshold))
-                            || _overlap(
-                                sd.right_part(),
-                                (start - threshold, length + 2 * threshold),
-                            )
-                        {
-                            return true;
-                        }
-                    }
-                    false
-                })
+) -> Result<()> {
+    let index = RunResultIndex::new(result);
+    let to_keep = duplicons_near_features(&index, features_families, threshold)?;
+
+    for (family, sds) in result.families.iter_mut().enumerate() {
+        let mut member = 0;
+        sds.retain(|_| {
+            member += 1;
+            to_keep.contains(&DupliconId {
+                family,
+                member: member - 1,
             })
-        })
-    });
-    result.families = families;
+        });
+    }
+    Ok(())
 }
 
 fn filter_features_in_sds(
     result: &mut RunResult,
     features_families: &mut [Vec<Feature>],
     threshold: usize,
-) {
-    fn _overlap((xstart, xlen): (usize, usize), (ystart, ylen): (usize, usize)) -> bool {
-        let xend = xstart + xlen;
-        let yend = ystart + ylen;
-
-        (xstart >= ystart && xstart <= yend) || (ystart >= xstart && ystart <= xend)
+) -> Result<()> {
+    let index = RunResultIndex::new(result);
+    for family in features_families.iter_mut() {
+        let mut kept = Vec::new();
+        for feature in family.drain(..) {
+            let near = feature
+                .positions
+                .iter()
+                .map(|p| duplicons_near(&index, p, threshold))
+                .collect::<Result<Vec<_>>>()?;
+            if near.iter().any(|ids| !ids.is_empty()) {
+                kept.push(feature);
+            }
+        }
+        *family = kept;
     }
-
-    features_families.iter_mut().for_each(|ref mut family| {
-        family.retain(|feature| {
-            feature.positions.iter().any(|p| {
-                let (start, length) = match *p {
-                    FeaturePosition::Relative {
-                        ref chr,
-                        start,
-                        length,
-                    } => {
-                        let chr = result
-                            .strand
-                            .find_chr(chr)
-                            .unwrap_or_else(|| panic!("Unable to find fragment `{}`", chr));
-                        (chr.position + start, length)
-                    }
-                    FeaturePosition::Absolute { start, length } => (start, length),
-                };
-
-                result.families.iter().any(|family| {
-                    family.iter().any(|sd| {
-                        _overlap(sd.left_part(), (start - threshold, length + 2 * threshold))
-                            || _overlap(
-                                sd.right_part(),
-                                (start - threshold, length + 2 * threshold),
-                            )
-                    })
-                })
-            })
-        })
-    });
+    Ok(())
 }
 
-fn read_feature_file(r: &RunResult, file: &str) -> Result<Vec<Feature>> {
+fn read_feature_file(r: &RunResult, aliases: &Aliases, file: &str) -> Result<Vec<Feature>> {
     let path = Path::new(file);
     let extension: &str = path.extension().unwrap().to_str().unwrap();
 
     match extension {
-        "gff3" => read_gff3_feature_file(r, file),
-        _ => read_custom_feature_file(r, file),
+        "gff3" => read_gff3_feature_file(r, aliases, file),
+        _ => read_custom_feature_file(r, aliases, file),
     }
 }
 
-fn read_gff3_feature_file(_r: &RunResult, file: &str) -> Result<Vec<Feature>> {
+fn read_gff3_feature_file(r: &RunResult, aliases: &Aliases, file: &str) -> Result<Vec<Feature>> {
     let f = File::open(file).with_context(|| format!("Unable to open {}", file))?;
     let f = BufReader::new(f);
 
-    let r = f
-        .lines()
-        .map(|l| l.unwrap())
-        .filter(|l| !l.is_empty() && !l.starts_with('#'))
-        .fold(Vec::new(), |mut ax, l| {
-            let l = l.split('\t').collect::<Vec<&str>>();
-            let start = l[3].parse::<usize>().unwrap();
-            let end = l[4].parse::<usize>().unwrap();
-
-            let name = if l[8].contains("Name=") {
-                l[8].split(';')
-                    .find(|cx| cx.contains("Name"))
-                    .unwrap()
-                    .split('=')
-                    .nth(1)
-                    .unwrap() // unwrap is safe because we check for "Name="
-                    .to_string()
-            } else {
-                l[8].to_owned()
-            };
-
-            let feature = Feature {
-                name,
-                positions: vec![FeaturePosition::Relative {
-                    chr: l[0].to_string(),
-                    start: start,
-                    length: end - start,
-                }],
-            };
-            ax.push(feature);
-            ax
+    let mut features = Vec::new();
+    for (i, line) in f.lines().enumerate() {
+        let line = line.with_context(|| format!("Unable to read {}", file))?;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let l = line.split('\t').collect::<Vec<&str>>();
+        if l.len() != 9 {
+            return Err(anyhow!(
+                "{}:L{} `{}`: expected 9 columns, found {}",
+                file,
+                i + 1,
+                line,
+                l.len()
+            ));
+        }
+        let coordinate = |s: &str| {
+            s.parse::<usize>()
+                .with_context(|| format!("{}:L{}: invalid coordinate `{}`", file, i + 1, s))
+        };
+        let start = coordinate(l[3])?;
+        let end = coordinate(l[4])?;
+
+        let name = if l[8].contains("Name=") {
+            l[8].split(';')
+                .find(|cx| cx.contains("Name"))
+                .unwrap()
+                .split('=')
+                .nth(1)
+                .unwrap() // unwrap is safe because we check for "Name="
+                .to_string()
+        } else {
+            l[8].to_owned()
+        };
+        let chr = aliases
+            .resolve(&r.strand, l[0])
+            .ok_or_else(|| anyhow!("{}:L{}: unable to find fragment `{}`", file, i + 1, l[0]))?;
+
+        features.push(Feature {
+            name,
+            positions: vec![FeaturePosition::Relative {
+                chr: chr.name.clone(),
+                start,
+                length: end.saturating_sub(start),
+            }],
         });
+    }
 
-    Ok(r)
+    Ok(features)
 }
 
-fn read_custom_feature_file(r: &RunResult, file: &str) -> Result<Vec<Feature>> {
+fn read_custom_feature_file(r: &RunResult, aliases: &Aliases, file: &str) -> Result<Vec<Feature>> {
     let f = File::open(file).with_context(|| format!("Unable to open {}", file))?;
     let f = BufReader::new(f);
     let mut d = HashMap::new();
@@ -249,10 +231,14 @@ fn read_custom_feature_file(r: &RunResult, file: &str) -> Result<Vec<Feature>> {
                 .parse::<usize>()
                 .unwrap();
             // XXX Incorrect for non-endofeature runs
-            let chr = r
-                .strand
-                .find_chr(chr_name)
-                .unwrap_or_else(|| panic!("Unable to find fragment `{}`", chr_name));
+            let chr = aliases.resolve(&r.strand, chr_name).ok_or_else(|| {
+                anyhow!(
+                    "{}:L{}: unable to find fragment `{}`",
+                    file,
+                    i + 1,
+                    chr_name
+                )
+            })?;
             if chr.length < position {
                 return Err(anyhow!(
                     "{} greater than {} length ({})",
@@ -295,9 +281,20 @@ fn read_custom_feature_file(r: &RunResult, file: &str) -> Result<Vec<Feature>> {
 )]
 struct Args {
     #[arg()]
-    /// Sets the input file(s) to use. If not specified, JSON data will be expected from STDIN
+    /// Sets the input file(s) to use. If not specified, data will be expected from STDIN
     files: Option<Vec<String>>,
 
+    #[arg(long, value_parser = importers::FORMATS, requires = "genome")]
+    /// Read the inputs in this format rather than as ASGART files: the UCSC
+    /// `genomicSuperDups` table or WGAC output (`superdups`), SEDEF output
+    /// (`sedef`), BEDPE or PAF
+    import: Option<String>,
+
+    #[arg(long)]
+    /// A `.fai` or `.genome` file listing the lengths of the fragments the
+    /// imported duplications stand on
+    genome: Option<String>,
+
     #[command(flatten)]
     verbose: clap_verbosity_flag::Verbosity,
 
@@ -349,6 +346,32 @@ struct Args {
     /// Ignore all fragments is in the given list
     exclude_fragments: Option<Vec<String>>,
 
+    #[arg(long = "region", value_name = "CHR:START-END")]
+    /// Ignore all duplicons not having at least an arm overlapping this
+    /// 0-based, half-open region; may be repeated
+    regions: Vec<Region>,
+
+    #[arg(long = "regions", value_name = "FILE.bed")]
+    /// Ignore all duplicons not having at least an arm overlapping a region
+    /// listed in this BED file
+    regions_file: Option<String>,
+
+    #[arg(long)]
+    /// Require both arms of the duplicons to overlap the regions
+    both_arms: bool,
+
+    #[arg(long)]
+    /// Zoom on the regions, plotting them instead of the whole fragments;
+    /// implies --both-arms
+    clip: bool,
+
+    #[arg(long, value_name = "FILE")]
+    /// An alias table used to resolve the fragments named in feature files,
+    /// either an NCBI assembly report or a tabulated file whose columns are
+    /// names of the same fragment; as any alias resolves to the fragment of
+    /// the results, there is no --naming to choose
+    aliases: Option<String>,
+
     #[arg(long)]
     /// Additional feature tracks to plot
     features: Vec<String>,
@@ -414,23 +437,36 @@ fn main() -> Result<()> {
         .init()
         .context("failed to initialize simple_logger")?;
 
+    let read = |files: &[String]| match args.import.as_ref() {
+        Some(format) => importers::import_files(format, files, args.genome.as_ref().unwrap()),
+        None => RunResult::from_files(files),
+    };
     let (mut result, out_file) = if let Some(files) = args.files.as_ref() {
         (
-            RunResult::from_files(files)?,
+            read(files)?,
             asgart::utils::make_out_filename(args.out.as_deref(), &files.join("-"), ""),
         )
     } else {
         log::warn!("Reading results from STDIN");
         (
-            RunResult::from_stdin()?,
+            match args.import.as_ref() {
+                Some(format) => importers::import_stdin(format, args.genome.as_ref().unwrap())?,
+                None => RunResult::from_stdin()?,
+            },
             asgart::utils::make_out_filename(args.out.as_deref(), "out", ""),
         )
     };
 
+    let aliases = match args.aliases.as_ref() {
+        // Resolution goes through all the names of a fragment, whatever the
+        // naming fragments would be renamed after
+        Some(filename) => Aliases::from_file(filename, aliases::NAMINGS[0])?,
+        None => Aliases::default(),
+    };
     let mut feature_tracks = args
         .features
         .iter()
-        .map(|track| read_feature_file(&result, track))
+        .map(|track| read_feature_file(&result, &aliases, track))
         .collect::<Result<Vec<_>>>()?;
 
     if args.no_direct {
@@ -459,6 +495,19 @@ fn main() -> Result<()> {
         log::info!("Ignoring fragments {:?}", exclude_fragments);
         result.exclude_fragments(exclude_fragments);
     }
+    let mut regions = args.regions.clone();
+    if let Some(filename) = args.regions_file.as_ref() {
+        regions.extend(regions::read_bed(filename)?);
+    }
+    if !regions.is_empty() {
+        log::info!("Restricting to {} region(s)", regions.len());
+        let overlap = if args.both_arms {
+            RegionOverlap::BothArms
+        } else {
+            RegionOverlap::AnyArm
+        };
+        result.keep_regions(&regions, overlap, args.clip)?;
+    }
 
     result.families.iter_mut().for_each(|family| {
         family.retain(|sd| sd.left_length.max(sd.right_length) >= args.min_length)
@@ -469,15 +518,15 @@ fn main() -> Result<()> {
     });
 
     if let Some(filter_families) = args.filter_families {
-        filter_families_in_features(&mut result, &feature_tracks, filter_families);
+        filter_families_in_features(&mut result, &feature_tracks, filter_families)?;
     }
 
     if let Some(filter_duplicons) = args.filter_duplicons {
-        filter_duplicons_in_features(&mut result, &feature_tracks, filter_duplicons);
+        filter_duplicons_in_features(&mut result, &feature_tracks, filter_duplicons)?;
     }
 
     if let Some(filter_features) = args.filter_features {
-        filter_features_in_sds(&mut result, &mut feature_tracks, filter_features);
+        filter_features_in_sds(&mut result, &mut feature_tracks, filter_features)?;
     }
 
     let settings = Settings {
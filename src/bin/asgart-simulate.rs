@@ -0,0 +1,119 @@
+use std::{fs::File, io::BufWriter};
+
+use anyhow::{Context, Result};
+use clap::*;
+use log::*;
+
+use asgart::{
+    exporters::{Exporter, JSONExporter},
+    simulate::{Orientation, Placement, Simulation, SimulationSettings},
+};
+
+#[derive(Parser)]
+#[command(
+    name = "ASGART simulate",
+    version,
+    author,
+    about = "asgart-simulate generates a random genome containing known duplications, to be used as a ground truth."
+)]
+struct Args {
+    #[arg(short = 'o', long, default_value = "simulated")]
+    /// Prefix of the output files; the genome is written to PREFIX.fa and the
+    /// planted duplications to PREFIX.truth.json
+    out: String,
+
+    #[arg(long)]
+    /// Seed of the random generator; a random one is used if not set
+    seed: Option<u64>,
+
+    #[arg(long, default_value = "3")]
+    /// Number of fragments to generate
+    fragments: usize,
+
+    #[arg(long, default_value = "100000")]
+    /// Length of each fragment
+    fragment_length: usize,
+
+    #[arg(long, default_value = "10")]
+    /// Number of duplication families to plant
+    families: usize,
+
+    #[arg(long, num_args = 2, default_values = ["1000", "5000"])]
+    /// Minimal and maximal length of the planted duplicons
+    length: Vec<usize>,
+
+    #[arg(long, num_args = 2, default_values = ["2", "2"])]
+    /// Minimal and maximal number of members in a family
+    copies: Vec<usize>,
+
+    #[arg(long, default_value = "0.98")]
+    /// Expected proportion of unsubstituted nucleotides in copies
+    identity: f64,
+
+    #[arg(long, default_value = "0.001")]
+    /// Probability of an insertion or a deletion at each nucleotide of copies
+    indel_rate: f64,
+
+    #[arg(long, value_delimiter = ',', value_parser = ["direct", "R", "C", "RC"], default_value = "direct")]
+    /// Orientations in which the duplicons are copied, chosen at random
+    orientations: Vec<String>,
+
+    #[arg(long, value_parser = ["intra", "inter", "any"], default_value = "any")]
+    /// Whether copies should be placed on the fragment of their source, on
+    /// other fragments, or anywhere
+    placement: String,
+}
+
+fn main() -> Result<()> {
+    simple_logger::SimpleLogger::new()
+        .with_level(LevelFilter::Info)
+        .with_colors(true)
+        .init()
+        .context("failed to initialize simple_logger")?;
+
+    let args = Args::parse();
+
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let settings = SimulationSettings {
+        seed,
+        fragments: args.fragments,
+        fragment_length: args.fragment_length,
+        families: args.families,
+        length: (args.length[0], args.length[1]),
+        copies: (args.copies[0], args.copies[1]),
+        identity: args.identity,
+        indel_rate: args.indel_rate,
+        orientations: args
+            .orientations
+            .iter()
+            .map(|o| match o.as_str() {
+                "R" => Orientation::Reversed,
+                "C" => Orientation::Complemented,
+                "RC" => Orientation::ReverseComplemented,
+                _ => Orientation::Direct,
+            })
+            .collect(),
+        placement: match args.placement.as_str() {
+            "intra" => Placement::Intra,
+            "inter" => Placement::Inter,
+            _ => Placement::Any,
+        },
+    };
+    info!("Using seed {}", seed);
+
+    let simulation = Simulation::new(&settings)?;
+
+    let fasta_filename = format!("{}.fa", args.out);
+    simulation.write_fasta(&fasta_filename)?;
+    info!("Genome written to {}", fasta_filename);
+
+    let truth_filename = format!("{}.truth.json", args.out);
+    let mut out = BufWriter::new(
+        File::create(&truth_filename)
+            .with_context(|| format!("Unable to create `{}`", truth_filename))?,
+    );
+    JSONExporter.save(&simulation.truth, &mut out)?;
+    info!("Planted duplications written to {}", truth_filename);
+
+    Ok(())
+}
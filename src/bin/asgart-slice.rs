@@ -1,11 +1,21 @@
-use std::fs::File;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::*;
 use log::LevelFilter;
 
 use asgart::{
+    aliases::{self, Aliases},
+    collapse::CollapseRule,
     exporters::{self, Exporter},
+    filter::Filter,
+    importers,
+    liftover::{Liftover, Rejection},
+    plot::colorizers::*,
+    regions::{self, Region},
     structs::*,
 };
 
@@ -14,17 +24,47 @@ use asgart::{
     name = "ASGART slice",
     version,
     author,
-    about = "asgart-slice combines multiple ASGART JSON files into a single output file in the desired format, and features functions to filter, convert and collapse data."
+    about = "asgart-slice combines multiple ASGART files (either JSON or binary) into a single output file in the desired format, and features functions to filter, convert and collapse data."
 )]
 struct Args {
     #[arg()]
     /// The input file(s) to slice
     inputs: Vec<String>,
 
-    #[arg(short='f', long, value_parser=["json", "gff2", "gff3"], default_value="json")]
-    /// Set the desired output format
+    #[arg(long, value_parser = importers::FORMATS, requires = "genome")]
+    /// Read the inputs in this format rather than as ASGART files: the UCSC
+    /// `genomicSuperDups` table or WGAC output (`superdups`), SEDEF output
+    /// (`sedef`), BEDPE or PAF
+    import: Option<String>,
+
+    #[arg(long)]
+    /// A `.fai` or `.genome` file listing the lengths of the fragments the
+    /// imported duplications stand on
+    genome: Option<String>,
+
+    #[arg(short='f', long, value_parser=["json", "bin", "gff2", "gff3", "bedpe", "bed9", "bed12", "paf", "vcf", "chain", "tsv", "csv", "gfa", "sqlite"], default_value="json")]
+    /// Set the desired output format; `bin` is a compact binary format that
+    /// can be read back by all ASGART tools
     format: String,
 
+    #[arg(long, value_delimiter = ',')]
+    /// The comma-separated columns to write in TSV and CSV outputs, among
+    /// family, family_size, member, chr_left, left_position,
+    /// global_left_position, left_length, chr_right, right_position,
+    /// global_right_position, right_length, identity, orientation, left_seq
+    /// and right_seq; with --per-arm, among family, family_size, member, arm,
+    /// chr, position, global_position, length, identity, orientation and seq.
+    /// Defaults to all of them but the sequences
+    columns: Vec<String>,
+
+    #[arg(long)]
+    /// Write one row per arm rather than per duplicon in TSV and CSV outputs
+    per_arm: bool,
+
+    #[arg(long, value_parser = ["by-type", "by-position", "by-fragment", "none"], default_value = "by-type")]
+    /// Criterion on which to colorize duplicons in BED9 and BED12 outputs
+    colorize: String,
+
     #[arg(short = 'o', long)]
     /// If specified, write the result to this file; otherwise, write to STDOUT
     output: Option<String>,
@@ -45,6 +85,38 @@ struct Args {
     /// Filter out non-complemented duplications
     no_uncomplemented: bool,
 
+    #[arg(long, value_name = "FILE")]
+    /// Rename the fragments after this alias table, either an NCBI assembly
+    /// report or a tabulated file whose first column is a name and the second
+    /// one the name to rename it to; applied before the other filters
+    aliases: Option<String>,
+
+    #[arg(long, value_parser = aliases::NAMINGS, default_value = "ucsc", requires = "aliases")]
+    /// The naming convention to rename the fragments to when --aliases is an
+    /// NCBI assembly report
+    naming: String,
+
+    #[arg(long, value_name = "FILE.chain", requires = "target_genome")]
+    /// Lift the duplicons over to another assembly through this UCSC chain
+    /// file, whose targets are the fragments of the results; applied after
+    /// --aliases and before the other filters
+    liftover: Option<String>,
+
+    #[arg(long, value_name = "FILE.fai")]
+    /// A `.fai` or `.genome` file listing the fragments of the assembly to
+    /// lift the duplicons over to
+    target_genome: Option<String>,
+
+    #[arg(long, default_value = "0.05")]
+    /// Reject the duplicons having an arm whose length changes by more than
+    /// this fraction when lifted over
+    liftover_tolerance: f64,
+
+    #[arg(long, value_name = "FILE", requires = "liftover")]
+    /// Write the duplicons that could not be lifted over to this JSON file, in
+    /// their original coordinates
+    liftover_rejects: Option<String>,
+
     #[arg(short = 'M', long)]
     /// Skip families with more duplicons than specified
     max_family_members: Option<usize>,
@@ -66,12 +138,34 @@ struct Args {
     /// Filter duplicons shorter than the given value
     min_length: Option<usize>,
 
+    #[arg(long = "where", value_name = "EXPR")]
+    /// Only keep the duplicons satisfying this expression, e.g.
+    /// `identity > 0.95 && (chr_left =~ '^chrY' || reversed)`; see the
+    /// README for the available variables and operators
+    where_duplicons: Option<String>,
+
+    #[arg(long = "where-family", value_name = "EXPR")]
+    /// Only keep the families satisfying this expression, e.g.
+    /// `family_size <= 20`; applied before --where
+    where_families: Option<String>,
+
     #[arg(short = 'C', long)]
     /// Merge all the smaller-than-average-plus-one-sigma fragments into a
     /// single one (useful to deal with datasets containing large numbers of
-    /// small fragments)
+    /// small fragments); shorthand for `--collapse-bin auto`
     collapse: bool,
 
+    #[arg(long = "collapse-bin", value_name = "[BIN=]CRITERION")]
+    /// Merge the fragments matching CRITERION, i.e. `auto`, `length<N`,
+    /// `regex:REGEX` or `names:A,B,...`, into the bin BIN (by default
+    /// ASGART_COLLAPSED); may be repeated, the first matching rule winning
+    collapse_bins: Vec<CollapseRule>,
+
+    #[arg(long)]
+    /// Restore the original fragments of collapsed results before applying
+    /// any other filter
+    uncollapse: bool,
+
     #[arg(long)]
     /// Ignore all duplicons not having at least an arm in a fragment in the
     /// given list
@@ -88,8 +182,30 @@ struct Args {
     #[arg(short = 'E', long)]
     /// Use regexp matching instead of literal for keep- and exclude-fragments
     regexp: bool,
+
+    #[arg(long = "region", value_name = "CHR:START-END")]
+    /// Ignore all duplicons not having at least an arm overlapping this
+    /// 0-based, half-open region; may be repeated
+    regions: Vec<Region>,
+
+    #[arg(long = "regions", value_name = "FILE.bed")]
+    /// Ignore all duplicons not having at least an arm overlapping a region
+    /// listed in this BED file
+    regions_file: Option<String>,
+
+    #[arg(long)]
+    /// Require both arms of the duplicons to overlap the regions
+    both_arms: bool,
+
+    #[arg(long)]
+    /// Replace the fragments by the regions, clipping the duplicons to them;
+    /// implies --both-arms
+    clip: bool,
 }
 
+/// How many families are filtered at once while streaming the input
+const BATCH_SIZE: usize = 10_000;
+
 fn main() -> Result<()> {
     simple_logger::SimpleLogger::new()
         .with_level(LevelFilter::Info)
@@ -99,32 +215,232 @@ fn main() -> Result<()> {
 
     let args = Args::parse();
 
-    let mut results = if !args.inputs.is_empty() {
-        RunResult::from_files(&args.inputs)?
+    let mut families = if let Some(format) = args.import.as_ref() {
+        let genome = args.genome.as_ref().unwrap();
+        if !args.inputs.is_empty() {
+            importers::import_files(format, &args.inputs, genome)?.into()
+        } else {
+            log::warn!("Reading results from STDIN");
+            importers::import_stdin(format, genome)?.into()
+        }
+    } else if !args.inputs.is_empty() {
+        RunResult::stream_files(&args.inputs)?
     } else {
         log::warn!("Reading results from STDIN");
-        RunResult::from_stdin()?
+        RunResult::stream_stdin()?
     };
 
-    let mut out: Box<dyn std::io::Write> = if let Some(output) = args.output.as_ref() {
-        let out_filename = asgart::utils::make_out_filename(Some(output), "out", &args.format);
-        Box::new(File::create(out_filename)?)
+    let mut out: Box<dyn Write> = if let Some(output) = args.output.as_ref() {
+        let extension = match args.format.as_str() {
+            "bin" => "asgb",
+            "bed9" | "bed12" => "bed",
+            format => format,
+        };
+        let out_filename = asgart::utils::make_out_filename(Some(output), "out", extension);
+        Box::new(BufWriter::new(File::create(out_filename)?))
     } else {
-        Box::new(std::io::stdout())
+        Box::new(BufWriter::new(std::io::stdout()))
     };
 
-    let exporter = match args.format.as_str() {
-        "json" => Box::new(exporters::JSONExporter) as Box<dyn Exporter>,
-        "gff2" => Box::new(exporters::GFF2Exporter) as Box<dyn Exporter>,
-        "gff3" => Box::new(exporters::GFF3Exporter) as Box<dyn Exporter>,
-        format @ _ => {
-            log::warn!("Unknown output format `{}`: using json instead", format);
-            Box::new(exporters::JSONExporter) as Box<dyn Exporter>
+    let mut data = FilterData {
+        aliases: args
+            .aliases
+            .as_ref()
+            .map(|filename| Aliases::from_file(filename, &args.naming))
+            .transpose()?,
+        regions: args.regions.clone(),
+        liftover: None,
+        collapse: args.collapse_bins.clone(),
+    };
+    if args.collapse {
+        data.collapse.push("auto".parse()?);
+    }
+    if let Some(filename) = args.regions_file.as_ref() {
+        data.regions.extend(regions::read_bed(filename)?);
+    }
+    if let Some(filename) = args.liftover.as_ref() {
+        if args.liftover_tolerance < 0.0 {
+            bail!("--liftover-tolerance should be non-negative");
+        }
+        data.liftover = Some((
+            Liftover::from_file(filename)?,
+            importers::read_genome(args.target_genome.as_ref().unwrap())?,
+        ));
+    }
+
+    // Rejected duplicons are written with the fragments they come from
+    let mut rejects = match args.liftover_rejects.as_ref() {
+        Some(filename) => {
+            let mut source = RunResult {
+                strand: families.strand.clone(),
+                settings: families.settings.clone(),
+                families: Vec::new(),
+            };
+            if args.uncollapse {
+                source.unflatten()?;
+            }
+            if let Some(aliases) = data.aliases.as_ref() {
+                source.rename_fragments(aliases)?;
+            }
+            let mut out = BufWriter::new(
+                File::create(filename)
+                    .with_context(|| format!("Unable to create `{}`", filename))?,
+            );
+            exporters::JSONExporter.begin(&source.strand, &source.settings, &mut out)?;
+            Some(out)
         }
+        None => None,
     };
+    let mut rejected = (0, 0, 0);
+    let mut rejected_families = 0;
 
-    if args.collapse {
-        results.flatten();
+    // Fragment-level filters only depend on the fragments map, so that it can
+    // be computed once and for all before streaming the families in batches
+    let mut header = RunResult {
+        strand: families.strand.clone(),
+        settings: families.settings.clone(),
+        families: Vec::new(),
+    };
+    filter(&args, &data, &mut header)?;
+
+    let mut exporter =
+        match args.format.as_str() {
+            "json" => Box::new(exporters::JSONExporter) as Box<dyn Exporter>,
+            "bin" => Box::new(exporters::BinaryExporter::new()) as Box<dyn Exporter>,
+            "gff2" => Box::new(exporters::GFF2Exporter) as Box<dyn Exporter>,
+            "gff3" => Box::new(exporters::GFF3Exporter) as Box<dyn Exporter>,
+            "bedpe" => Box::new(exporters::BEDPEExporter) as Box<dyn Exporter>,
+            "paf" => Box::new(exporters::PAFExporter::new()) as Box<dyn Exporter>,
+            "vcf" => Box::new(exporters::VCFExporter) as Box<dyn Exporter>,
+            "chain" => Box::new(exporters::ChainExporter::new()) as Box<dyn Exporter>,
+            "gfa" => Box::new(exporters::GFAExporter::new()) as Box<dyn Exporter>,
+            "sqlite" => Box::new(exporters::SQLiteExporter::new()) as Box<dyn Exporter>,
+            "tsv" => Box::new(exporters::DelimitedExporter::new(
+                '\t',
+                &args.columns,
+                args.per_arm,
+            )?) as Box<dyn Exporter>,
+            "csv" => Box::new(exporters::DelimitedExporter::new(
+                ',',
+                &args.columns,
+                args.per_arm,
+            )?) as Box<dyn Exporter>,
+            "bed9" => Box::new(exporters::BEDExporter::bed9(colorizer(&args, &header)))
+                as Box<dyn Exporter>,
+            "bed12" => Box::new(exporters::BEDExporter::bed12(colorizer(&args, &header)))
+                as Box<dyn Exporter>,
+            format => {
+                log::warn!("Unknown output format `{}`: using json instead", format);
+                Box::new(exporters::JSONExporter) as Box<dyn Exporter>
+            }
+        };
+    exporter.begin(&header.strand, &header.settings, &mut out)?;
+
+    let mut i = 0;
+    loop {
+        let batch = families
+            .by_ref()
+            .take(BATCH_SIZE)
+            .collect::<Result<Vec<_>>>()?;
+        if batch.is_empty() {
+            break;
+        }
+
+        let mut results = RunResult {
+            strand: families.strand.clone(),
+            settings: families.settings.clone(),
+            families: batch,
+        };
+        let batch_rejects = filter(&args, &data, &mut results)?;
+        for family in &results.families {
+            exporter.family(i, family, &mut out)?;
+            i += 1;
+        }
+
+        for (_, _, rejection) in &batch_rejects {
+            match rejection {
+                Rejection::Unmapped { .. } => rejected.0 += 1,
+                Rejection::UnknownFragment(_) => rejected.1 += 1,
+                Rejection::LengthChanged { .. } => rejected.2 += 1,
+            }
+        }
+        if let Some(rejects) = rejects.as_mut() {
+            let mut by_family: Vec<SDsFamily> = Vec::new();
+            let mut last = None;
+            for (family, sd, _) in batch_rejects {
+                if last != Some(family) {
+                    by_family.push(Vec::new());
+                    last = Some(family);
+                }
+                by_family.last_mut().unwrap().push(sd);
+            }
+            for family in &by_family {
+                exporters::JSONExporter.family(rejected_families, family, rejects)?;
+                rejected_families += 1;
+            }
+        }
+    }
+    exporter.end(&mut out)?;
+    out.flush()?;
+
+    if data.liftover.is_some() {
+        let (unmapped, unknown, length) = rejected;
+        if unmapped + unknown + length > 0 {
+            log::warn!(
+                "{} duplicons could not be lifted over: {} not mapped, {} on fragments missing from the target genome, {} changing length",
+                unmapped + unknown + length,
+                unmapped,
+                unknown,
+                length
+            );
+        }
+    }
+    if let Some(mut rejects) = rejects {
+        exporters::JSONExporter.end(&mut rejects)?;
+        rejects.flush()?;
+    }
+
+    Ok(())
+}
+
+fn colorizer(args: &Args, header: &RunResult) -> Box<dyn Colorizer> {
+    match args.colorize.as_str() {
+        "by-position" => Box::new(PositionColorizer::new(header)),
+        "by-fragment" => Box::new(FragmentColorizer::new(header)),
+        "none" => Box::new(TypeColorizer::new((0.5, 0.5, 0.5), (0.5, 0.5, 0.5))),
+        _ => Box::new(TypeColorizer::new((1.0, 0.36, 0.0), (0.0, 0.70, 0.68))),
+    }
+}
+
+/// The data the filters read from files, loaded once and for all
+struct FilterData {
+    aliases: Option<Aliases>,
+    regions: Vec<Region>,
+    /// The chains to lift the duplicons over with, and the target genome
+    liftover: Option<(Liftover, StrandResult)>,
+    /// The rules to collapse fragments with
+    collapse: Vec<CollapseRule>,
+}
+
+/// Apply the filters set in `args` to `results`, and return the duplicons
+/// that could not be lifted over, with the index of their family
+fn filter(
+    args: &Args,
+    data: &FilterData,
+    results: &mut RunResult,
+) -> Result<Vec<(usize, SD, Rejection)>> {
+    if args.uncollapse {
+        results.unflatten()?;
+    }
+    if let Some(aliases) = data.aliases.as_ref() {
+        results.rename_fragments(aliases)?;
+    }
+    let rejected = match data.liftover.as_ref() {
+        Some((liftover, target)) => results.liftover(liftover, target, args.liftover_tolerance),
+        None => Vec::new(),
+    };
+    if !data.collapse.is_empty() {
+        results.flatten(&data.collapse)?;
     }
     if args.no_direct {
         results.remove_direct();
@@ -156,6 +472,12 @@ fn main() -> Result<()> {
     if let Some(max_family_members) = args.max_family_members {
         results.max_family_members(max_family_members);
     }
+    if let Some(expr) = args.where_families.as_ref() {
+        results.filter_families(&Filter::families(expr)?);
+    }
+    if let Some(expr) = args.where_duplicons.as_ref() {
+        results.filter_duplicons(&Filter::duplicons(expr)?);
+    }
     if let Some(keep_fragments) = args.keep_fragments.as_ref() {
         if args.regexp {
             for keep_fragment in keep_fragments {
@@ -189,7 +511,14 @@ fn main() -> Result<()> {
             results.exclude_fragments(exclude_fragments);
         }
     }
+    if !data.regions.is_empty() {
+        let overlap = if args.both_arms {
+            RegionOverlap::BothArms
+        } else {
+            RegionOverlap::AnyArm
+        };
+        results.keep_regions(&data.regions, overlap, args.clip)?;
+    }
 
-    exporter.save(&results, &mut out)?;
-    Ok(())
+    Ok(rejected)
 }
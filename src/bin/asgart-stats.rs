@@ -0,0 +1,157 @@
+use anyhow::{bail, Context, Result};
+use clap::*;
+use log::*;
+
+use asgart::{
+    stats::{Bin, Stats},
+    structs::RunResult,
+};
+
+#[derive(Parser)]
+#[command(
+    name = "ASGART stats",
+    version,
+    author,
+    about = "asgart-stats summarizes ASGART files (either JSON or binary): the coverage of each fragment by duplications, the distribution of their length and identity, and the breakdown of their families and orientations."
+)]
+struct Args {
+    #[arg()]
+    /// The input file(s) to summarize; if none, read from STDIN
+    inputs: Vec<String>,
+
+    #[arg(short = 'f', long, default_value = "text", value_parser = ["text", "json"])]
+    /// The format to print the statistics in
+    format: String,
+
+    #[arg(long)]
+    /// Do not report the coverage of each fragment
+    no_fragments: bool,
+}
+
+fn percent(x: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        100.0 * x as f64 / total as f64
+    }
+}
+
+fn print_histogram(title: &str, bins: &[Bin], unit: &str, total: usize) {
+    println!("{:<24} {:>10} {:>8}", title, "Count", "%");
+    for bin in bins {
+        let range = match bin.to {
+            Some(to) => format!("[{}{}, {}{}[", bin.from, unit, to, unit),
+            None => format!(">= {}{}", bin.from, unit),
+        };
+        println!(
+            "{:<24} {:>10} {:>8.2}",
+            range,
+            bin.count,
+            percent(bin.count, total)
+        );
+    }
+}
+
+fn print_text(stats: &Stats, fragments: bool) {
+    let total = stats.duplicons;
+    println!("{:<16}{:>10}", "Families:", stats.families);
+    println!("{:<16}{:>10}", "Duplicons:", total);
+    for (name, count) in [
+        ("Intra", stats.intra),
+        ("Inter", stats.inter),
+        ("Direct", stats.direct),
+        ("Reversed", stats.reversed),
+        ("Complemented", stats.complemented),
+        ("Palindromic", stats.palindromic),
+    ] {
+        println!(
+            "{:<16}{:>10} ({:.2}%)",
+            format!("  {}:", name),
+            count,
+            percent(count, total)
+        );
+    }
+    println!(
+        "{:<16}{:>10}/{}bp ({:.2}%)",
+        "Coverage:",
+        stats.covered,
+        stats.genome_length,
+        percent(stats.covered, stats.genome_length)
+    );
+
+    if fragments {
+        println!();
+        println!(
+            "{:<24} {:>12} {:>8} {:>12} {:>8}",
+            "Fragment", "Length", "Arms", "Covered", "%"
+        );
+        for f in &stats.fragments {
+            println!(
+                "{:<24} {:>12} {:>8} {:>12} {:>8}",
+                f.name,
+                f.length
+                    .map(|l| l.to_string())
+                    .unwrap_or_else(|| "?".into()),
+                f.arms,
+                f.covered,
+                f.length
+                    .map(|l| format!("{:.2}", percent(f.covered, l)))
+                    .unwrap_or_else(|| "?".into())
+            );
+        }
+    }
+
+    println!();
+    print_histogram("Length", &stats.lengths, "bp", total);
+    println!();
+    print_histogram("Identity", &stats.identities, "%", total);
+    if stats.unknown_identity > 0 {
+        println!(
+            "{:<24} {:>10} {:>8.2}",
+            "Unknown",
+            stats.unknown_identity,
+            percent(stats.unknown_identity, total)
+        );
+    }
+
+    println!();
+    println!("{:<24} {:>10} {:>8}", "Family size", "Count", "%");
+    for (size, count) in &stats.family_sizes {
+        println!(
+            "{:<24} {:>10} {:>8.2}",
+            size,
+            count,
+            percent(*count, stats.families)
+        );
+    }
+}
+
+fn main() -> Result<()> {
+    simple_logger::SimpleLogger::new()
+        .with_level(LevelFilter::Info)
+        .with_colors(true)
+        .init()
+        .context("failed to initialize simple_logger")?;
+
+    let args = Args::parse();
+    let families = if !args.inputs.is_empty() {
+        RunResult::stream_files(&args.inputs)?
+    } else {
+        warn!("Reading results from STDIN");
+        RunResult::stream_stdin()?
+    };
+    let mut stats = Stats::from_stream(families)?;
+
+    match args.format.as_str() {
+        "text" => print_text(&stats, !args.no_fragments),
+        "json" => {
+            if args.no_fragments {
+                stats.fragments.clear();
+            }
+            println!("{}", serde_json::to_string_pretty(&stats)?);
+        }
+        format => bail!("Unknown format `{}`", format),
+    }
+
+    Ok(())
+}
@@ -9,8 +9,8 @@ use std::{
     time::{Duration, Instant},
 };
 
-use anyhow::{Context, Result};
-use clap::*;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{builder::PossibleValuesParser, *};
 use console::style;
 use indicatif::{HumanDuration, ProgressBar, ProgressStyle};
 use log::*;
@@ -19,8 +19,12 @@ use thousands::Separable;
 
 use asgart::{
     automaton,
+    config::{Config, PRESETS},
     divsufsort::{divsufsort64, SuffixArray},
-    exporters, searcher,
+    exporters,
+    jobs::{self, Job, Manifest},
+    regions::{self, Region},
+    searcher,
     structs::*,
     utils,
 };
@@ -59,7 +63,7 @@ impl Step for Sort {
     fn run(&self, mut input: Vec<ProtoSDsFamily>, _strand: &Strand) -> Vec<ProtoSDsFamily> {
         input
             .iter_mut()
-            .for_each(|family| family.sort_by(|a, b| (a.left).cmp(&b.left)));
+            .for_each(|family| family.sort_by_key(|a| a.left));
         input
     }
 }
@@ -78,16 +82,18 @@ impl Step for ReduceOverlap {
     }
 }
 
-struct FilterNs;
+struct FilterNs {
+    max_n_content: f32,
+}
 impl Step for FilterNs {
     fn name(&self) -> &str {
         "Filtering uncertain duplications"
     }
 
     fn run(&self, mut input: Vec<ProtoSDsFamily>, strand: &Strand) -> Vec<ProtoSDsFamily> {
-        input
-            .par_iter_mut()
-            .for_each(|family| family.retain(|sd| sd.n_content(&strand.data) <= 0.2));
+        input.par_iter_mut().for_each(|family| {
+            family.retain(|sd| sd.n_content(&strand.data) <= self.max_n_content)
+        });
         input
             .into_iter()
             .filter(|family| !family.is_empty())
@@ -115,17 +121,24 @@ struct SearchDuplications<'a> {
     chunks_to_process: &'a [(usize, usize)],
     trim: Option<(usize, usize)>,
     settings: RunSettings,
+    suffix_array: Option<Arc<SuffixArray>>,
+    // Whether only a part of the strand is probed
+    restricted: bool,
 }
 impl SearchDuplications<'_> {
     fn new(
         chunks_to_process: &[(usize, usize)],
         trim: Option<(usize, usize)>,
         settings: RunSettings,
-    ) -> SearchDuplications {
+        suffix_array: Option<SuffixArray>,
+        restricted: bool,
+    ) -> SearchDuplications<'_> {
         SearchDuplications {
             chunks_to_process,
             trim,
             settings,
+            suffix_array: suffix_array.map(Arc::new),
+            restricted,
         }
     }
 }
@@ -139,7 +152,9 @@ impl<'a> Step for SearchDuplications<'a> {
         //
         debug!("Building suffix array");
         let sa_build_time = Instant::now();
-        let shared_suffix_array = if let Some((start, end)) = self.trim {
+        let shared_suffix_array = if let Some(suffix_array) = self.suffix_array.as_ref() {
+            Arc::clone(suffix_array)
+        } else if let Some((start, end)) = self.trim {
             let mut sub_strand = strand.data[start..end].to_vec();
             sub_strand.push(b'$');
             let mut suffix_array = r_divsufsort(&sub_strand);
@@ -224,7 +239,8 @@ impl<'a> Step for SearchDuplications<'a> {
                     &shared_suffix_array.clone(),
                     &shared_searcher.clone(),
                     &progresses[id],
-                    self.settings,
+                    &self.settings,
+                    self.restricted.then_some(self.chunks_to_process),
                 );
                 proto_sds_families.iter_mut().for_each(|proto_family| {
                     proto_family.iter_mut().for_each(|proto_sd| {
@@ -254,6 +270,14 @@ impl<'a> Step for SearchDuplications<'a> {
 
         let _ = tx_monitor.send(());
         monitor_thread.join().unwrap();
+        let overflowing = shared_searcher.overflowing();
+        if overflowing > 0 {
+            warn!(
+                "Skipped {} probes standing for more than {} concrete ones",
+                overflowing.separate_with_spaces(),
+                searcher::MAX_EXPANSIONS
+            );
+        }
         result
     }
 }
@@ -270,11 +294,7 @@ struct Strand {
     pub map: Vec<Start>,
 }
 
-fn prepare_data(
-    strands_files: &[String],
-    skip_masked: bool,
-    trim: Option<(usize, usize)>,
-) -> Result<PreparedData> {
+fn prepare_data(strands_files: &[String], settings: &RunSettings) -> Result<PreparedData> {
     fn read_fasta(filename: &str, skip_masked: bool) -> Result<(Vec<Start>, Vec<u8>)> {
         let mut map = Vec::new();
         let mut r = Vec::new();
@@ -294,7 +314,7 @@ fn prepare_data(
             for c in &mut seq {
                 if ALPHABET_MASKED.contains(c) && skip_masked {
                     *c = b'N'
-                } else if !(ALPHABET).contains(c) {
+                } else if !ALPHABET.contains(c) && !AMBIGUITY_CODES.contains(c) {
                     trace!("Undefined base `{}` replaced by `N`", *c as char);
                     *c = b'N'
                 }
@@ -304,6 +324,7 @@ fn prepare_data(
                 name,
                 position: counter,
                 length: seq.len(),
+                checksum: None,
             });
             counter += seq.len();
             r.append(&mut seq);
@@ -314,7 +335,7 @@ fn prepare_data(
 
     // Given a DNA fragment, returns a list of segments without too many N's in them
     // Coordinates are relative to the fragment.
-    fn find_chunks_to_process(strand: &[u8]) -> Vec<(usize, usize)> {
+    fn find_chunks_to_process(strand: &[u8], threshold: usize) -> Vec<(usize, usize)> {
         fn count_n(strand: &[u8], start: usize) -> usize {
             strand
                 .iter()
@@ -323,7 +344,6 @@ fn prepare_data(
                 .count()
         }
 
-        let threshold = 5000;
         let mut start = 0;
         let mut count = 0;
         let mut chunks = Vec::new();
@@ -372,21 +392,53 @@ fn prepare_data(
     let mut offset = 0;
     let mut chunks_to_process = Vec::new();
 
+    let mut masked = 0;
+
     for file_name in strands_files {
-        let (map, new_strand) = read_fasta(file_name, skip_masked)
+        let (map, mut new_strand) = read_fasta(file_name, settings.skip_masked)
             .with_context(|| format!("Unable to parse `{}`", file_name))?;
 
+        // Hard-mask the excluded regions before looking for the chunks to process
+        if let Some(excluded) = settings.excluded.as_ref() {
+            for chr in map.iter() {
+                let mut spans = Vec::new();
+                for r in excluded.iter().filter(|r| r.chr == chr.name) {
+                    if r.end > chr.length {
+                        bail!(
+                            "Excluded region {}:{}-{} extends beyond the end of `{}` ({}bp)",
+                            r.chr,
+                            r.start,
+                            r.end,
+                            chr.name,
+                            chr.length
+                        );
+                    }
+                    new_strand[chr.position + r.start..chr.position + r.end]
+                        .iter_mut()
+                        .for_each(|n| *n = b'N');
+                    spans.push((r.start, r.end));
+                }
+                masked += covered_length(spans);
+            }
+        }
+
         // We want to add each fragment separately to ensure that chunks are cutting
         // between fragments
         for chr in map.iter() {
             chunks_to_process.extend(
-                find_chunks_to_process(&new_strand[chr.position..chr.position + chr.length])
-                    .into_iter()
-                    .map(|(start, length)| (chr.position + offset + start, length)),
+                find_chunks_to_process(
+                    &new_strand[chr.position..chr.position + chr.length],
+                    settings.n_threshold,
+                )
+                .into_iter()
+                .map(|(start, length)| (chr.position + offset + start, length)),
             );
         }
         maps.extend(map.into_iter().map(|start| Start {
             position: start.position + offset,
+            checksum: Some(utils::fnv1a(
+                &new_strand[start.position..start.position + start.length],
+            )),
             ..start
         }));
 
@@ -399,6 +451,20 @@ fn prepare_data(
         if strands_files.len() > 1 { "s" } else { "" },
         maps.len()
     );
+    if let Some(excluded) = settings.excluded.as_ref() {
+        if let Some(r) = excluded
+            .iter()
+            .find(|r| !maps.iter().any(|chr| chr.name == r.chr))
+        {
+            bail!("Unable to find fragment `{}`", r.chr);
+        }
+        info!(
+            "Masked {} excluded region{} totalling {}bp",
+            excluded.len().separate_with_spaces(),
+            if excluded.len() > 1 { "s" } else { "" },
+            masked.separate_with_spaces()
+        );
+    }
     maps.iter().for_each(|s| {
         debug!(
             "{:>20}: {:>15}  --> {:>15}    {:>15} bp",
@@ -409,6 +475,15 @@ fn prepare_data(
         )
     });
 
+    if let Some(regions) = settings.regions.as_ref() {
+        chunks_to_process = restrict_chunks(&chunks_to_process, regions, &maps)?;
+        info!(
+            "Restricting probes to {} region{}",
+            regions.len(),
+            if regions.len() > 1 { "s" } else { "" }
+        );
+    }
+
     let chunks_length = chunks_to_process.iter().fold(0, |ax, c| ax + c.1);
     info!(
         "Processing {} chunks totalling {}bp, skipping {}bp out of {} ({}%)",
@@ -430,7 +505,7 @@ fn prepare_data(
     strand.push(b'$'); // For the SA construction
 
     Ok((
-        trim.and_then(|(shift, _stop)| {
+        settings.trim.and_then(|(shift, _stop)| {
             // Ensure that shift & stop actually stay in the dataset
             //
             let mut stop = _stop;
@@ -470,6 +545,72 @@ fn prepare_data(
     ))
 }
 
+// Returns the total length covered by the given (start, end) intervals
+fn covered_length(mut intervals: Vec<(usize, usize)>) -> usize {
+    intervals.sort_unstable();
+    let mut total = 0;
+    let mut covered_until = 0;
+    for (start, end) in intervals {
+        let start = start.max(covered_until);
+        if end > start {
+            total += end - start;
+            covered_until = end;
+        }
+    }
+    total
+}
+
+// Only keep the parts of the chunks to process that lay within the given
+// fragment-relative regions
+fn restrict_chunks(
+    chunks: &[(usize, usize)],
+    regions: &[Region],
+    map: &[Start],
+) -> Result<Vec<(usize, usize)>> {
+    let mut intervals = regions
+        .iter()
+        .map(|r| {
+            let chr = map
+                .iter()
+                .find(|c| c.name == r.chr)
+                .ok_or_else(|| anyhow!("Unable to find fragment `{}`", r.chr))?;
+            if r.end > chr.length {
+                bail!(
+                    "Region {}:{}-{} extends beyond the end of `{}` ({}bp)",
+                    r.chr,
+                    r.start,
+                    r.end,
+                    chr.name,
+                    chr.length
+                );
+            }
+            Ok((chr.position + r.start, chr.position + r.end))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Merge overlapping regions so that no probe is processed twice
+    intervals.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    let mut r = Vec::new();
+    for &(start, length) in chunks {
+        let end = start + length;
+        for &(r_start, r_end) in &merged {
+            let (new_start, new_end) = (start.max(r_start), end.min(r_end));
+            if new_start < new_end {
+                r.push((new_start, new_end - new_start));
+            }
+        }
+    }
+    Ok(r)
+}
+
 pub fn r_divsufsort(dna: &[u8]) -> SuffixArray {
     let mut sa = vec![0; dna.len()];
     unsafe {
@@ -566,68 +707,199 @@ fn reduce_overlap(result: &[ProtoSD]) -> Vec<ProtoSD> {
     name = "ASGART",
     version,
     author,
-    about = "A Segmental duplications Gathering and Refinement Tool"
+    about = "A Segmental duplications Gathering and Refinement Tool",
+    args_conflicts_with_subcommands = true
 )]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    search: SearchArgs,
+
+    #[command(flatten)]
+    verbose: clap_verbosity_flag::Verbosity,
+
+    #[arg(long, default_value = "")]
+    /// prefix to prepend to the default output file name
+    prefix: String,
+
+    #[arg(long)]
+    /// set the output file name
+    out: Option<String>,
+
+    #[arg(long)]
+    /// Load the suffix array from this index, as built by `asgart plan`,
+    /// instead of building it
+    index: Option<String>,
+
+    #[arg(long)]
+    /// Only probe the fragments that are new or have changed since this
+    /// previous result, and reuse the duplications standing on the others
+    incremental: Option<String>,
+
+    #[arg(long, default_value = "1000000")]
+    /// Size used to slice input data for parallel processing
+    chunk_size: usize,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Split a search into balanced jobs sharing a single index
+    Plan {
+        #[command(flatten)]
+        search: Box<SearchArgs>,
+
+        #[arg(short = 'j', long, value_parser = clap::value_parser!(u64).range(1..))]
+        /// How many jobs to create
+        jobs: u64,
+
+        #[arg(short = 'o', long, default_value = "asgart-jobs")]
+        /// The directory where to write the index, the jobs and their manifest
+        out_dir: String,
+    },
+
+    /// Merge the results of the jobs of a plan
+    Merge {
+        #[arg()]
+        /// The manifest of the plan, as written by `asgart plan`
+        manifest: String,
+
+        #[arg(short = 'o', long)]
+        /// set the output file name; default to `merged.json` next to the manifest
+        out: Option<String>,
+    },
+}
+
+#[derive(clap::Args)]
+struct SearchArgs {
     #[arg()]
     /// The files to process
     strands: Vec<String>,
 
-    #[command(flatten)]
-    verbose: clap_verbosity_flag::Verbosity,
+    #[arg(short = 'c', long)]
+    /// Read the run settings from this TOML file; command-line arguments take
+    /// precedence over it
+    config: Option<String>,
+
+    #[arg(long, value_parser = PossibleValuesParser::new(PRESETS.iter().map(|(name, _)| name)))]
+    /// Use the settings of this preset for those that are not otherwise set
+    preset: Option<String>,
 
-    #[arg(long, default_value = "1000")]
-    /// Minimal lenngth (in bp) of the dpulications to be reported
-    min_length: usize,
+    #[arg(long)]
+    /// Minimal lenngth (in bp) of the dpulications to be reported [default: 1000]
+    min_length: Option<usize>,
 
-    #[arg(short = 'k', long, default_value = "20")]
-    /// Probing k-mers size
-    probe_size: usize,
+    #[arg(short = 'k', long)]
+    /// Probing k-mers size [default: 20]
+    probe_size: Option<usize>,
 
-    #[arg(short = 'g', long, default_value = "100")]
-    /// Maximum length of a gap
-    gap_size: usize,
+    #[arg(short = 'g', long)]
+    /// Maximum length of a gap [default: 100]
+    gap_size: Option<usize>,
 
-    #[arg(short = 'R', long)]
+    #[arg(short = 'R', long, overrides_with = "no_reverse")]
     /// Search for reversed duplications
     reverse: bool,
 
-    #[arg(short = 'C', long)]
+    #[arg(long, overrides_with = "reverse")]
+    /// Do not search for reversed duplications, whatever the configuration
+    no_reverse: bool,
+
+    #[arg(short = 'C', long, overrides_with = "no_complement")]
     /// Search for complemented duplications
     complement: bool,
 
-    #[arg(short = 'S', long)]
+    #[arg(long, overrides_with = "complement")]
+    /// Do not search for complemented duplications, whatever the
+    /// configuration
+    no_complement: bool,
+
+    #[arg(short = 'S', long, overrides_with = "no_skip_masked")]
     /// Ignore soft-masked repeated zones (lowercased regions)
     skip_masked: bool,
 
+    #[arg(long, overrides_with = "skip_masked")]
+    /// Search soft-masked zones, whatever the configuration
+    no_skip_masked: bool,
+
+    #[arg(long, value_parser = ["mismatch", "wildcard"])]
+    /// How to handle probes containing IUPAC ambiguity codes: either skip
+    /// them, or let the codes match any of the nucleotides they stand for
+    /// [default: mismatch]
+    ambiguity: Option<String>,
+
     #[arg(long, num_args = 2)]
     /// Trim the first strand
     trim: Option<Vec<usize>>,
 
-    #[arg(long, default_value = "500")]
-    /// maximal cardinality of duplication families
-    max_cardinality: usize,
-
-    #[arg(long, default_value = "")]
-    /// prefix to prepend to the default output file name
-    prefix: String,
+    #[arg(long)]
+    /// Only probe for duplications in the fragment-relative intervals listed
+    /// in this BED file; duplicons are still searched for in whole genome
+    regions: Option<String>,
 
     #[arg(long)]
-    /// set the output file name
-    out: Option<String>,
+    /// Hard-mask the fragment-relative intervals listed in this BED file, so
+    /// that they are neither probed nor matched
+    exclude: Option<String>,
 
     #[arg(long)]
+    /// maximal cardinality of duplication families [default: 500]
+    max_cardinality: Option<usize>,
+
+    #[arg(long, overrides_with = "no_compute_score")]
     /// Compute the Levenshtein distance between duplicons
     /// /!\ WARNING THIS IS A TIME- AND MEMORY-HEAVY OPERATION
     compute_score: bool,
 
+    #[arg(long, overrides_with = "compute_score")]
+    /// Do not compute the Levenshtein distance between duplicons, whatever
+    /// the configuration
+    no_compute_score: bool,
+
     #[arg(long)]
     /// number of threads to use; default to the number of cores
     threads: Option<usize>,
-
-    #[arg(long, default_value = "1000000")]
-    /// Size used to slice input data for parallel processing
-    chunk_size: usize,
+}
+impl SearchArgs {
+    /// The configuration set on the command line, completed by the
+    /// configuration file if any
+    fn config(&self) -> Result<Config> {
+        // A switch is only set if either of its flags is given
+        let switch = |on: bool, off: bool| {
+            if on {
+                Some(true)
+            } else if off {
+                Some(false)
+            } else {
+                None
+            }
+        };
+        let cli = Config {
+            preset: self.preset.clone(),
+            min_length: self.min_length,
+            probe_size: self.probe_size,
+            gap_size: self.gap_size,
+            max_cardinality: self.max_cardinality,
+            reverse: switch(self.reverse, self.no_reverse),
+            complement: switch(self.complement, self.no_complement),
+            skip_masked: switch(self.skip_masked, self.no_skip_masked),
+            ambiguity: self.ambiguity.as_deref().map(|a| match a {
+                "wildcard" => AmbiguityPolicy::Wildcard,
+                _ => AmbiguityPolicy::Mismatch,
+            }),
+            trim: self.trim.as_ref().map(|trim| (trim[0], trim[1])),
+            regions: self.regions.clone(),
+            exclude: self.exclude.clone(),
+            compute_score: switch(self.compute_score, self.no_compute_score),
+            threads: self.threads,
+            ..Default::default()
+        };
+        Ok(match self.config.as_ref() {
+            Some(filename) => cli.or(Config::from_file(filename)?),
+            None => cli,
+        })
+    }
 }
 
 fn main() -> Result<()> {
@@ -639,7 +911,23 @@ fn main() -> Result<()> {
         .init()
         .context("failed to initialize simple_logger")?;
 
+    match &args.command {
+        Some(Command::Plan {
+            search,
+            jobs,
+            out_dir,
+        }) => plan_jobs(search, *jobs as usize, out_dir),
+        Some(Command::Merge { manifest, out }) => merge_jobs(manifest, out.as_deref()),
+        None => run(&args),
+    }
+}
+
+fn run(args: &Args) -> Result<()> {
+    if args.search.strands.is_empty() {
+        bail!("No files to process");
+    }
     let radix = (args
+        .search
         .strands
         .iter()
         .map(|n| {
@@ -653,73 +941,96 @@ fn main() -> Result<()> {
         .collect::<Vec<String>>())
     .join("-");
 
-    info!("Processing {}", &args.strands.join(", "));
-    debug!("K-mers size                {}", args.probe_size);
-    debug!("Max gap size               {}", args.gap_size);
-    debug!("Reversed duplications      {}", args.reverse);
-    debug!("Complemented duplications  {}", args.complement);
-    debug!("Skipping soft-masked       {}", args.skip_masked);
-    debug!("Min. length                {}", args.min_length);
-    debug!("Max. cardinality           {}", args.max_cardinality);
+    let settings = args.search.config()?.into_settings()?;
+    if settings.trim.is_some() && args.index.is_some() {
+        bail!("An index can not be used when trimming");
+    }
+    let previous = args
+        .incremental
+        .as_ref()
+        .map(|filename| -> Result<RunResult> {
+            if settings.trim.is_some() {
+                bail!("Incremental searches can not be trimmed");
+            }
+            let previous = RunResult::from_file(filename)?;
+            if previous.strand.map.iter().all(|c| c.checksum.is_none()) {
+                bail!(
+                    "`{}` holds no fragment checksums and can not be used incrementally",
+                    filename
+                );
+            }
+            if !previous.settings.is_compatible(&settings) {
+                bail!("`{}` has been obtained with different settings", filename);
+            }
+            Ok(previous)
+        })
+        .transpose()?;
+
+    info!("Processing {}", &args.search.strands.join(", "));
+    if let Some(preset) = settings.preset.as_ref() {
+        debug!("Preset                     {}", preset);
+    }
+    debug!("K-mers size                {}", settings.probe_size);
+    debug!(
+        "Max gap size               {}",
+        settings.max_gap_size as usize - settings.probe_size
+    );
+    debug!("Reversed duplications      {}", settings.reverse);
+    debug!("Complemented duplications  {}", settings.complement);
+    debug!("Skipping soft-masked       {}", settings.skip_masked);
+    debug!("Ambiguity codes            {:?}", settings.ambiguity);
     debug!(
-        "Threads count              {}",
-        args.threads.unwrap_or(num_cpus::get_physical())
+        "Min. length                {}",
+        settings.min_duplication_length
     );
-    if let Some(trim) = args.trim.as_ref() {
-        debug!("Trimming                   {} → {}", trim[0], trim[1]);
+    debug!("Max. cardinality           {}", settings.max_cardinality);
+    debug!("Threads count              {}", settings.threads_count);
+    if let Some(trim) = settings.trim.as_ref() {
+        debug!("Trimming                   {} → {}", trim.0, trim.1);
+    }
+    if let Some(regions) = settings.regions.as_ref() {
+        debug!("Regions                    {} intervals", regions.len());
+    }
+    if let Some(excluded) = settings.excluded.as_ref() {
+        debug!("Excluded                   {} intervals", excluded.len());
     }
 
     rayon::ThreadPoolBuilder::new()
-        .num_threads(args.threads.unwrap_or(num_cpus::get_physical()))
+        .num_threads(settings.threads_count)
         .build_global()
         .expect("Unable to create thread pool");
 
+    let (reverse, complement, trim) = (settings.reverse, settings.complement, settings.trim);
     let result = search_duplications(
-        &args.strands,
-        RunSettings {
-            probe_size: args.probe_size,
-            max_gap_size: args.gap_size as u32 + args.probe_size as u32,
-            min_duplication_length: args.min_length,
-            max_cardinality: args.max_cardinality,
-
-            reverse: args.reverse,
-            complement: args.complement,
-            skip_masked: args.skip_masked,
-
-            compute_score: args.compute_score,
-            threads_count: args.threads.unwrap_or(num_cpus::get_physical()),
-            trim: args.trim.clone().map(|trim| (trim[0], trim[1])),
-        },
+        &args.search.strands,
+        settings,
+        args.index.as_deref(),
+        previous.as_ref(),
     )?;
 
-    let out_radix = if args.out.is_none() {
+    let out_radix = args.out.clone().unwrap_or_else(|| {
         format!(
             "{}{}{}{}{}{}.json",
             &args.prefix,
             radix,
-            if args.reverse || args.complement {
-                "_"
-            } else {
-                ""
-            },
-            if args.reverse { "R" } else { "" },
-            if args.complement { "C" } else { "" },
-            &args
-                .trim
-                .map(|trim| format!("_{}-{}", trim[0], trim[1]))
+            if reverse || complement { "_" } else { "" },
+            if reverse { "R" } else { "" },
+            if complement { "C" } else { "" },
+            &trim
+                .map(|trim| format!("_{}-{}", trim.0, trim.1))
                 .unwrap_or_default()
         )
-    } else {
-        args.out.unwrap()
-    };
+    });
 
     let out_filename = asgart::utils::make_out_filename(Some(&out_radix), "", "json")
         .to_str()
         .unwrap()
         .to_owned();
-    let mut out = std::fs::File::create(&out_filename)
-        .with_context(|| format!("Unable to create `{}`", out_filename))?;
-    let exporter = Box::new(exporters::JSONExporter) as Box<dyn exporters::Exporter>;
+    let mut out = std::io::BufWriter::new(
+        std::fs::File::create(&out_filename)
+            .with_context(|| format!("Unable to create `{}`", out_filename))?,
+    );
+    let mut exporter = Box::new(exporters::JSONExporter) as Box<dyn exporters::Exporter>;
     exporter.save(&result, &mut out)?;
     info!(
         "{}",
@@ -728,16 +1039,238 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn search_duplications(strands_files: &[String], settings: RunSettings) -> Result<RunResult> {
+fn plan_jobs(search: &SearchArgs, count: usize, out_dir: &str) -> Result<()> {
+    let config = search.config()?;
+    let settings = config.clone().into_settings()?;
+    if settings.trim.is_some() {
+        bail!("Trimming can not be used when planning jobs");
+    }
+    if search.strands.is_empty() {
+        bail!("No files to process");
+    }
+    let absolute = |filename: &str| -> Result<String> {
+        Ok(std::fs::canonicalize(filename)
+            .with_context(|| format!("Unable to find `{}`", filename))?
+            .to_string_lossy()
+            .into_owned())
+    };
+    let strands = search
+        .strands
+        .iter()
+        .map(|s| absolute(s))
+        .collect::<Result<Vec<_>>>()?;
+    std::fs::create_dir_all(out_dir).with_context(|| format!("Unable to create `{}`", out_dir))?;
+    let out_dir = absolute(out_dir)?;
+    let in_dir = |filename: &str| {
+        path::Path::new(&out_dir)
+            .join(filename)
+            .to_string_lossy()
+            .into_owned()
+    };
+
+    info!("Preprocessing data");
+    let (_, chunks, strand) = prepare_data(&strands, &settings)?;
+
+    info!("Building suffix array");
+    let sa_build_time = Instant::now();
+    let index = "index.sa";
+    jobs::write_index(&in_dir(index), &strand.data, &r_divsufsort(&strand.data))?;
+    debug!("Done in {}", HumanDuration(sa_build_time.elapsed()));
+
+    let exclude = config.exclude.as_deref().map(absolute).transpose()?;
+    let split = jobs::split_chunks(&chunks, &strand.map, count);
+    let digits = split.len().to_string().len();
+    let mut planned = Vec::new();
+    let mut commands = String::new();
+    for (i, regions) in split.into_iter().enumerate() {
+        let name = format!("job-{:0width$}", i + 1, width = digits);
+        let bed = format!("{}.bed", name);
+        regions::write_bed(&in_dir(&bed), &regions)?;
+
+        let job_config = Config {
+            regions: Some(bed),
+            exclude: exclude.clone(),
+            ..config.clone()
+        };
+        let config_file = format!("{}.toml", name);
+        std::fs::write(
+            in_dir(&config_file),
+            toml::to_string(&job_config).context("Unable to serialize configuration")?,
+        )
+        .with_context(|| format!("Unable to write `{}`", in_dir(&config_file)))?;
+
+        let output = format!("{}.json", name);
+        commands.push_str(&format!(
+            "asgart {} --config {} --index {} --out {}\n",
+            strands.join(" "),
+            in_dir(&config_file),
+            in_dir(index),
+            in_dir(&output)
+        ));
+        planned.push(Job {
+            probed: regions.iter().map(|r| r.len()).sum(),
+            name,
+            config: config_file,
+            output,
+            regions,
+        });
+    }
+
+    let manifest = Manifest {
+        strands,
+        index: index.to_owned(),
+        regions: settings.regions,
+        jobs: planned,
+    };
+    manifest.write(&in_dir("manifest.json"))?;
+    std::fs::write(in_dir("jobs.txt"), commands)
+        .with_context(|| format!("Unable to write `{}`", in_dir("jobs.txt")))?;
+
+    info!(
+        "{}",
+        style(format!(
+            "{} jobs written to {}; their commands are listed in {}",
+            manifest.jobs.len(),
+            out_dir,
+            in_dir("jobs.txt")
+        ))
+        .bold()
+    );
+    Ok(())
+}
+
+fn merge_jobs(manifest_file: &str, out: Option<&str>) -> Result<()> {
+    let manifest = Manifest::from_file(manifest_file)?;
+
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    for job in &manifest.jobs {
+        let filename = jobs::resolve(manifest_file, &job.output);
+        if !path::Path::new(&filename).exists() {
+            errors.push(format!("{}: `{}` not found", job.name, filename));
+            continue;
+        }
+        let result = RunResult::from_file(&filename)?;
+        match jobs::check_job(job, &result) {
+            Ok(()) => results.push(result),
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+    if !errors.is_empty() {
+        errors.iter().for_each(|e| error!("{}", e));
+        bail!(
+            "{} job{} out of {} not covered",
+            errors.len(),
+            if errors.len() > 1 { "s" } else { "" },
+            manifest.jobs.len()
+        );
+    }
+
+    let before = results
+        .iter()
+        .map(|r| r.families.iter().map(|f| f.len()).sum::<usize>())
+        .sum::<usize>();
+    let mut result = jobs::merge_results(results)?;
+    result.settings.regions = manifest.regions;
+    let after = result.families.iter().map(|f| f.len()).sum::<usize>();
+    info!(
+        "Merged {} jobs: {} duplications, {} found twice",
+        manifest.jobs.len(),
+        after.separate_with_spaces(),
+        (before - after).separate_with_spaces()
+    );
+
+    let out_filename = out
+        .map(|o| o.to_owned())
+        .unwrap_or_else(|| jobs::resolve(manifest_file, "merged.json"));
+    let mut out = std::io::BufWriter::new(
+        std::fs::File::create(&out_filename)
+            .with_context(|| format!("Unable to create `{}`", out_filename))?,
+    );
+    let mut exporter = Box::new(exporters::JSONExporter) as Box<dyn exporters::Exporter>;
+    exporter.save(&result, &mut out)?;
+    info!(
+        "{}",
+        style(format!("Result written to {}", &out_filename)).bold()
+    );
+    Ok(())
+}
+
+fn search_duplications(
+    strands_files: &[String],
+    settings: RunSettings,
+    index: Option<&str>,
+    previous: Option<&RunResult>,
+) -> Result<RunResult> {
     let total = Instant::now();
 
     info!("Preprocessing data");
-    let (trim, to_process, strand) =
-        prepare_data(strands_files, settings.skip_masked, settings.trim)?;
+    let (trim, mut to_process, strand) = prepare_data(strands_files, &settings)?;
+    let suffix_array = index
+        .map(|filename| {
+            info!("Loading index `{}`", filename);
+            jobs::read_index(filename, &strand.data)
+        })
+        .transpose()?;
+
+    let strand_result = StrandResult {
+        name: strand.file_names.clone(),
+        length: strand.map.iter().fold(0, |ax, chr| ax + chr.length),
+        map: strand.map.clone(),
+        collapsed: Vec::new(),
+    };
+
+    // Only probe the new or changed fragments, and reuse the duplications
+    // standing on the other ones
+    let mut reused = Vec::new();
+    if let Some(previous) = previous {
+        let unchanged = previous
+            .strand
+            .unchanged_fragments(&strand_result)
+            .into_iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>();
+        to_process.retain(|chunk| {
+            strand_result
+                .find_chr_by_pos(chunk.0)
+                .is_some_and(|c| !unchanged.contains(&c.name.as_str()))
+        });
+        reused = previous
+            .unchanged_families(&strand_result)
+            .into_iter()
+            .map(|family| {
+                family
+                    .into_iter()
+                    .filter(|sd| {
+                        sd.reversed == settings.reverse && sd.complemented == settings.complement
+                    })
+                    .collect::<SDsFamily>()
+            })
+            .filter(|family| !family.is_empty())
+            .collect::<Vec<_>>();
+        info!(
+            "Reusing {} duplications from {} unchanged fragments, probing {} new or changed ones",
+            reused
+                .iter()
+                .map(|f| f.len())
+                .sum::<usize>()
+                .separate_with_spaces(),
+            unchanged.len(),
+            strand.map.len() - unchanged.len()
+        );
+    }
 
     let mut steps: Vec<Box<dyn Step>> = vec![
-        Box::new(SearchDuplications::new(&to_process, trim, settings)),
-        Box::new(FilterNs {}),
+        Box::new(SearchDuplications::new(
+            &to_process,
+            trim,
+            settings.clone(),
+            suffix_array,
+            settings.regions.is_some() || previous.is_some(),
+        )),
+        Box::new(FilterNs {
+            max_n_content: settings.max_n_content,
+        }),
         Box::new(ReOrder {}),
         Box::new(ReduceOverlap {}),
     ];
@@ -767,18 +1300,14 @@ fn search_duplications(strands_files: &[String], settings: RunSettings) -> Resul
         .bold()
     );
 
-    let strand = StrandResult {
-        name: strand.file_names.clone(),
-        length: strand.map.iter().fold(0, |ax, chr| ax + chr.length),
-        map: strand.map.clone(),
-    };
+    let strand = strand_result;
 
     Ok(RunResult {
         strand: strand.clone(),
         settings,
-        families: result
-            .iter()
-            .map(|family| {
+        families: reused
+            .into_iter()
+            .chain(result.iter().map(|family| {
                 family
                     .iter()
                     .map(|sd| SD {
@@ -816,7 +1345,7 @@ fn search_duplications(strands_files: &[String], settings: RunSettings) -> Resul
                         complemented: sd.complemented,
                     })
                     .collect::<Vec<SD>>()
-            })
+            }))
             .collect(),
     })
 }
@@ -0,0 +1,371 @@
+//! A compact binary encoding of `RunResult`s.
+//!
+//! After a magic number and a format version, the header stores the strand
+//! name and length, the settings as JSON, the fragments map, where each
+//! fragment checksum is preceded by a byte flagging its presence, and the
+//! record of the collapsed fragments, each stored as its original fragment
+//! followed by the name of the fragment it lies in and its offset. The header is
+//! followed by a list of records, each starting with a tag:
+//!   - `NAME` defines a fragment name, numbered in order of appearance after
+//!     the names of the map;
+//!   - `FAMILY` stores a family as a count of duplicons followed by them, with
+//!     fragment names replaced by their index;
+//!   - `END` terminates the file.
+//!
+//! All the integers are stored as LEB128 varints.
+
+use std::io::{self, BufRead, Cursor, Read, Write};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::structs::*;
+
+pub const MAGIC: &[u8; 4] = b"ASGB";
+pub const VERSION: u64 = 3;
+
+const TAG_END: u8 = 0;
+const TAG_NAME: u8 = 1;
+const TAG_FAMILY: u8 = 2;
+
+const FLAG_REVERSED: u8 = 1;
+const FLAG_COMPLEMENTED: u8 = 1 << 1;
+const FLAG_LEFT_SEQ: u8 = 1 << 2;
+const FLAG_RIGHT_SEQ: u8 = 1 << 3;
+
+/// A reader whose first bytes have already been read once
+pub type Sniffed<R> = io::Chain<Cursor<Vec<u8>>, R>;
+
+/// Check whether `reader` contains binary results, and return a reader
+/// yielding its whole content
+pub fn sniff<R: Read>(mut reader: R) -> Result<(bool, Sniffed<R>)> {
+    let mut prefix = Vec::with_capacity(MAGIC.len());
+    (&mut reader)
+        .take(MAGIC.len() as u64)
+        .read_to_end(&mut prefix)
+        .context("Unable to read data")?;
+    Ok((prefix == MAGIC, Cursor::new(prefix).chain(reader)))
+}
+
+pub fn write_varint(out: &mut dyn Write, mut x: u64) -> io::Result<()> {
+    let mut buffer = [0u8; 10];
+    let mut i = 0;
+    loop {
+        let byte = (x & 0x7f) as u8;
+        x >>= 7;
+        if x == 0 {
+            buffer[i] = byte;
+            i += 1;
+            break;
+        } else {
+            buffer[i] = byte | 0x80;
+            i += 1;
+        }
+    }
+    out.write_all(&buffer[..i])
+}
+
+pub fn write_str(out: &mut dyn Write, s: &str) -> io::Result<()> {
+    write_varint(out, s.len() as u64)?;
+    out.write_all(s.as_bytes())
+}
+
+pub fn write_header(
+    out: &mut dyn Write,
+    strand: &StrandResult,
+    settings: &RunSettings,
+) -> Result<()> {
+    out.write_all(MAGIC)?;
+    write_varint(out, VERSION)?;
+    write_str(out, &strand.name)?;
+    write_varint(out, strand.length as u64)?;
+    write_str(
+        out,
+        &serde_json::to_string(settings).context("Unable to serialize settings")?,
+    )?;
+    write_varint(out, strand.map.len() as u64)?;
+    for chr in &strand.map {
+        write_start(out, chr)?;
+    }
+    write_varint(out, strand.collapsed.len() as u64)?;
+    for c in &strand.collapsed {
+        write_start(out, &c.fragment)?;
+        write_str(out, &c.into)?;
+        write_varint(out, c.offset as u64)?;
+    }
+    Ok(())
+}
+
+fn write_start(out: &mut dyn Write, chr: &Start) -> io::Result<()> {
+    write_str(out, &chr.name)?;
+    write_varint(out, chr.position as u64)?;
+    write_varint(out, chr.length as u64)?;
+    match chr.checksum {
+        Some(checksum) => {
+            out.write_all(&[1])?;
+            write_varint(out, checksum)
+        }
+        None => out.write_all(&[0]),
+    }
+}
+
+pub fn write_name(out: &mut dyn Write, name: &str) -> io::Result<()> {
+    out.write_all(&[TAG_NAME])?;
+    write_str(out, name)
+}
+
+/// Write a family, whose fragment names must already have been given an
+/// index by `name_index`
+pub fn write_family(
+    out: &mut dyn Write,
+    family: &SDsFamily,
+    name_index: impl Fn(&str) -> usize,
+) -> io::Result<()> {
+    out.write_all(&[TAG_FAMILY])?;
+    write_varint(out, family.len() as u64)?;
+    for sd in family {
+        write_varint(out, name_index(&sd.chr_left) as u64)?;
+        write_varint(out, name_index(&sd.chr_right) as u64)?;
+        write_varint(out, sd.global_left_position as u64)?;
+        write_varint(out, sd.global_right_position as u64)?;
+        write_varint(out, sd.chr_left_position as u64)?;
+        write_varint(out, sd.chr_right_position as u64)?;
+        write_varint(out, sd.left_length as u64)?;
+        write_varint(out, sd.right_length as u64)?;
+        out.write_all(&sd.identity.to_le_bytes())?;
+
+        let mut flags = 0;
+        if sd.reversed {
+            flags |= FLAG_REVERSED;
+        }
+        if sd.complemented {
+            flags |= FLAG_COMPLEMENTED;
+        }
+        if sd.left_seq.is_some() {
+            flags |= FLAG_LEFT_SEQ;
+        }
+        if sd.right_seq.is_some() {
+            flags |= FLAG_RIGHT_SEQ;
+        }
+        out.write_all(&[flags])?;
+        if let Some(seq) = sd.left_seq.as_ref() {
+            write_str(out, seq)?;
+        }
+        if let Some(seq) = sd.right_seq.as_ref() {
+            write_str(out, seq)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn write_end(out: &mut dyn Write) -> io::Result<()> {
+    out.write_all(&[TAG_END])
+}
+
+fn read_u8<R: Read>(r: &mut R) -> Result<u8> {
+    let mut b = [0u8; 1];
+    r.read_exact(&mut b).context("Unexpected end of data")?;
+    Ok(b[0])
+}
+
+fn read_varint<R: Read>(r: &mut R) -> Result<u64> {
+    let mut x = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = read_u8(r)?;
+        if shift >= 64 {
+            bail!("Invalid varint");
+        }
+        x |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(x);
+        }
+        shift += 7;
+    }
+}
+
+fn read_usize<R: Read>(r: &mut R) -> Result<usize> {
+    read_varint(r).map(|x| x as usize)
+}
+
+fn read_string<R: Read>(r: &mut R) -> Result<String> {
+    let len = read_usize(r)?;
+    let mut buffer = Vec::new();
+    r.take(len as u64)
+        .read_to_end(&mut buffer)
+        .context("Unexpected end of data")?;
+    if buffer.len() != len {
+        bail!("Unexpected end of data");
+    }
+    String::from_utf8(buffer).context("Invalid UTF-8 string")
+}
+
+fn read_start<R: Read>(r: &mut R, version: u64) -> Result<Start> {
+    let name = read_string(r)?;
+    let position = read_usize(r)?;
+    let length = read_usize(r)?;
+    // Version 1 did not store checksums
+    let checksum = if version >= 2 && read_u8(r)? != 0 {
+        Some(read_varint(r)?)
+    } else {
+        None
+    };
+    Ok(Start {
+        name,
+        position,
+        length,
+        checksum,
+    })
+}
+
+/// Lazily decodes binary results
+pub struct BinaryReader<R: BufRead> {
+    reader: R,
+    names: Vec<String>,
+    done: bool,
+}
+impl<R: BufRead> BinaryReader<R> {
+    /// Read the header of the binary results provided by `reader`
+    pub fn new(mut reader: R) -> Result<(StrandResult, RunSettings, BinaryReader<R>)> {
+        let mut magic = [0u8; 4];
+        reader
+            .read_exact(&mut magic)
+            .context("Unexpected end of data")?;
+        if &magic != MAGIC {
+            bail!("Not an ASGART binary file");
+        }
+        let version = read_varint(&mut reader)?;
+        if version == 0 || version > VERSION {
+            bail!(
+                "Unsupported binary format version {} (expected at most {})",
+                version,
+                VERSION
+            );
+        }
+
+        let name = read_string(&mut reader)?;
+        let length = read_usize(&mut reader)?;
+        let settings: RunSettings =
+            serde_json::from_str(&read_string(&mut reader)?).context("Unable to parse settings")?;
+        let map_len = read_usize(&mut reader)?;
+        // Lengths read from the file can not be trusted for allocations
+        let mut map = Vec::with_capacity(map_len.min(1024));
+        for _ in 0..map_len {
+            map.push(read_start(&mut reader, version)?);
+        }
+        // Versions 1 and 2 did not store collapsed fragments
+        let mut collapsed = Vec::new();
+        if version >= 3 {
+            for _ in 0..read_usize(&mut reader)? {
+                collapsed.push(CollapsedFragment {
+                    fragment: read_start(&mut reader, version)?,
+                    into: read_string(&mut reader)?,
+                    offset: read_usize(&mut reader)?,
+                });
+            }
+        }
+
+        let names = map.iter().map(|c| c.name.clone()).collect();
+        Ok((
+            StrandResult {
+                name,
+                length,
+                map,
+                collapsed,
+            },
+            settings,
+            BinaryReader {
+                reader,
+                names,
+                done: false,
+            },
+        ))
+    }
+
+    fn name(&self, i: usize) -> Result<String> {
+        self.names
+            .get(i)
+            .cloned()
+            .ok_or_else(|| anyhow!("Undefined fragment #{}", i))
+    }
+
+    fn read_family(&mut self) -> Result<Option<SDsFamily>> {
+        loop {
+            match read_u8(&mut self.reader)? {
+                TAG_END => return Ok(None),
+                TAG_NAME => {
+                    let name = read_string(&mut self.reader)?;
+                    self.names.push(name);
+                }
+                TAG_FAMILY => {
+                    let count = read_usize(&mut self.reader)?;
+                    let mut family = Vec::with_capacity(count.min(1024));
+                    for _ in 0..count {
+                        let left_index = read_usize(&mut self.reader)?;
+                        let right_index = read_usize(&mut self.reader)?;
+                        let chr_left = self.name(left_index)?;
+                        let chr_right = self.name(right_index)?;
+                        let global_left_position = read_usize(&mut self.reader)?;
+                        let global_right_position = read_usize(&mut self.reader)?;
+                        let chr_left_position = read_usize(&mut self.reader)?;
+                        let chr_right_position = read_usize(&mut self.reader)?;
+                        let left_length = read_usize(&mut self.reader)?;
+                        let right_length = read_usize(&mut self.reader)?;
+                        let mut identity = [0u8; 4];
+                        self.reader
+                            .read_exact(&mut identity)
+                            .context("Unexpected end of data")?;
+                        let flags = read_u8(&mut self.reader)?;
+                        let left_seq = if flags & FLAG_LEFT_SEQ != 0 {
+                            Some(read_string(&mut self.reader)?)
+                        } else {
+                            None
+                        };
+                        let right_seq = if flags & FLAG_RIGHT_SEQ != 0 {
+                            Some(read_string(&mut self.reader)?)
+                        } else {
+                            None
+                        };
+
+                        family.push(SD {
+                            chr_left,
+                            chr_right,
+                            global_left_position,
+                            global_right_position,
+                            chr_left_position,
+                            chr_right_position,
+                            left_length,
+                            right_length,
+                            left_seq,
+                            right_seq,
+                            identity: f32::from_le_bytes(identity),
+                            reversed: flags & FLAG_REVERSED != 0,
+                            complemented: flags & FLAG_COMPLEMENTED != 0,
+                        });
+                    }
+                    return Ok(Some(family));
+                }
+                tag => bail!("Unknown record type {}", tag),
+            }
+        }
+    }
+}
+impl<R: BufRead> Iterator for BinaryReader<R> {
+    type Item = Result<SDsFamily>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.read_family() {
+            Ok(Some(family)) => Some(Ok(family)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! The rules deciding which fragments are merged into collapsed bins.
+//!
+//! A rule is written `[BIN=]CRITERION`, where `CRITERION` is one of:
+//!   - `auto`, the fragments shorter than the mean length plus one standard
+//!     deviation, whose name is longer than two characters;
+//!   - `length<N`, the fragments shorter than N bp;
+//!   - `regex:REGEX`, the fragments whose name matches REGEX;
+//!   - `names:A,B,...`, the listed fragments.
+//!
+//! and `BIN` defaults to `ASGART_COLLAPSED`.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+
+use crate::structs::*;
+
+#[derive(Debug, Clone)]
+pub enum Criterion {
+    Auto,
+    ShorterThan(usize),
+    Matching(Regex),
+    Named(Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+pub struct CollapseRule {
+    /// The bin the fragments matched by `criterion` are merged into
+    pub bin: String,
+    pub criterion: Criterion,
+}
+impl FromStr for CollapseRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // A regex may contain a `=`, but a bin name may not contain the
+        // delimiters of the criteria
+        let (bin, criterion) = match s.split_once('=') {
+            Some((bin, criterion)) if !bin.contains([':', '<']) => (bin, criterion),
+            _ => (COLLAPSED_NAME, s),
+        };
+        if bin.is_empty() {
+            return Err(anyhow!("`{}`: empty bin name", s));
+        }
+
+        let criterion = if criterion == "auto" {
+            Criterion::Auto
+        } else if let Some(length) = criterion.strip_prefix("length<") {
+            Criterion::ShorterThan(
+                length
+                    .parse()
+                    .with_context(|| format!("`{}`: invalid length `{}`", s, length))?,
+            )
+        } else if let Some(regex) = criterion.strip_prefix("regex:") {
+            Criterion::Matching(
+                Regex::new(regex).with_context(|| format!("`{}`: invalid regex", s))?,
+            )
+        } else if let Some(names) = criterion.strip_prefix("names:") {
+            Criterion::Named(names.split(',').map(str::to_owned).collect())
+        } else {
+            return Err(anyhow!(
+                "`{}`: expected `auto`, `length<N`, `regex:REGEX` or `names:A,B,...`",
+                s
+            ));
+        };
+
+        Ok(CollapseRule {
+            bin: bin.to_owned(),
+            criterion,
+        })
+    }
+}
+
+/// The bins of the fragments of `map`, in order of first appearance in
+/// `rules`, and for each fragment the index of its bin if it is collapsed,
+/// i.e. if one of the `rules` matches it, the first one winning
+pub fn assign(map: &[Start], rules: &[CollapseRule]) -> (Vec<String>, Vec<Option<usize>>) {
+    // The historical heuristic, trying not to collapse normal but small
+    // chromosomes
+    let auto_threshold = if map.len() < 2 {
+        0.0
+    } else {
+        let n = map.len() as f64;
+        let avg = map.iter().map(|c| c.length as f64).sum::<f64>() / n;
+        let std = (map
+            .iter()
+            .map(|c| (c.length as f64 - avg).powf(2.0))
+            .sum::<f64>()
+            / (n - 1.0))
+            .sqrt();
+        avg + std
+    };
+    let matches = |rule: &CollapseRule, chr: &Start| match &rule.criterion {
+        Criterion::Auto => {
+            map.len() >= 2 && chr.length as f64 <= auto_threshold && chr.name.len() > 2
+        }
+        Criterion::ShorterThan(length) => chr.length < *length,
+        Criterion::Matching(regex) => regex.is_match(&chr.name),
+        Criterion::Named(names) => names.contains(&chr.name),
+    };
+
+    let mut bins: Vec<String> = Vec::new();
+    for rule in rules {
+        if !bins.contains(&rule.bin) {
+            bins.push(rule.bin.clone());
+        }
+    }
+    let assigned = map
+        .iter()
+        .map(|chr| {
+            rules
+                .iter()
+                .find(|rule| matches(rule, chr))
+                .map(|rule| bins.iter().position(|b| *b == rule.bin).unwrap())
+        })
+        .collect();
+    (bins, assigned)
+}
@@ -0,0 +1,212 @@
+//! Comparison of two sets of duplications.
+//!
+//! Duplicons are matched on their fragment-relative coordinates, so that sets
+//! obtained on different layouts of the same fragments can be compared. Two
+//! duplicons match if each arm of one reciprocally overlaps an arm of the
+//! other, regardless of which one is left or right.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::structs::*;
+
+/// An arm of a duplicon, as (fragment, start, length)
+type Arm<'a> = (&'a str, usize, usize);
+
+fn arms(sd: &SD) -> (Arm<'_>, Arm<'_>) {
+    (
+        (&sd.chr_left, sd.chr_left_position, sd.left_length),
+        (&sd.chr_right, sd.chr_right_position, sd.right_length),
+    )
+}
+
+/// Whether `a` and `b` overlap by at least `min_overlap` of both their lengths
+pub fn reciprocal_overlap(a: Arm, b: Arm, min_overlap: f64) -> bool {
+    if a.0 != b.0 {
+        return false;
+    }
+    let shared = (a.1 + a.2).min(b.1 + b.2).saturating_sub(a.1.max(b.1)) as f64;
+    shared > 0.0 && shared >= min_overlap * a.2 as f64 && shared >= min_overlap * b.2 as f64
+}
+
+/// Whether `a` and `b` describe the same duplication
+pub fn same_duplication(a: &SD, b: &SD, min_overlap: f64) -> bool {
+    let (a_left, a_right) = arms(a);
+    let (b_left, b_right) = arms(b);
+    (reciprocal_overlap(a_left, b_left, min_overlap)
+        && reciprocal_overlap(a_right, b_right, min_overlap))
+        || (reciprocal_overlap(a_left, b_right, min_overlap)
+            && reciprocal_overlap(a_right, b_left, min_overlap))
+}
+
+/// The (start, index of the duplicon) of the arms lying on a fragment, and
+/// the length of the longest one
+type FragmentArms = (Vec<(usize, usize)>, usize);
+
+/// The arms of a set of duplicons, sorted by start on each fragment
+struct ArmIndex<'a> {
+    arms: HashMap<&'a str, FragmentArms>,
+}
+impl<'a> ArmIndex<'a> {
+    fn new(sds: &[&'a SD]) -> ArmIndex<'a> {
+        let mut arms: HashMap<&str, FragmentArms> = HashMap::new();
+        for (i, sd) in sds.iter().enumerate() {
+            let (left, right) = self::arms(sd);
+            for arm in [left, right] {
+                let entry = arms.entry(arm.0).or_default();
+                entry.0.push((arm.1, i));
+                entry.1 = entry.1.max(arm.2);
+            }
+        }
+        arms.values_mut().for_each(|(a, _)| a.sort_unstable());
+        ArmIndex { arms }
+    }
+
+    /// The duplicons having an arm overlapping `arm`
+    fn overlapping(&self, arm: Arm) -> impl Iterator<Item = usize> + '_ {
+        let (starts, longest) = self
+            .arms
+            .get(arm.0)
+            .map(|(s, l)| (s.as_slice(), *l))
+            .unwrap_or((&[], 0));
+        let from = starts.partition_point(|&(start, _)| start + longest <= arm.1);
+        let to = starts.partition_point(|&(start, _)| start < arm.1 + arm.2);
+        starts[from..to.max(from)].iter().map(|&(_, i)| i)
+    }
+}
+
+/// The duplicons of two sets, each flagged with whether it has been matched
+/// in the other set
+pub struct Comparison<'a> {
+    pub reference: Vec<(&'a SD, bool)>,
+    pub query: Vec<(&'a SD, bool)>,
+}
+impl<'a> Comparison<'a> {
+    pub fn new(reference: &'a RunResult, query: &'a RunResult, min_overlap: f64) -> Comparison<'a> {
+        Comparison::from_duplicons(
+            reference.families.iter().flatten().collect(),
+            query.families.iter().flatten().collect(),
+            min_overlap,
+        )
+    }
+
+    pub fn from_duplicons(
+        reference: Vec<&'a SD>,
+        query: Vec<&'a SD>,
+        min_overlap: f64,
+    ) -> Comparison<'a> {
+        let index = ArmIndex::new(&query);
+        let mut query_matched = vec![false; query.len()];
+        let reference = reference
+            .into_iter()
+            .map(|r| {
+                let mut matched = false;
+                for q in index.overlapping(arms(r).0) {
+                    if same_duplication(r, query[q], min_overlap) {
+                        matched = true;
+                        query_matched[q] = true;
+                    }
+                }
+                (r, matched)
+            })
+            .collect();
+
+        Comparison {
+            reference,
+            query: query.into_iter().zip(query_matched).collect(),
+        }
+    }
+
+    /// The proportion of the reference duplicons found in the query
+    pub fn recall(&self) -> f64 {
+        ratio(&self.reference)
+    }
+
+    /// The proportion of the query duplicons found in the reference
+    pub fn precision(&self) -> f64 {
+        ratio(&self.query)
+    }
+
+    /// The comparison restricted to the duplicons having an arm on each
+    /// fragment, by fragment name
+    pub fn per_fragment(&self) -> BTreeMap<&'a str, FragmentComparison> {
+        let mut fragments: BTreeMap<&str, FragmentComparison> = BTreeMap::new();
+        for (sds, is_reference) in [(&self.reference, true), (&self.query, false)] {
+            for &(sd, matched) in sds.iter() {
+                let mut names = vec![sd.chr_left.as_str(), sd.chr_right.as_str()];
+                names.dedup();
+                for name in names {
+                    let counts = fragments.entry(name).or_default();
+                    let (total, found) = if is_reference {
+                        (&mut counts.reference, &mut counts.found)
+                    } else {
+                        (&mut counts.query, &mut counts.correct)
+                    };
+                    *total += 1;
+                    if matched {
+                        *found += 1;
+                    }
+                }
+            }
+        }
+        fragments
+    }
+}
+
+/// How many duplicons having an arm on a fragment are shared by both sets
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FragmentComparison {
+    /// How many reference duplicons stand on this fragment
+    pub reference: usize,
+    /// How many of them have been found in the query
+    pub found: usize,
+    /// How many query duplicons stand on this fragment
+    pub query: usize,
+    /// How many of them are present in the reference
+    pub correct: usize,
+}
+impl FragmentComparison {
+    pub fn recall(&self) -> f64 {
+        if self.reference == 0 {
+            1.0
+        } else {
+            self.found as f64 / self.reference as f64
+        }
+    }
+
+    pub fn precision(&self) -> f64 {
+        if self.query == 0 {
+            1.0
+        } else {
+            self.correct as f64 / self.query as f64
+        }
+    }
+}
+
+fn ratio(sds: &[(&SD, bool)]) -> f64 {
+    if sds.is_empty() {
+        1.0
+    } else {
+        sds.iter().filter(|(_, matched)| *matched).count() as f64 / sds.len() as f64
+    }
+}
+
+/// Restrict `result` to the duplicons for which `keep` is true; `keep` is
+/// called on the duplicons in order
+pub fn filter_result(result: &RunResult, mut keep: impl FnMut(&SD) -> bool) -> RunResult {
+    RunResult {
+        strand: result.strand.clone(),
+        settings: result.settings.clone(),
+        families: result
+            .families
+            .iter()
+            .map(|family| {
+                family
+                    .iter()
+                    .filter(|sd| keep(sd))
+                    .cloned()
+                    .collect::<SDsFamily>()
+            })
+            .filter(|family| !family.is_empty())
+            .collect(),
+    }
+}
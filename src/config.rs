@@ -0,0 +1,213 @@
+//! Declarative run configurations.
+//!
+//! A configuration may be read from a TOML file, or from one of the built-in
+//! presets. All its fields are optional, so that configurations can be layered
+//! on top of each other -- typically the command line over a file, over a
+//! preset, over the default values.
+
+use std::{fs, path::Path, str::FromStr};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_derive::*;
+
+use crate::regions;
+use crate::structs::*;
+
+pub const DEFAULT_MIN_LENGTH: usize = 1000;
+pub const DEFAULT_PROBE_SIZE: usize = 20;
+pub const DEFAULT_GAP_SIZE: usize = 100;
+pub const DEFAULT_MAX_CARDINALITY: usize = 500;
+
+/// The built-in presets, as `(name, TOML definition)`
+pub const PRESETS: &[(&str, &str)] = &[
+    (
+        "human-sd",
+        r#"
+        # Segmental duplications as usually defined in mammalian genomes
+        min_length = 1000
+        probe_size = 20
+        gap_size = 100
+        max_cardinality = 500
+        "#,
+    ),
+    (
+        "bacterial",
+        r#"
+        # Small, gapless genomes with short repeats such as IS elements
+        min_length = 500
+        probe_size = 16
+        gap_size = 50
+        max_cardinality = 100
+        n_threshold = 1000
+        "#,
+    ),
+    (
+        "plant-polyploid",
+        r#"
+        # Large and highly repeated genomes with many homeologous copies
+        min_length = 5000
+        probe_size = 25
+        gap_size = 200
+        max_cardinality = 2000
+        skip_masked = true
+        max_arms = 1000
+        relative_gap = 0.15
+        "#,
+    ),
+];
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// A preset to use for the fields left unset
+    pub preset: Option<String>,
+
+    pub min_length: Option<usize>,
+    pub probe_size: Option<usize>,
+    pub gap_size: Option<usize>,
+    pub max_cardinality: Option<usize>,
+    pub reverse: Option<bool>,
+    pub complement: Option<bool>,
+    pub skip_masked: Option<bool>,
+    pub ambiguity: Option<AmbiguityPolicy>,
+    pub trim: Option<(usize, usize)>,
+    /// A BED file of the regions to probe
+    pub regions: Option<String>,
+    /// A BED file of the regions to hard-mask
+    pub exclude: Option<String>,
+    pub compute_score: Option<bool>,
+    pub threads: Option<usize>,
+
+    pub n_threshold: Option<usize>,
+    pub max_n_content: Option<f32>,
+    pub max_arms: Option<usize>,
+    pub relative_gap: Option<f64>,
+}
+
+impl FromStr for Config {
+    type Err = anyhow::Error;
+
+    fn from_str(content: &str) -> Result<Config> {
+        toml::from_str(content).map_err(|e| anyhow!("{}", e.message()))
+    }
+}
+
+impl Config {
+    /// Read a configuration file; the relative paths it contains are taken
+    /// relative to its own location
+    pub fn from_file(filename: &str) -> Result<Config> {
+        let content = fs::read_to_string(filename)
+            .with_context(|| format!("Unable to read `{}`", filename))?;
+        let mut config = content
+            .parse::<Config>()
+            .with_context(|| format!("Failed to parse `{}`", filename))?;
+
+        let root = Path::new(filename)
+            .parent()
+            .unwrap_or_else(|| Path::new(""));
+        let resolve = |f: String| root.join(f).to_string_lossy().into_owned();
+        config.regions = config.regions.map(resolve);
+        config.exclude = config.exclude.map(resolve);
+        Ok(config)
+    }
+
+    pub fn from_preset(name: &str) -> Result<Config> {
+        let (_, content) = PRESETS.iter().find(|(n, _)| *n == name).ok_or_else(|| {
+            anyhow!(
+                "Unknown preset `{}`; available presets are: {}",
+                name,
+                PRESETS
+                    .iter()
+                    .map(|(n, _)| *n)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })?;
+        content
+            .parse::<Config>()
+            .with_context(|| format!("Invalid preset `{}`", name))
+    }
+
+    /// Complete the fields unset in `self` with the ones of `other`
+    pub fn or(self, other: Config) -> Config {
+        Config {
+            preset: self.preset.or(other.preset),
+            min_length: self.min_length.or(other.min_length),
+            probe_size: self.probe_size.or(other.probe_size),
+            gap_size: self.gap_size.or(other.gap_size),
+            max_cardinality: self.max_cardinality.or(other.max_cardinality),
+            reverse: self.reverse.or(other.reverse),
+            complement: self.complement.or(other.complement),
+            skip_masked: self.skip_masked.or(other.skip_masked),
+            ambiguity: self.ambiguity.or(other.ambiguity),
+            trim: self.trim.or(other.trim),
+            regions: self.regions.or(other.regions),
+            exclude: self.exclude.or(other.exclude),
+            compute_score: self.compute_score.or(other.compute_score),
+            threads: self.threads.or(other.threads),
+            n_threshold: self.n_threshold.or(other.n_threshold),
+            max_n_content: self.max_n_content.or(other.max_n_content),
+            max_arms: self.max_arms.or(other.max_arms),
+            relative_gap: self.relative_gap.or(other.relative_gap),
+        }
+    }
+
+    /// Complete `self` with its preset, if any, then build the settings of a
+    /// run, using the default values for the fields still unset
+    pub fn into_settings(self) -> Result<RunSettings> {
+        let config = match self.preset.as_ref() {
+            Some(preset) => {
+                let preset = Config::from_preset(preset)?;
+                self.or(preset)
+            }
+            None => self,
+        };
+
+        let probe_size = config.probe_size.unwrap_or(DEFAULT_PROBE_SIZE);
+        if probe_size < 8 {
+            bail!("probe_size should be at least 8, found {}", probe_size);
+        }
+        let max_n_content = config.max_n_content.unwrap_or(DEFAULT_MAX_N_CONTENT);
+        if !(0.0..=1.0).contains(&max_n_content) {
+            bail!(
+                "max_n_content should be between 0 and 1, found {}",
+                max_n_content
+            );
+        }
+        let relative_gap = config.relative_gap.unwrap_or(DEFAULT_RELATIVE_GAP);
+        if relative_gap < 0.0 {
+            bail!("relative_gap should be positive, found {}", relative_gap);
+        }
+
+        Ok(RunSettings {
+            probe_size,
+            max_gap_size: (config.gap_size.unwrap_or(DEFAULT_GAP_SIZE) + probe_size) as u32,
+            min_duplication_length: config.min_length.unwrap_or(DEFAULT_MIN_LENGTH),
+            max_cardinality: config.max_cardinality.unwrap_or(DEFAULT_MAX_CARDINALITY),
+            trim: config.trim,
+            regions: config
+                .regions
+                .as_ref()
+                .map(|filename| regions::read_bed(filename))
+                .transpose()?,
+            excluded: config
+                .exclude
+                .as_ref()
+                .map(|filename| regions::read_bed(filename))
+                .transpose()?,
+
+            reverse: config.reverse.unwrap_or(false),
+            complement: config.complement.unwrap_or(false),
+            skip_masked: config.skip_masked.unwrap_or(false),
+            ambiguity: config.ambiguity.unwrap_or_default(),
+            n_threshold: config.n_threshold.unwrap_or(DEFAULT_N_THRESHOLD),
+            max_n_content,
+            max_arms: config.max_arms.unwrap_or(DEFAULT_MAX_ARMS),
+            relative_gap,
+            preset: config.preset,
+
+            threads_count: config.threads.unwrap_or_else(num_cpus::get_physical),
+            compute_score: config.compute_score.unwrap_or(false),
+        })
+    }
+}
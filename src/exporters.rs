@@ -1,113 +1,1158 @@
-use std::io::Write;
+use std::{collections::HashMap, io::Write};
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+use bio::alignment::{pairwise::Aligner, AlignmentOperation};
 use serde_json;
 
-use crate::structs::*;
+use crate::{binary, plot::colorizers::Colorizer, sqlite, structs::*};
 
+/// An `Exporter` writes a `RunResult` family by family, so that results can
+/// be streamed to their destination without being fully held in memory.
 pub trait Exporter {
-    fn save(&self, result: &RunResult, out: &mut dyn Write) -> Result<()>;
+    /// Write whatever has to precede the families, e.g. headers or metadata
+    fn begin(
+        &mut self,
+        strand: &StrandResult,
+        settings: &RunSettings,
+        out: &mut dyn Write,
+    ) -> Result<()>;
+
+    /// Write the `i`-th family
+    fn family(&mut self, i: usize, family: &SDsFamily, out: &mut dyn Write) -> Result<()>;
+
+    /// Write whatever has to follow the families
+    fn end(&mut self, _out: &mut dyn Write) -> Result<()> {
+        Ok(())
+    }
+
+    fn save(&mut self, result: &RunResult, out: &mut dyn Write) -> Result<()> {
+        self.begin(&result.strand, &result.settings, out)?;
+        for (i, family) in result.families.iter().enumerate() {
+            self.family(i, family, out)?;
+        }
+        self.end(out)
+    }
+
+    /// Export families as they are produced by `families`
+    fn save_stream(
+        &mut self,
+        strand: &StrandResult,
+        settings: &RunSettings,
+        families: &mut dyn Iterator<Item = Result<SDsFamily>>,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        self.begin(strand, settings, out)?;
+        for (i, family) in families.enumerate() {
+            self.family(i, &family?, out)?;
+        }
+        self.end(out)
+    }
 }
 
 pub struct JSONExporter;
 impl Exporter for JSONExporter {
-    fn save(&self, result: &RunResult, out: &mut dyn Write) -> Result<()> {
-        let _ = writeln!(
+    fn begin(
+        &mut self,
+        strand: &StrandResult,
+        settings: &RunSettings,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        write!(
             out,
-            "{}",
-            serde_json::to_string_pretty(&result)
-                .context("Unable to serialize result into JSON")?
+            "{{\n\"strand\": {},\n\"settings\": {},\n\"families\": [",
+            serde_json::to_string(strand).context("Unable to serialize result into JSON")?,
+            serde_json::to_string(settings).context("Unable to serialize result into JSON")?,
         )
-        .context("Unable to write results");
+        .context("Unable to write results")
+    }
 
+    fn family(&mut self, i: usize, family: &SDsFamily, out: &mut dyn Write) -> Result<()> {
+        if i > 0 {
+            write!(out, ",").context("Unable to write results")?;
+        }
+        writeln!(out).context("Unable to write results")?;
+        serde_json::to_writer(&mut *out, family).context("Unable to serialize result into JSON")?;
         Ok(())
     }
+
+    fn end(&mut self, out: &mut dyn Write) -> Result<()> {
+        writeln!(out, "\n]}}").context("Unable to write results")?;
+        out.flush().context("Unable to write results")
+    }
+}
+
+/// Exports results in the compact binary format described in `binary`
+#[derive(Default)]
+pub struct BinaryExporter {
+    names: HashMap<String, usize>,
+    next_name: usize,
+}
+impl BinaryExporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+impl Exporter for BinaryExporter {
+    fn begin(
+        &mut self,
+        strand: &StrandResult,
+        settings: &RunSettings,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        self.names.clear();
+        for (i, chr) in strand.map.iter().enumerate() {
+            self.names.entry(chr.name.clone()).or_insert(i);
+        }
+        self.next_name = strand.map.len();
+        binary::write_header(out, strand, settings).context("Unable to write results")
+    }
+
+    fn family(&mut self, _i: usize, family: &SDsFamily, out: &mut dyn Write) -> Result<()> {
+        for sd in family {
+            for name in [&sd.chr_left, &sd.chr_right] {
+                if !self.names.contains_key(name) {
+                    binary::write_name(out, name).context("Unable to write results")?;
+                    self.names.insert(name.clone(), self.next_name);
+                    self.next_name += 1;
+                }
+            }
+        }
+        binary::write_family(out, family, |name| self.names[name])
+            .context("Unable to write results")
+    }
+
+    fn end(&mut self, out: &mut dyn Write) -> Result<()> {
+        binary::write_end(out).context("Unable to write results")?;
+        out.flush().context("Unable to write results")
+    }
 }
 
 pub struct GFF2Exporter;
 impl Exporter for GFF2Exporter {
-    fn save(&self, result: &RunResult, out: &mut dyn Write) -> Result<()> {
+    fn begin(
+        &mut self,
+        strand: &StrandResult,
+        _settings: &RunSettings,
+        out: &mut dyn Write,
+    ) -> Result<()> {
         writeln!(
             out,
             "track name=Duplications\tuseScore=1\tdescription=\"ASGART - {dataset}\"",
-            dataset = result.strand.name,
+            dataset = strand.name,
         )
-        .context("Unable to write results")?;
-        for (i, family) in result.families.iter().enumerate() {
-            for (j, sd) in family.iter().enumerate() {
+        .context("Unable to write results")
+    }
+
+    fn family(&mut self, i: usize, family: &SDsFamily, out: &mut dyn Write) -> Result<()> {
+        for (j, sd) in family.iter().enumerate() {
+            writeln!(
+                out,
+                "{chr_left}\tASGART\tSD\t{left}\t{end}\t#{identity}\t+\t.\tSD#{i}/{j}-{chr_left}",
+                chr_left = str::replace(sd.chr_left.trim(), " ", "_"),
+                left = sd.chr_left_position,
+                end = sd.chr_left_position + sd.left_length,
+                identity = sd.identity * 100.0,
+                i = i,
+                j = j
+            )
+            .context("Unable to write results")?;
+            writeln!(
+                out,
+                "{chr_right}\tASGART\tSD\t{right}\t{end}\t#{identity}\t#{reverse}\t.\tSD#{i}/{j}-{chr_right}",
+                chr_right = str::replace(sd.chr_right.trim(), " ", "_"),
+                right = sd.chr_right_position,
+                end = sd.chr_right_position + sd.right_length,
+                identity = sd.identity * 100.0,
+                reverse = if sd.reversed { "-" } else { "+" },
+                i = i,
+                j = j
+            )
+            .context("Unable to write results")?;
+        }
+        writeln!(out).context("Unable to write results")
+    }
+}
+
+pub struct GFF3Exporter;
+impl Exporter for GFF3Exporter {
+    fn begin(
+        &mut self,
+        strand: &StrandResult,
+        _settings: &RunSettings,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        writeln!(out, "##gff-version 3.2.1").context("Unable to write results")?;
+        for chr in &strand.map {
+            writeln!(
+                out,
+                "##sequence-region {name} {start} {end}",
+                name = &chr.name,
+                start = chr.position + 1,
+                end = chr.position + chr.length + 1,
+            )
+            .context("Unable to write results")?;
+        }
+
+        Ok(())
+    }
+
+    fn family(&mut self, i: usize, family: &SDsFamily, out: &mut dyn Write) -> Result<()> {
+        for (j, sd) in family.iter().enumerate() {
+            writeln!(
+                out,
+                "{chr_left}\tASGART\tSD\t{left}\t{end}\t{identity}\t+\t.\tID=SD#{i}-{j};Name=SD#{i}-{j}",
+                chr_left = str::replace(sd.chr_left.trim(), " ", "_"),
+                left = sd.chr_left_position + 1,
+                end = sd.chr_left_position + sd.left_length + 1,
+                identity = sd.identity,
+                i = i,
+                j = j
+            )
+            .context("Unable to write results")?;
+            writeln!(
+                out,
+                "{chr_right}\tASGART\tSD\t{right}\t{end}\t{identity}\t{reverse}\t.\tID=SD#{i}-{j}-right;Parent=SD#{i}-{j};Name=SD#{i}-{j}",
+                chr_right = str::replace(sd.chr_right.trim(), " ", "_"),
+                right     = sd.chr_right_position + 1,
+                end       = sd.chr_right_position + sd.right_length + 1,
+                identity  = sd.identity,
+                reverse   = if sd.reversed { "-" } else { "+" },
+                i         = i, j = j
+            ).context("Unable to write results")?;
+        }
+        writeln!(out).context("Unable to write results")
+    }
+}
+
+/// Exports one line per duplicon holding both its arms, in the BEDPE format
+/// used by bedtools. The name is `SD#FAMILY/MEMBER`, the score is the identity
+/// as a fraction, and two extra columns give the identity in percents and the
+/// family index.
+pub struct BEDPEExporter;
+impl Exporter for BEDPEExporter {
+    fn begin(
+        &mut self,
+        _strand: &StrandResult,
+        _settings: &RunSettings,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        writeln!(
+            out,
+            "#chrom1\tstart1\tend1\tchrom2\tstart2\tend2\tname\tscore\tstrand1\tstrand2\tidentity\tfamily"
+        )
+        .context("Unable to write results")
+    }
+
+    fn family(&mut self, i: usize, family: &SDsFamily, out: &mut dyn Write) -> Result<()> {
+        for (j, sd) in family.iter().enumerate() {
+            writeln!(
+                out,
+                "{chr_left}\t{left}\t{left_end}\t{chr_right}\t{right}\t{right_end}\tSD#{i}/{j}\t{score:.4}\t+\t{reverse}\t{identity}\t{i}",
+                chr_left = str::replace(sd.chr_left.trim(), " ", "_"),
+                left = sd.chr_left_position,
+                left_end = sd.chr_left_position + sd.left_length,
+                chr_right = str::replace(sd.chr_right.trim(), " ", "_"),
+                right = sd.chr_right_position,
+                right_end = sd.chr_right_position + sd.right_length,
+                score = sd.identity / 100.0,
+                reverse = if sd.reversed { "-" } else { "+" },
+                identity = sd.identity,
+                i = i,
+                j = j
+            )
+            .context("Unable to write results")?;
+        }
+        Ok(())
+    }
+}
+
+/// Exports duplicons as BED features colored by a `Colorizer`. In BED9, each
+/// arm is written as an independent feature; in BED12, both arms of an
+/// intra-fragment duplicon are written as the two blocks of a single feature,
+/// unless they overlap.
+pub struct BEDExporter {
+    colorizer: Box<dyn Colorizer>,
+    blocks: bool,
+}
+impl BEDExporter {
+    pub fn bed9(colorizer: Box<dyn Colorizer>) -> Self {
+        BEDExporter {
+            colorizer,
+            blocks: false,
+        }
+    }
+
+    pub fn bed12(colorizer: Box<dyn Colorizer>) -> Self {
+        BEDExporter {
+            colorizer,
+            blocks: true,
+        }
+    }
+}
+impl Exporter for BEDExporter {
+    fn begin(
+        &mut self,
+        strand: &StrandResult,
+        _settings: &RunSettings,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        writeln!(
+            out,
+            "track name=Duplications itemRgb=On description=\"ASGART - {dataset}\"",
+            dataset = strand.name,
+        )
+        .context("Unable to write results")
+    }
+
+    fn family(&mut self, i: usize, family: &SDsFamily, out: &mut dyn Write) -> Result<()> {
+        for (j, sd) in family.iter().enumerate() {
+            let rgb = hex_to_rgb(&self.colorizer.color(sd));
+            let score = (sd.identity * 10.0).clamp(0.0, 1000.0) as usize;
+            let strand = if sd.reversed { "-" } else { "+" };
+            let (left, left_end) = (sd.chr_left_position, sd.chr_left_position + sd.left_length);
+            let (right, right_end) = (
+                sd.chr_right_position,
+                sd.chr_right_position + sd.right_length,
+            );
+
+            if self.blocks && sd.chr_left == sd.chr_right && left_end <= right {
                 writeln!(
                     out,
-                    "{chr_left}\tASGART\tSD\t{left}\t{end}\t#{identity}\t+\t.\tSD#{i}/{j}-{chr_left}",
-                    chr_left = str::replace(sd.chr_left.trim(), " ", "_"),
-                    left = sd.chr_left_position,
-                    end = sd.chr_left_position + sd.left_length,
-                    identity = sd.identity * 100.0,
-                    i = i,
-                    j = j
+                    "{chr}\t{left}\t{right_end}\tSD#{i}/{j}\t{score}\t{strand}\t{left}\t{right_end}\t{rgb}\t2\t{left_length},{right_length}\t0,{offset}",
+                    chr = str::replace(sd.chr_left.trim(), " ", "_"),
+                    left_length = sd.left_length,
+                    right_length = sd.right_length,
+                    offset = right - left,
                 )
                 .context("Unable to write results")?;
-                writeln!(
+                continue;
+            }
+
+            for (chr, start, end, arm_strand) in [
+                (&sd.chr_left, left, left_end, "+"),
+                (&sd.chr_right, right, right_end, strand),
+            ] {
+                write!(
                     out,
-                    "{chr_right}\tASGART\tSD\t{right}\t{end}\t#{identity}\t#{reverse}\t.\tSD#{i}/{j}-{chr_right}",
-                    chr_right = str::replace(sd.chr_right.trim(), " ", "_"),
-                    right = sd.chr_right_position,
-                    end = sd.chr_right_position + sd.right_length,
-                    identity = sd.identity * 100.0,
-                    reverse = if sd.reversed { "-" } else { "+" },
-                    i = i,
-                    j = j
+                    "{chr}\t{start}\t{end}\tSD#{i}/{j}\t{score}\t{arm_strand}\t{start}\t{end}\t{rgb}",
+                    chr = str::replace(chr.trim(), " ", "_"),
                 )
                 .context("Unable to write results")?;
+                if self.blocks {
+                    write!(out, "\t1\t{}\t0", end - start).context("Unable to write results")?;
+                }
+                writeln!(out).context("Unable to write results")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Convert a `#rrggbb` color, as produced by a `Colorizer`, into the `r,g,b`
+/// form expected by the BED itemRgb field
+fn hex_to_rgb(color: &str) -> String {
+    let hex = color.trim_start_matches('#');
+    (0..3)
+        .map(|i| {
+            hex.get(2 * i..2 * i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+                .unwrap_or(0)
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Exports each duplicon as a minimap2-style PAF record, its left arm being
+/// the query and its right arm the target. Duplicons that are reversed or
+/// complemented are written on the `-` strand. The numbers of matching bases
+/// and the block lengths are estimated from the identity, unless the
+/// sequences of both arms are available and no longer than
+/// `MAX_ALIGNED_LENGTH`, in which case they are aligned and the alignment is
+/// given as a `cg:Z:` tag.
+#[derive(Default)]
+pub struct PAFExporter {
+    lengths: HashMap<String, usize>,
+}
+impl PAFExporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+impl Exporter for PAFExporter {
+    fn begin(
+        &mut self,
+        strand: &StrandResult,
+        _settings: &RunSettings,
+        _out: &mut dyn Write,
+    ) -> Result<()> {
+        self.lengths = strand
+            .map
+            .iter()
+            .map(|chr| (chr.name.clone(), chr.length))
+            .collect();
+        Ok(())
+    }
+
+    fn family(&mut self, _i: usize, family: &SDsFamily, out: &mut dyn Write) -> Result<()> {
+        for sd in family {
+            let length = |name: &str| {
+                self.lengths
+                    .get(name)
+                    .copied()
+                    .ok_or_else(|| anyhow!("Unknown fragment `{}`", name))
+            };
+            let operations = match (sd.left_seq.as_ref(), sd.right_seq.as_ref()) {
+                (Some(left), Some(right)) => align(
+                    sd,
+                    &orient(sd, left),
+                    &right.as_bytes().to_ascii_uppercase(),
+                ),
+                _ => None,
+            };
+            let (matches, block, cigar) = match operations {
+                Some(operations) => (
+                    matches(&operations),
+                    operations.len(),
+                    Some(cigar(&operations)),
+                ),
+                None => {
+                    let block = sd.left_length.max(sd.right_length);
+                    let matches = (f64::from(sd.identity) / 100.0 * block as f64).round() as usize;
+                    (matches.min(block), block, None)
+                }
+            };
+
+            write!(
+                out,
+                "{chr_left}\t{left_length}\t{left}\t{left_end}\t{strand}\t{chr_right}\t{right_length}\t{right}\t{right_end}\t{matches}\t{block}\t255",
+                chr_left = str::replace(sd.chr_left.trim(), " ", "_"),
+                left_length = length(&sd.chr_left)?,
+                left = sd.chr_left_position,
+                left_end = sd.chr_left_position + sd.left_length,
+                strand = if sd.reversed || sd.complemented { "-" } else { "+" },
+                chr_right = str::replace(sd.chr_right.trim(), " ", "_"),
+                right_length = length(&sd.chr_right)?,
+                right = sd.chr_right_position,
+                right_end = sd.chr_right_position + sd.right_length,
+            )
+            .context("Unable to write results")?;
+            if let Some(cigar) = cigar {
+                write!(out, "\tcg:Z:{}", cigar).context("Unable to write results")?;
             }
             writeln!(out).context("Unable to write results")?;
         }
+        Ok(())
+    }
+}
+
+/// Bring `seq` in the orientation of the other arm of `sd`
+fn orient(sd: &SD, seq: &str) -> Vec<u8> {
+    let mut seq = seq.as_bytes().to_ascii_uppercase();
+    if sd.reversed {
+        seq.reverse();
+    }
+    if sd.complemented {
+        seq.iter_mut()
+            .for_each(|n| *n = bio::alphabets::dna::complement(*n));
+    }
+    seq
+}
+
+/// The length of the longest arms that are aligned on export. As the
+/// alignment fills a matrix of the size of both arms, longer ones would take
+/// hundreds of megabytes; their alignment is estimated instead.
+pub const MAX_ALIGNED_LENGTH: usize = 10_000;
+
+/// Globally align `x` against `y`, the arms of `sd`, if neither is longer
+/// than `MAX_ALIGNED_LENGTH`
+fn align(sd: &SD, x: &[u8], y: &[u8]) -> Option<Vec<AlignmentOperation>> {
+    if x.len().max(y.len()) > MAX_ALIGNED_LENGTH {
+        log::warn!(
+            "{}:{} -> {}:{}: arms longer than {}bp are not aligned",
+            sd.chr_left,
+            sd.chr_left_position,
+            sd.chr_right,
+            sd.chr_right_position,
+            MAX_ALIGNED_LENGTH
+        );
+        return None;
+    }
+    let score = |a: u8, b: u8| if a == b { 1i32 } else { -1i32 };
+    Some(
+        Aligner::with_capacity(x.len(), y.len(), -5, -1, score)
+            .global(x, y)
+            .operations,
+    )
+}
+
+/// The CIGAR string of an alignment, with `M` standing for both matches and
+/// substitutions
+fn cigar(operations: &[AlignmentOperation]) -> String {
+    let mut cigar = String::new();
+    let mut run: Option<(char, usize)> = None;
+    for op in operations {
+        let c = match op {
+            AlignmentOperation::Match | AlignmentOperation::Subst => 'M',
+            AlignmentOperation::Ins => 'I',
+            AlignmentOperation::Del => 'D',
+            _ => continue,
+        };
+        run = match run {
+            Some((last, n)) if last == c => Some((c, n + 1)),
+            Some((last, n)) => {
+                cigar.push_str(&format!("{}{}", n, last));
+                Some((c, 1))
+            }
+            None => Some((c, 1)),
+        };
+    }
+    if let Some((last, n)) = run {
+        cigar.push_str(&format!("{}{}", n, last));
+    }
+    cigar
+}
+
+fn matches(operations: &[AlignmentOperation]) -> usize {
+    operations
+        .iter()
+        .filter(|op| **op == AlignmentOperation::Match)
+        .count()
+}
+
+/// Exports duplicons as VCF 4.3 structural variants. An intra-fragment
+/// duplicon is written as a `<DUP>` spanning its left arm if both arms share
+/// the same orientation, and otherwise as an `<INV>` spanning both its arms,
+/// i.e. the inversion that a NAHR between them would yield. An inter-fragment
+/// duplicon is written as a pair of mated breakends, whose `EVENTTYPE` tells
+/// whether it is a duplication or an inversion. Records follow the order of
+/// the families, and should be sorted, e.g. by `bcftools sort`, before being
+/// indexed.
+pub struct VCFExporter;
+impl Exporter for VCFExporter {
+    fn begin(
+        &mut self,
+        strand: &StrandResult,
+        _settings: &RunSettings,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        writeln!(out, "##fileformat=VCFv4.3").context("Unable to write results")?;
+        writeln!(out, "##source=ASGART {}", env!("CARGO_PKG_VERSION"))
+            .context("Unable to write results")?;
+        for chr in &strand.map {
+            writeln!(
+                out,
+                "##contig=<ID={},length={}>",
+                str::replace(chr.name.trim(), " ", "_"),
+                chr.length
+            )
+            .context("Unable to write results")?;
+        }
+        for line in [
+            r#"##ALT=<ID=DUP,Description="Duplication">"#,
+            r#"##ALT=<ID=INV,Description="Inversion">"#,
+            r#"##INFO=<ID=SVTYPE,Number=1,Type=String,Description="Type of structural variant">"#,
+            r#"##INFO=<ID=END,Number=1,Type=Integer,Description="End position of the variant">"#,
+            r#"##INFO=<ID=SVLEN,Number=1,Type=Integer,Description="Length of the variant">"#,
+            r#"##INFO=<ID=MATEID,Number=.,Type=String,Description="ID of the mate breakend">"#,
+            r#"##INFO=<ID=EVENT,Number=1,Type=String,Description="ID of the duplicon the breakend belongs to">"#,
+            r#"##INFO=<ID=EVENTTYPE,Number=1,Type=String,Description="Type of the duplicon the breakend belongs to">"#,
+            r#"##INFO=<ID=PAIRED,Number=1,Type=String,Description="Paralogous region, as CHR:START-END">"#,
+            r#"##INFO=<ID=FAMILY,Number=1,Type=Integer,Description="Index of the duplication family">"#,
+            r#"##INFO=<ID=IDENTITY,Number=1,Type=Float,Description="Identity between the arms, in percents">"#,
+            r#"##INFO=<ID=LEFTLEN,Number=1,Type=Integer,Description="Length of the left arm">"#,
+            r#"##INFO=<ID=RIGHTLEN,Number=1,Type=Integer,Description="Length of the right arm">"#,
+            "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO",
+        ] {
+            writeln!(out, "{}", line).context("Unable to write results")?;
+        }
+        Ok(())
+    }
+
+    fn family(&mut self, i: usize, family: &SDsFamily, out: &mut dyn Write) -> Result<()> {
+        for (j, sd) in family.iter().enumerate() {
+            let chr_left = str::replace(sd.chr_left.trim(), " ", "_");
+            let chr_right = str::replace(sd.chr_right.trim(), " ", "_");
+            let inverted = sd.reversed || sd.complemented;
+            let id = format!("SD#{}/{}", i, j);
+            let info = format!(
+                "FAMILY={};IDENTITY={};LEFTLEN={};RIGHTLEN={}",
+                i, sd.identity, sd.left_length, sd.right_length
+            );
 
+            if sd.chr_left == sd.chr_right {
+                let (sv, end) = if inverted {
+                    ("INV", sd.chr_right_position + sd.right_length)
+                } else {
+                    ("DUP", sd.chr_left_position + sd.left_length)
+                };
+                writeln!(
+                    out,
+                    "{chr}\t{pos}\t{id}\tN\t<{sv}>\t.\tPASS\tSVTYPE={sv};END={end};SVLEN={len};PAIRED={chr}:{right}-{right_end};{info}",
+                    chr = chr_left,
+                    pos = sd.chr_left_position + 1,
+                    len = end - sd.chr_left_position,
+                    right = sd.chr_right_position + 1,
+                    right_end = sd.chr_right_position + sd.right_length,
+                )
+                .context("Unable to write results")?;
+            } else {
+                // VCF positions are 1-based; the right breakend stands at the
+                // end of the right arm for inversions
+                let left = sd.chr_left_position + 1;
+                let right = if inverted {
+                    sd.chr_right_position + sd.right_length
+                } else {
+                    sd.chr_right_position + 1
+                };
+                let (left_alt, right_alt) = if inverted {
+                    (
+                        format!("N]{}:{}]", chr_right, right),
+                        format!("N]{}:{}]", chr_left, left),
+                    )
+                } else {
+                    (
+                        format!("N[{}:{}[", chr_right, right),
+                        format!("]{}:{}]N", chr_left, left),
+                    )
+                };
+                let event = if inverted { "INV" } else { "DUP" };
+                for (chr, pos, alt, own, mate) in [
+                    (&chr_left, left, left_alt, 1, 2),
+                    (&chr_right, right, right_alt, 2, 1),
+                ] {
+                    writeln!(
+                        out,
+                        "{chr}\t{pos}\t{id}_{own}\tN\t{alt}\t.\tPASS\tSVTYPE=BND;MATEID={id}_{mate};EVENT={id};EVENTTYPE={event};{info}",
+                    )
+                    .context("Unable to write results")?;
+                }
+            }
+        }
         Ok(())
     }
 }
 
-pub struct GFF3Exporter;
-impl Exporter for GFF3Exporter {
-    fn save(&self, result: &RunResult, out: &mut dyn Write) -> Result<()> {
-        writeln!(out, "##gff-version 3.2.1").context("Unable to write results")?;
-        for chr in &result.strand.map {
+/// Exports each duplicon as a UCSC chain, its left arm being the target and
+/// its right arm the query, so that `liftOver` projects annotations from the
+/// former to the latter; `chainSwap` gives the other direction. Reversed or
+/// complemented right arms are expressed on the query reverse strand. The
+/// ungapped blocks are taken from the alignment of the arms when their
+/// sequences are available and no longer than `MAX_ALIGNED_LENGTH`;
+/// otherwise, a single block spans the shortest arm. Chains are scored by
+/// their identity times their aligned length.
+#[derive(Default)]
+pub struct ChainExporter {
+    lengths: HashMap<String, usize>,
+    id: usize,
+}
+impl ChainExporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+impl Exporter for ChainExporter {
+    fn begin(
+        &mut self,
+        strand: &StrandResult,
+        _settings: &RunSettings,
+        _out: &mut dyn Write,
+    ) -> Result<()> {
+        self.lengths = strand
+            .map
+            .iter()
+            .map(|chr| (chr.name.clone(), chr.length))
+            .collect();
+        self.id = 0;
+        Ok(())
+    }
+
+    fn family(&mut self, _i: usize, family: &SDsFamily, out: &mut dyn Write) -> Result<()> {
+        for sd in family {
+            let length = |name: &str| {
+                self.lengths
+                    .get(name)
+                    .copied()
+                    .ok_or_else(|| anyhow!("Unknown fragment `{}`", name))
+            };
+            let t_size = length(&sd.chr_left)?;
+            let q_size = length(&sd.chr_right)?;
+            let inverted = sd.reversed || sd.complemented;
+
+            // Blocks are (size, target gap, query gap) triplets, the gaps
+            // following the block; leading gaps shift the start of the chain
+            let operations = match (sd.left_seq.as_ref(), sd.right_seq.as_ref()) {
+                (Some(left), Some(right)) => align(
+                    sd,
+                    &left.as_bytes().to_ascii_uppercase(),
+                    &orient(sd, right),
+                ),
+                _ => None,
+            };
+            let (blocks, t_offset, q_offset, score) = match operations {
+                Some(operations) => {
+                    let (blocks, t_offset, q_offset) = chain_blocks(&operations);
+                    (blocks, t_offset, q_offset, 100 * matches(&operations))
+                }
+                None => {
+                    let size = sd.left_length.min(sd.right_length);
+                    let score = (f64::from(sd.identity) * size as f64).round() as usize;
+                    (vec![(size, 0, 0)], 0, 0, score)
+                }
+            };
+            if blocks.is_empty() {
+                continue;
+            }
+
+            let t_start = sd.chr_left_position + t_offset;
+            let q_start = if inverted {
+                q_size - (sd.chr_right_position + sd.right_length)
+            } else {
+                sd.chr_right_position
+            } + q_offset;
+            let t_span = blocks.iter().map(|b| b.0 + b.1).sum::<usize>();
+            let q_span = blocks.iter().map(|b| b.0 + b.2).sum::<usize>();
+
+            self.id += 1;
             writeln!(
                 out,
-                "##sequence-region {name} {start} {end}",
-                name = &chr.name,
-                start = chr.position + 1,
-                end = chr.position + chr.length + 1,
+                "chain {score} {chr_left} {t_size} + {t_start} {t_end} {chr_right} {q_size} {q_strand} {q_start} {q_end} {id}",
+                chr_left = str::replace(sd.chr_left.trim(), " ", "_"),
+                t_end = t_start + t_span,
+                chr_right = str::replace(sd.chr_right.trim(), " ", "_"),
+                q_strand = if inverted { "-" } else { "+" },
+                q_end = q_start + q_span,
+                id = self.id,
             )
             .context("Unable to write results")?;
+            for (k, (size, dt, dq)) in blocks.iter().enumerate() {
+                if k + 1 < blocks.len() {
+                    writeln!(out, "{}\t{}\t{}", size, dt, dq)
+                } else {
+                    writeln!(out, "{}", size)
+                }
+                .context("Unable to write results")?;
+            }
+            writeln!(out).context("Unable to write results")?;
         }
+        Ok(())
+    }
+}
 
-        for (i, family) in result.families.iter().enumerate() {
-            for (j, sd) in family.iter().enumerate() {
+/// Split an alignment of a target against a query into the ungapped blocks of
+/// a chain, as (size, target gap, query gap) triplets; the last block is
+/// followed by no gap. Also return by how much the leading gaps shift the
+/// start of the chain on the target and on the query.
+fn chain_blocks(operations: &[AlignmentOperation]) -> (Vec<(usize, usize, usize)>, usize, usize) {
+    let mut blocks: Vec<(usize, usize, usize)> = Vec::new();
+    let (mut t_offset, mut q_offset) = (0, 0);
+    let mut in_block = false;
+    for op in operations {
+        match op {
+            AlignmentOperation::Match | AlignmentOperation::Subst => {
+                if !in_block {
+                    blocks.push((0, 0, 0));
+                    in_block = true;
+                }
+                blocks.last_mut().unwrap().0 += 1;
+            }
+            AlignmentOperation::Ins | AlignmentOperation::Del => {
+                in_block = false;
+                let target_gap = *op == AlignmentOperation::Ins;
+                match blocks.last_mut() {
+                    Some(block) if target_gap => block.1 += 1,
+                    Some(block) => block.2 += 1,
+                    None if target_gap => t_offset += 1,
+                    None => q_offset += 1,
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(last) = blocks.last_mut() {
+        last.1 = 0;
+        last.2 = 0;
+    }
+    (blocks, t_offset, q_offset)
+}
+
+/// The columns that `DelimitedExporter` can write for each duplicon
+pub const PAIR_COLUMNS: [&str; 15] = [
+    "family",
+    "family_size",
+    "member",
+    "chr_left",
+    "left_position",
+    "global_left_position",
+    "left_length",
+    "chr_right",
+    "right_position",
+    "global_right_position",
+    "right_length",
+    "identity",
+    "orientation",
+    "left_seq",
+    "right_seq",
+];
+/// The columns that `DelimitedExporter` can write for each arm
+pub const ARM_COLUMNS: [&str; 11] = [
+    "family",
+    "family_size",
+    "member",
+    "arm",
+    "chr",
+    "position",
+    "global_position",
+    "length",
+    "identity",
+    "orientation",
+    "seq",
+];
+
+/// Exports one row per duplicon, or per arm, with the selected columns
+/// separated by a delimiter, e.g. as TSV or CSV; the first row holds the names
+/// of the columns. Positions are 0-based.
+pub struct DelimitedExporter {
+    delimiter: char,
+    columns: Vec<String>,
+    per_arm: bool,
+}
+impl DelimitedExporter {
+    /// Write the given `columns`, chosen among `PAIR_COLUMNS`, or among
+    /// `ARM_COLUMNS` if `per_arm` is set; all of them but the sequences are
+    /// written if `columns` is empty
+    pub fn new<T: AsRef<str>>(delimiter: char, columns: &[T], per_arm: bool) -> Result<Self> {
+        let available = if per_arm {
+            &ARM_COLUMNS[..]
+        } else {
+            &PAIR_COLUMNS[..]
+        };
+        let columns = if columns.is_empty() {
+            available
+                .iter()
+                .filter(|c| !c.ends_with("seq"))
+                .map(|c| c.to_string())
+                .collect()
+        } else {
+            columns
+                .iter()
+                .map(|c| {
+                    let c = c.as_ref().trim();
+                    if available.contains(&c) {
+                        Ok(c.to_owned())
+                    } else {
+                        Err(anyhow!(
+                            "Unknown column `{}`; available columns are: {}",
+                            c,
+                            available.join(", ")
+                        ))
+                    }
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        Ok(DelimitedExporter {
+            delimiter,
+            columns,
+            per_arm,
+        })
+    }
+
+    fn write_row(&self, row: &[String], out: &mut dyn Write) -> Result<()> {
+        let row = row
+            .iter()
+            .map(|field| {
+                if field.contains(self.delimiter) || field.contains('"') || field.contains('\n') {
+                    format!("\"{}\"", field.replace('"', "\"\""))
+                } else {
+                    field.to_owned()
+                }
+            })
+            .collect::<Vec<_>>();
+        writeln!(out, "{}", row.join(&self.delimiter.to_string()))
+            .context("Unable to write results")
+    }
+}
+impl Exporter for DelimitedExporter {
+    fn begin(
+        &mut self,
+        _strand: &StrandResult,
+        _settings: &RunSettings,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        self.write_row(&self.columns, out)
+    }
+
+    fn family(&mut self, i: usize, family: &SDsFamily, out: &mut dyn Write) -> Result<()> {
+        for (j, sd) in family.iter().enumerate() {
+            let orientation = match (sd.reversed, sd.complemented) {
+                (false, false) => "direct",
+                (true, false) => "R",
+                (false, true) => "C",
+                (true, true) => "RC",
+            };
+            let common = |column: &str| -> Option<String> {
+                Some(match column {
+                    "family" => i.to_string(),
+                    "family_size" => family.len().to_string(),
+                    "member" => j.to_string(),
+                    "identity" => sd.identity.to_string(),
+                    "orientation" => orientation.to_owned(),
+                    _ => return None,
+                })
+            };
+
+            if self.per_arm {
+                let arms = [
+                    (
+                        "left",
+                        &sd.chr_left,
+                        sd.chr_left_position,
+                        sd.global_left_position,
+                        sd.left_length,
+                        &sd.left_seq,
+                    ),
+                    (
+                        "right",
+                        &sd.chr_right,
+                        sd.chr_right_position,
+                        sd.global_right_position,
+                        sd.right_length,
+                        &sd.right_seq,
+                    ),
+                ];
+                for (arm, chr, position, global_position, length, seq) in arms {
+                    let row = self
+                        .columns
+                        .iter()
+                        .map(|column| {
+                            common(column).unwrap_or_else(|| match column.as_str() {
+                                "arm" => arm.to_owned(),
+                                "chr" => chr.to_owned(),
+                                "position" => position.to_string(),
+                                "global_position" => global_position.to_string(),
+                                "length" => length.to_string(),
+                                "seq" => seq.clone().unwrap_or_default(),
+                                _ => unreachable!(),
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    self.write_row(&row, out)?;
+                }
+            } else {
+                let row = self
+                    .columns
+                    .iter()
+                    .map(|column| {
+                        common(column).unwrap_or_else(|| match column.as_str() {
+                            "chr_left" => sd.chr_left.to_owned(),
+                            "left_position" => sd.chr_left_position.to_string(),
+                            "global_left_position" => sd.global_left_position.to_string(),
+                            "left_length" => sd.left_length.to_string(),
+                            "chr_right" => sd.chr_right.to_owned(),
+                            "right_position" => sd.chr_right_position.to_string(),
+                            "global_right_position" => sd.global_right_position.to_string(),
+                            "right_length" => sd.right_length.to_string(),
+                            "left_seq" => sd.left_seq.clone().unwrap_or_default(),
+                            "right_seq" => sd.right_seq.clone().unwrap_or_default(),
+                            _ => unreachable!(),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                self.write_row(&row, out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Exports the duplicons as a sequence graph in GFA. Each fragment is split
+/// into segments at the boundaries of the arms standing on it, and these
+/// segments are linked in the order of the fragment. Each duplicon is then
+/// written as a link without overlap from the first segment of its left arm
+/// to the homologous end of its right arm, on the reverse strand if the right
+/// arm is reversed or complemented; these links are told apart from those
+/// along the fragments by their tags, the duplicon ID, its family index (`fm`)
+/// and its identity (`pi`), so that the output is valid GFA 1.0. Segments
+/// carry no sequence, but only their length; as segments can only be known
+/// once all the duplicons have been read, the families are kept in memory
+/// until the end of the export.
+#[derive(Default)]
+pub struct GFAExporter {
+    map: Vec<Start>,
+    families: Vec<SDsFamily>,
+}
+impl GFAExporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+impl Exporter for GFAExporter {
+    fn begin(
+        &mut self,
+        strand: &StrandResult,
+        _settings: &RunSettings,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        self.map = strand.map.clone();
+        self.families.clear();
+        writeln!(out, "H\tVN:Z:1.0").context("Unable to write results")
+    }
+
+    fn family(&mut self, _i: usize, family: &SDsFamily, _out: &mut dyn Write) -> Result<()> {
+        self.families.push(family.clone());
+        Ok(())
+    }
+
+    fn end(&mut self, out: &mut dyn Write) -> Result<()> {
+        // The boundaries of the segments of each fragment
+        let mut breakpoints: HashMap<&str, Vec<usize>> = self
+            .map
+            .iter()
+            .map(|chr| (chr.name.as_str(), vec![0, chr.length]))
+            .collect();
+        for sd in self.families.iter().flatten() {
+            for (chr, start, length) in [
+                (&sd.chr_left, sd.chr_left_position, sd.left_length),
+                (&sd.chr_right, sd.chr_right_position, sd.right_length),
+            ] {
+                if let Some(b) = breakpoints.get_mut(chr.as_str()) {
+                    b.push(start);
+                    b.push(start + length);
+                }
+            }
+        }
+        for (chr, b) in breakpoints.iter_mut() {
+            let length = self.map.iter().find(|c| c.name == *chr).unwrap().length;
+            b.iter_mut().for_each(|p| *p = (*p).min(length));
+            b.sort_unstable();
+            b.dedup();
+        }
+
+        let segment = |chr: &str, start: usize, end: usize| {
+            format!("{}:{}-{}", str::replace(chr.trim(), " ", "_"), start, end)
+        };
+        for chr in &self.map {
+            let b = &breakpoints[chr.name.as_str()];
+            for w in b.windows(2) {
                 writeln!(
                     out,
-                    "{chr_left}\tASGART\tSD\t{left}\t{end}\t{identity}\t+\t.\tID=SD#{i}-{j};Name=SD#{i}-{j}",
-                    chr_left = str::replace(sd.chr_left.trim(), " ", "_"),
-                    left = sd.chr_left_position + 1,
-                    end = sd.chr_left_position + sd.left_length + 1,
-                    identity = sd.identity,
-                    i = i,
-                    j = j
+                    "S\t{}\t*\tLN:i:{}",
+                    segment(&chr.name, w[0], w[1]),
+                    w[1] - w[0]
                 )
                 .context("Unable to write results")?;
+            }
+            for w in b.windows(3) {
                 writeln!(
                     out,
-                    "{chr_right}\tASGART\tSD\t{right}\t{end}\t{identity}\t{reverse}\t.\tID=SD#{i}-{j}-right;Parent=SD#{i}-{j};Name=SD#{i}-{j}",
-                    chr_right = str::replace(sd.chr_right.trim(), " ", "_"),
-                    right     = sd.chr_right_position + 1,
-                    end       = sd.chr_right_position + sd.right_length + 1,
-                    identity  = sd.identity,
-                    reverse   = if sd.reversed { "-" } else { "+" },
-                    i         = i, j = j
-                ).context("Unable to write results")?;
+                    "L\t{}\t+\t{}\t+\t0M",
+                    segment(&chr.name, w[0], w[1]),
+                    segment(&chr.name, w[1], w[2])
+                )
+                .context("Unable to write results")?;
             }
-            writeln!(out).context("Unable to write results")?;
         }
 
+        // The segment of `chr` starting at `start`, or ending at `end`
+        let starting_at = |chr: &str, start: usize| {
+            let b = &breakpoints[chr];
+            let k = b.partition_point(|&p| p <= start);
+            (k < b.len()).then(|| segment(chr, b[k - 1], b[k]))
+        };
+        let ending_at = |chr: &str, end: usize| {
+            let b = &breakpoints[chr];
+            let k = b.partition_point(|&p| p < end);
+            (k > 0 && k < b.len()).then(|| segment(chr, b[k - 1], b[k]))
+        };
+        for (i, family) in self.families.iter().enumerate() {
+            for (j, sd) in family.iter().enumerate() {
+                if !breakpoints.contains_key(sd.chr_left.as_str())
+                    || !breakpoints.contains_key(sd.chr_right.as_str())
+                    || sd.left_length == 0
+                    || sd.right_length == 0
+                {
+                    continue;
+                }
+                let inverted = sd.reversed || sd.complemented;
+                let from = starting_at(&sd.chr_left, sd.chr_left_position);
+                let to = if inverted {
+                    ending_at(&sd.chr_right, sd.chr_right_position + sd.right_length)
+                } else {
+                    starting_at(&sd.chr_right, sd.chr_right_position)
+                };
+                if let (Some(from), Some(to)) = (from, to) {
+                    writeln!(
+                        out,
+                        "L\t{from}\t+\t{to}\t{strand}\t*\tID:Z:SD#{i}/{j}\tfm:i:{i}\tpi:f:{identity}",
+                        strand = if inverted { "-" } else { "+" },
+                        identity = sd.identity,
+                    )
+                    .context("Unable to write results")?;
+                }
+            }
+        }
+
+        self.families.clear();
+        out.flush().context("Unable to write results")
+    }
+}
+
+/// Exports results as an SQLite database, described in `sqlite`. As SQLite
+/// needs a file to work on, the database is built in a temporary file, which
+/// is copied to the output once complete.
+#[derive(Default)]
+pub struct SQLiteExporter {
+    path: Option<std::path::PathBuf>,
+    writer: Option<sqlite::Writer>,
+}
+impl SQLiteExporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+impl Exporter for SQLiteExporter {
+    fn begin(
+        &mut self,
+        strand: &StrandResult,
+        settings: &RunSettings,
+        _out: &mut dyn Write,
+    ) -> Result<()> {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or_default();
+        let path =
+            std::env::temp_dir().join(format!("asgart-{}-{}.sqlite", std::process::id(), nanos));
+        self.writer = Some(sqlite::Writer::create(
+            &path.to_string_lossy(),
+            strand,
+            settings,
+        )?);
+        self.path = Some(path);
         Ok(())
     }
+
+    fn family(&mut self, i: usize, family: &SDsFamily, _out: &mut dyn Write) -> Result<()> {
+        self.writer
+            .as_mut()
+            .ok_or_else(|| anyhow!("SQLite export not started"))?
+            .family(i, family)
+            .context("Unable to write results")
+    }
+
+    fn end(&mut self, out: &mut dyn Write) -> Result<()> {
+        let (writer, path) = self
+            .writer
+            .take()
+            .zip(self.path.take())
+            .ok_or_else(|| anyhow!("SQLite export not started"))?;
+        writer.finish().context("Unable to write results")?;
+        let r = std::fs::File::open(&path)
+            .and_then(|mut db| std::io::copy(&mut db, out))
+            .context("Unable to write results");
+        let _ = std::fs::remove_file(&path);
+        r?;
+        out.flush().context("Unable to write results")
+    }
 }
@@ -0,0 +1,557 @@
+//! A small expression language to filter duplicons and families.
+//!
+//! Expressions combine the fields of duplicons with the usual operators:
+//! `||`, `&&`, `!`, comparisons (`==`, `!=`, `<`, `<=`, `>`, `>=`), regular
+//! expression matching (`=~`, `!~`, against a string literal), arithmetic
+//! (`+`, `-`, `*`, `/`), and the `min`, `max` and `abs` functions, e.g.
+//! `identity > 0.95 && max(left_length, right_length) >= 10000 && (chr_left =~ 'chrY' || reversed)`.
+//!
+//! Contrary to `SD::identity`, `identity` is a fraction between 0 and 1; it is
+//! 0 when it has not been computed.
+//!
+//! Expressions are type-checked when parsed, and may be evaluated either on
+//! a duplicon or on a whole family; in the latter case, only the family-level
+//! variables are available.
+
+use std::fmt;
+
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+
+use crate::structs::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Type {
+    Number,
+    Str,
+    Bool,
+}
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Type::Number => "a number",
+                Type::Str => "a string",
+                Type::Bool => "a boolean",
+            }
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Number(f64),
+    Str(String),
+    Bool(bool),
+}
+impl Value {
+    fn number(&self) -> f64 {
+        match self {
+            Value::Number(x) => *x,
+            _ => unreachable!(),
+        }
+    }
+
+    fn boolean(&self) -> bool {
+        match self {
+            Value::Bool(x) => *x,
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// The variables available in expressions; the first ones describe duplicons
+/// and are not available in family-level expressions
+#[derive(Clone, Copy, Debug)]
+enum Var {
+    ChrLeft,
+    ChrRight,
+    LeftPosition,
+    RightPosition,
+    GlobalLeftPosition,
+    GlobalRightPosition,
+    LeftLength,
+    RightLength,
+    Identity,
+    Reversed,
+    Complemented,
+    Direct,
+    Inter,
+    Intra,
+    FamilySize,
+}
+impl Var {
+    const ALL: [(&'static str, Var); 15] = [
+        ("chr_left", Var::ChrLeft),
+        ("chr_right", Var::ChrRight),
+        ("left_position", Var::LeftPosition),
+        ("right_position", Var::RightPosition),
+        ("global_left_position", Var::GlobalLeftPosition),
+        ("global_right_position", Var::GlobalRightPosition),
+        ("left_length", Var::LeftLength),
+        ("right_length", Var::RightLength),
+        ("identity", Var::Identity),
+        ("reversed", Var::Reversed),
+        ("complemented", Var::Complemented),
+        ("direct", Var::Direct),
+        ("inter", Var::Inter),
+        ("intra", Var::Intra),
+        ("family_size", Var::FamilySize),
+    ];
+
+    fn typ(&self) -> Type {
+        match self {
+            Var::ChrLeft | Var::ChrRight => Type::Str,
+            Var::Reversed | Var::Complemented | Var::Direct | Var::Inter | Var::Intra => Type::Bool,
+            _ => Type::Number,
+        }
+    }
+
+    fn is_family_level(&self) -> bool {
+        matches!(self, Var::FamilySize)
+    }
+
+    fn value(&self, sd: Option<&SD>, family: &[SD]) -> Value {
+        if let Var::FamilySize = self {
+            return Value::Number(family.len() as f64);
+        }
+        // Duplicon-level variables are rejected in family-level filters
+        let sd = sd.unwrap();
+        match self {
+            Var::ChrLeft => Value::Str(sd.chr_left.clone()),
+            Var::ChrRight => Value::Str(sd.chr_right.clone()),
+            Var::LeftPosition => Value::Number(sd.chr_left_position as f64),
+            Var::RightPosition => Value::Number(sd.chr_right_position as f64),
+            Var::GlobalLeftPosition => Value::Number(sd.global_left_position as f64),
+            Var::GlobalRightPosition => Value::Number(sd.global_right_position as f64),
+            Var::LeftLength => Value::Number(sd.left_length as f64),
+            Var::RightLength => Value::Number(sd.right_length as f64),
+            Var::Identity => Value::Number(f64::from(sd.identity) / 100.0),
+            Var::Reversed => Value::Bool(sd.reversed),
+            Var::Complemented => Value::Bool(sd.complemented),
+            Var::Direct => Value::Bool(!sd.reversed && !sd.complemented),
+            Var::Inter => Value::Bool(sd.chr_left != sd.chr_right),
+            Var::Intra => Value::Bool(sd.chr_left == sd.chr_right),
+            Var::FamilySize => unreachable!(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Func {
+    Min,
+    Max,
+    Abs,
+}
+
+#[derive(Debug)]
+enum Expr {
+    Literal(Value),
+    Var(Var),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    Match(Box<Expr>, Regex, bool),
+    Call(Func, Vec<Expr>),
+}
+impl Expr {
+    fn eval(&self, sd: Option<&SD>, family: &[SD]) -> Value {
+        match self {
+            Expr::Literal(v) => v.clone(),
+            Expr::Var(v) => v.value(sd, family),
+            Expr::Not(e) => Value::Bool(!e.eval(sd, family).boolean()),
+            Expr::Neg(e) => Value::Number(-e.eval(sd, family).number()),
+            Expr::Binary(Op::Or, a, b) => {
+                Value::Bool(a.eval(sd, family).boolean() || b.eval(sd, family).boolean())
+            }
+            Expr::Binary(Op::And, a, b) => {
+                Value::Bool(a.eval(sd, family).boolean() && b.eval(sd, family).boolean())
+            }
+            Expr::Binary(Op::Eq, a, b) => Value::Bool(a.eval(sd, family) == b.eval(sd, family)),
+            Expr::Binary(Op::Ne, a, b) => Value::Bool(a.eval(sd, family) != b.eval(sd, family)),
+            Expr::Binary(op, a, b) => {
+                let (x, y) = (a.eval(sd, family).number(), b.eval(sd, family).number());
+                match op {
+                    Op::Lt => Value::Bool(x < y),
+                    Op::Le => Value::Bool(x <= y),
+                    Op::Gt => Value::Bool(x > y),
+                    Op::Ge => Value::Bool(x >= y),
+                    Op::Add => Value::Number(x + y),
+                    Op::Sub => Value::Number(x - y),
+                    Op::Mul => Value::Number(x * y),
+                    Op::Div => Value::Number(x / y),
+                    _ => unreachable!(),
+                }
+            }
+            Expr::Match(e, re, negated) => match e.eval(sd, family) {
+                Value::Str(s) => Value::Bool(re.is_match(&s) != *negated),
+                _ => unreachable!(),
+            },
+            Expr::Call(f, args) => {
+                let mut args = args.iter().map(|a| a.eval(sd, family).number());
+                Value::Number(match f {
+                    Func::Min => args.fold(f64::INFINITY, f64::min),
+                    Func::Max => args.fold(f64::NEG_INFINITY, f64::max),
+                    Func::Abs => args.next().unwrap().abs(),
+                })
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Ident(String),
+    Symbol(&'static str),
+}
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(x) => write!(f, "`{}`", x),
+            Token::Str(s) => write!(f, "'{}'", s),
+            Token::Ident(s) => write!(f, "`{}`", s),
+            Token::Symbol(s) => write!(f, "`{}`", s),
+        }
+    }
+}
+
+const SYMBOLS: [&str; 19] = [
+    "||", "&&", "==", "!=", "<=", ">=", "=~", "!~", "<", ">", "!", "+", "-", "*", "/", "(", ")",
+    ",", "=",
+];
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = s.trim_start();
+    while !rest.is_empty() {
+        let c = rest.chars().next().unwrap();
+        if c.is_ascii_digit() || c == '.' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '_'))
+                .unwrap_or(rest.len());
+            let number = rest[..end].replace('_', "");
+            tokens.push(Token::Number(
+                number
+                    .parse()
+                    .map_err(|_| anyhow!("invalid number `{}`", &rest[..end]))?,
+            ));
+            rest = &rest[end..];
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_owned()));
+            rest = &rest[end..];
+        } else if c == '\'' || c == '"' {
+            let end = rest[1..]
+                .find(c)
+                .ok_or_else(|| anyhow!("unterminated string `{}`", rest))?;
+            tokens.push(Token::Str(rest[1..=end].to_owned()));
+            rest = &rest[end + 2..];
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(*s)) {
+            if *symbol == "=" {
+                bail!("unexpected `=`; use `==` to test equality");
+            }
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else {
+            bail!("unexpected character `{}`", c);
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    family_level: bool,
+}
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<()> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            match self.peek() {
+                Some(t) => bail!("expected `{}`, found {}", symbol, t),
+                None => bail!("expected `{}`, found the end of the expression", symbol),
+            }
+        }
+    }
+
+    fn typed(&mut self, typ: Type, e: (Expr, Type), context: &str) -> Result<Expr> {
+        if e.1 != typ {
+            bail!("{} expects {}, found {}", context, typ, e.1);
+        }
+        Ok(e.0)
+    }
+
+    fn or(&mut self) -> Result<(Expr, Type)> {
+        let mut e = self.and()?;
+        while self.eat("||") {
+            let a = self.typed(Type::Bool, e, "`||`")?;
+            let b = self.and()?;
+            let b = self.typed(Type::Bool, b, "`||`")?;
+            e = (Expr::Binary(Op::Or, Box::new(a), Box::new(b)), Type::Bool);
+        }
+        Ok(e)
+    }
+
+    fn and(&mut self) -> Result<(Expr, Type)> {
+        let mut e = self.not()?;
+        while self.eat("&&") {
+            let a = self.typed(Type::Bool, e, "`&&`")?;
+            let b = self.not()?;
+            let b = self.typed(Type::Bool, b, "`&&`")?;
+            e = (Expr::Binary(Op::And, Box::new(a), Box::new(b)), Type::Bool);
+        }
+        Ok(e)
+    }
+
+    fn not(&mut self) -> Result<(Expr, Type)> {
+        if self.eat("!") {
+            let e = self.not()?;
+            let e = self.typed(Type::Bool, e, "`!`")?;
+            Ok((Expr::Not(Box::new(e)), Type::Bool))
+        } else {
+            self.comparison()
+        }
+    }
+
+    fn comparison(&mut self) -> Result<(Expr, Type)> {
+        let a = self.sum()?;
+        for (symbol, negated) in [("=~", false), ("!~", true)] {
+            if self.eat(symbol) {
+                let a = self.typed(Type::Str, a, &format!("`{}`", symbol))?;
+                let re = match self.peek().cloned() {
+                    Some(Token::Str(re)) => {
+                        self.position += 1;
+                        Regex::new(&re).with_context(|| format!("invalid regexp `{}`", re))?
+                    }
+                    _ => bail!("`{}` expects a string literal", symbol),
+                };
+                return Ok((Expr::Match(Box::new(a), re, negated), Type::Bool));
+            }
+        }
+        for (symbol, op) in [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ] {
+            if self.eat(symbol) {
+                let b = self.sum()?;
+                let context = format!("`{}`", symbol);
+                let (a, b) = if op == Op::Eq || op == Op::Ne {
+                    let typ = a.1;
+                    (a.0, self.typed(typ, b, &context)?)
+                } else {
+                    (
+                        self.typed(Type::Number, a, &context)?,
+                        self.typed(Type::Number, b, &context)?,
+                    )
+                };
+                return Ok((Expr::Binary(op, Box::new(a), Box::new(b)), Type::Bool));
+            }
+        }
+        Ok(a)
+    }
+
+    fn sum(&mut self) -> Result<(Expr, Type)> {
+        let mut e = self.product()?;
+        loop {
+            let (symbol, op) = if self.eat("+") {
+                ("`+`", Op::Add)
+            } else if self.eat("-") {
+                ("`-`", Op::Sub)
+            } else {
+                return Ok(e);
+            };
+            let a = self.typed(Type::Number, e, symbol)?;
+            let b = self.product()?;
+            let b = self.typed(Type::Number, b, symbol)?;
+            e = (Expr::Binary(op, Box::new(a), Box::new(b)), Type::Number);
+        }
+    }
+
+    fn product(&mut self) -> Result<(Expr, Type)> {
+        let mut e = self.unary()?;
+        loop {
+            let (symbol, op) = if self.eat("*") {
+                ("`*`", Op::Mul)
+            } else if self.eat("/") {
+                ("`/`", Op::Div)
+            } else {
+                return Ok(e);
+            };
+            let a = self.typed(Type::Number, e, symbol)?;
+            let b = self.unary()?;
+            let b = self.typed(Type::Number, b, symbol)?;
+            e = (Expr::Binary(op, Box::new(a), Box::new(b)), Type::Number);
+        }
+    }
+
+    fn unary(&mut self) -> Result<(Expr, Type)> {
+        if self.eat("-") {
+            let e = self.unary()?;
+            let e = self.typed(Type::Number, e, "`-`")?;
+            Ok((Expr::Neg(Box::new(e)), Type::Number))
+        } else {
+            self.atom()
+        }
+    }
+
+    fn atom(&mut self) -> Result<(Expr, Type)> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| anyhow!("unexpected end of the expression"))?;
+        self.position += 1;
+        match token {
+            Token::Number(x) => Ok((Expr::Literal(Value::Number(x)), Type::Number)),
+            Token::Str(s) => Ok((Expr::Literal(Value::Str(s)), Type::Str)),
+            Token::Symbol("(") => {
+                let e = self.or()?;
+                self.expect(")")?;
+                Ok(e)
+            }
+            Token::Ident(name) if name == "true" || name == "false" => {
+                Ok((Expr::Literal(Value::Bool(name == "true")), Type::Bool))
+            }
+            Token::Ident(name) if self.eat("(") => {
+                let f = match name.as_str() {
+                    "min" => Func::Min,
+                    "max" => Func::Max,
+                    "abs" => Func::Abs,
+                    _ => bail!("unknown function `{}`; available: min, max, abs", name),
+                };
+                let mut args = Vec::new();
+                if !self.eat(")") {
+                    loop {
+                        let arg = self.or()?;
+                        args.push(self.typed(Type::Number, arg, &format!("`{}`", name))?);
+                        if self.eat(")") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                match (f, args.len()) {
+                    (Func::Abs, 1) => {}
+                    (Func::Abs, n) => bail!("`abs` expects one argument, found {}", n),
+                    (_, 0) => bail!("`{}` expects at least one argument", name),
+                    _ => {}
+                }
+                Ok((Expr::Call(f, args), Type::Number))
+            }
+            Token::Ident(name) => {
+                let var = Var::ALL
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, v)| *v)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "unknown variable `{}`; available: {}",
+                            name,
+                            Var::ALL
+                                .iter()
+                                .filter(|(_, v)| !self.family_level || v.is_family_level())
+                                .map(|(n, _)| *n)
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )
+                    })?;
+                if self.family_level && !var.is_family_level() {
+                    bail!("`{}` is not available at the family level", name);
+                }
+                Ok((Expr::Var(var), var.typ()))
+            }
+            t => bail!("unexpected {}", t),
+        }
+    }
+}
+
+/// A parsed filter expression
+#[derive(Debug)]
+pub struct Filter {
+    expr: Expr,
+    family_level: bool,
+}
+impl Filter {
+    fn new(s: &str, family_level: bool) -> Result<Filter> {
+        let mut parser = Parser {
+            tokens: tokenize(s).with_context(|| format!("Invalid filter `{}`", s))?,
+            position: 0,
+            family_level,
+        };
+        let e = parser
+            .or()
+            .and_then(|e| {
+                if let Some(t) = parser.peek() {
+                    bail!("unexpected {}", t)
+                }
+                parser.typed(Type::Bool, e, "a filter")
+            })
+            .with_context(|| format!("Invalid filter `{}`", s))?;
+        Ok(Filter {
+            expr: e,
+            family_level,
+        })
+    }
+
+    /// Parse a filter to be evaluated on each duplicon
+    pub fn duplicons(s: &str) -> Result<Filter> {
+        Filter::new(s, false)
+    }
+
+    /// Parse a filter to be evaluated on each family
+    pub fn families(s: &str) -> Result<Filter> {
+        Filter::new(s, true)
+    }
+
+    /// Whether `sd`, a member of `family`, satisfies a duplicon-level filter
+    pub fn matches(&self, sd: &SD, family: &[SD]) -> bool {
+        self.expr.eval(Some(sd), family).boolean()
+    }
+
+    /// Whether `family` satisfies the filter
+    pub fn matches_family(&self, family: &[SD]) -> bool {
+        assert!(self.family_level, "not a family-level filter");
+        self.expr.eval(None, family).boolean()
+    }
+}
@@ -0,0 +1,357 @@
+//! Conversion of duplications found by third-party tools into `RunResult`s.
+//!
+//! These formats do not describe the genome they have been computed on, so
+//! the fragments lengths must be provided from a `.fai` or `.genome` file.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{config::Config, structs::*};
+
+/// The formats that can be imported, as accepted by `from_format`
+pub const FORMATS: [&str; 4] = ["superdups", "sedef", "bedpe", "paf"];
+
+/// An arm of a duplicon, as (fragment, start, length) relative to its fragment
+pub type Arm = (String, usize, usize);
+
+/// A pair of paralogous regions, as described in a record
+pub struct Pair {
+    pub left: Arm,
+    pub right: Arm,
+    /// Whether the arms are on opposite strands
+    pub reverse_complemented: bool,
+    /// The identity between the arms, in percents
+    pub identity: f32,
+    /// The family the pair belongs to, if the format records it
+    pub family: Option<String>,
+}
+
+/// An `Importer` reads a tabulated format, one duplicon per line, and builds
+/// a `RunResult` from it.
+pub trait Importer {
+    /// Parse the tab-separated `fields` of a record; `None` denotes a line
+    /// that does not describe a duplication, e.g. a header
+    fn pair(&mut self, fields: &[&str]) -> Result<Option<Pair>>;
+
+    /// Whether `fields`, the first line of a file that is not a comment, is a
+    /// header rather than a record
+    fn is_header(&self, _fields: &[&str]) -> bool {
+        false
+    }
+
+    /// Build a `RunResult` from the records of `input`, whose arms stand on
+    /// the fragments of `strand`. Each pair forms its own family, unless the
+    /// format records families. Pairs listed twice, once from each arm, are
+    /// only kept once.
+    fn import(
+        &mut self,
+        input: &mut dyn BufRead,
+        strand: &StrandResult,
+        source: &str,
+    ) -> Result<RunResult> {
+        let mut families: Vec<SDsFamily> = Vec::new();
+        let mut family_ids: HashMap<String, usize> = HashMap::new();
+        let mut seen = HashSet::new();
+
+        let mut first = true;
+        for (i, line) in input.lines().enumerate() {
+            let line = line.with_context(|| format!("Unable to read {}", source))?;
+            if line.trim().is_empty()
+                || line.starts_with('#')
+                || line.starts_with("track")
+                || line.starts_with("browser")
+            {
+                continue;
+            }
+            let fields = line.split('\t').map(str::trim).collect::<Vec<_>>();
+            if std::mem::take(&mut first) && self.is_header(&fields) {
+                continue;
+            }
+            let pair = match self
+                .pair(&fields)
+                .with_context(|| format!("{}:L{} `{}`", source, i + 1, line))?
+            {
+                Some(pair) => pair,
+                None => continue,
+            };
+
+            let global = |arm: &Arm| -> Result<usize> {
+                let chr = strand.find_chr(&arm.0).ok_or_else(|| {
+                    anyhow!("{}:L{}: unknown fragment `{}`", source, i + 1, arm.0)
+                })?;
+                if arm.1 + arm.2 > chr.length {
+                    return Err(anyhow!(
+                        "{}:L{}: {}:{}-{} lies past the end of `{}` ({}bp)",
+                        source,
+                        i + 1,
+                        arm.0,
+                        arm.1,
+                        arm.1 + arm.2,
+                        chr.name,
+                        chr.length
+                    ));
+                }
+                Ok(chr.position + arm.1)
+            };
+            let (mut left, mut right) = (
+                (global(&pair.left)?, pair.left),
+                (global(&pair.right)?, pair.right),
+            );
+            if right.0 < left.0 {
+                std::mem::swap(&mut left, &mut right);
+            }
+            if left == right || !seen.insert((left.clone(), right.clone())) {
+                continue;
+            }
+
+            let sd = SD {
+                chr_left: left.1 .0,
+                chr_right: right.1 .0,
+                global_left_position: left.0,
+                global_right_position: right.0,
+                chr_left_position: left.1 .1,
+                chr_right_position: right.1 .1,
+                left_length: left.1 .2,
+                right_length: right.1 .2,
+                left_seq: None,
+                right_seq: None,
+                identity: pair.identity,
+                reversed: pair.reverse_complemented,
+                complemented: pair.reverse_complemented,
+            };
+            match pair.family {
+                Some(id) => {
+                    let index = *family_ids.entry(id).or_insert_with(|| {
+                        families.push(Vec::new());
+                        families.len() - 1
+                    });
+                    families[index].push(sd);
+                }
+                None => families.push(vec![sd]),
+            }
+        }
+
+        Ok(RunResult {
+            strand: strand.clone(),
+            settings: Config::default().into_settings()?,
+            families,
+        })
+    }
+
+    fn import_file(&mut self, filename: &str, strand: &StrandResult) -> Result<RunResult> {
+        let f = File::open(filename).with_context(|| format!("Unable to open `{}`", filename))?;
+        self.import(&mut BufReader::new(f), strand, &format!("`{}`", filename))
+    }
+}
+
+pub fn from_format(format: &str) -> Option<Box<dyn Importer>> {
+    match format {
+        "superdups" => Some(Box::new(SuperDupsImporter)),
+        "sedef" => Some(Box::new(BEDPEImporter::sedef())),
+        "bedpe" => Some(Box::new(BEDPEImporter::default())),
+        "paf" => Some(Box::new(PAFImporter)),
+        _ => None,
+    }
+}
+
+/// Import and combine the duplications of `filenames`, written in `format`
+/// and standing on the fragments listed in the `genome` file
+pub fn import_files(format: &str, filenames: &[String], genome: &str) -> Result<RunResult> {
+    let strand = read_genome(genome)?;
+    let mut importer =
+        from_format(format).ok_or_else(|| anyhow!("Unknown import format `{}`", format))?;
+    let mut result = RunResult {
+        strand: strand.clone(),
+        settings: Config::default().into_settings()?,
+        families: Vec::new(),
+    };
+    for filename in filenames {
+        result
+            .families
+            .extend(importer.import_file(filename, &strand)?.families);
+    }
+    Ok(result)
+}
+
+/// Import the duplications written in `format` on STDIN
+pub fn import_stdin(format: &str, genome: &str) -> Result<RunResult> {
+    let strand = read_genome(genome)?;
+    let mut importer =
+        from_format(format).ok_or_else(|| anyhow!("Unknown import format `{}`", format))?;
+    importer.import(&mut std::io::stdin().lock(), &strand, "STDIN")
+}
+
+/// Read the fragments of a genome from a `.fai` or `.genome` file, i.e. a
+/// tabulated file whose first two columns are the names and the lengths of
+/// the fragments
+pub fn read_genome(filename: &str) -> Result<StrandResult> {
+    let f = File::open(filename).with_context(|| format!("Unable to open `{}`", filename))?;
+
+    let mut map = Vec::new();
+    let mut position = 0;
+    for (i, line) in BufReader::new(f).lines().enumerate() {
+        let line = line.with_context(|| format!("Unable to read `{}`", filename))?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = line.split('\t').collect::<Vec<_>>();
+        if fields.len() < 2 {
+            return Err(anyhow!(
+                "{}:L{} `{}`: expected at least two columns, found {}",
+                filename,
+                i + 1,
+                line,
+                fields.len()
+            ));
+        }
+        let length = fields[1]
+            .trim()
+            .parse::<usize>()
+            .with_context(|| format!("{}:L{}: invalid length `{}`", filename, i + 1, fields[1]))?;
+        map.push(Start {
+            name: fields[0].trim().to_owned(),
+            position,
+            length,
+            checksum: None,
+        });
+        position += length;
+    }
+
+    Ok(StrandResult {
+        name: Path::new(filename)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| filename.to_owned()),
+        length: position,
+        map,
+        collapsed: Vec::new(),
+    })
+}
+
+fn field<'a>(fields: &[&'a str], i: usize) -> Result<&'a str> {
+    fields.get(i).copied().ok_or_else(|| {
+        anyhow!(
+            "expected at least {} columns, found {}",
+            i + 1,
+            fields.len()
+        )
+    })
+}
+
+fn number<T: std::str::FromStr>(fields: &[&str], i: usize) -> Result<T> {
+    let f = field(fields, i)?;
+    f.parse::<T>()
+        .map_err(|_| anyhow!("invalid number `{}` in column {}", f, i + 1))
+}
+
+/// Build an arm from the half-open interval [start, end[
+fn arm(chr: &str, start: usize, end: usize) -> Result<Arm> {
+    if end < start {
+        return Err(anyhow!("end ({}) is lower than start ({})", end, start));
+    }
+    Ok((chr.to_owned(), start, end - start))
+}
+
+/// Reads the UCSC `genomicSuperDups` table, or the WGAC tabulated output it is
+/// derived from, with or without its leading `bin` column
+pub struct SuperDupsImporter;
+impl Importer for SuperDupsImporter {
+    fn pair(&mut self, fields: &[&str]) -> Result<Option<Pair>> {
+        if field(fields, 0)?.starts_with("chrom") || field(fields, 1)?.starts_with("chrom") {
+            return Ok(None);
+        }
+        // The `bin` column is an integer, contrary to `chrom`
+        let fields = if fields[0].parse::<usize>().is_ok() {
+            &fields[1..]
+        } else {
+            fields
+        };
+
+        Ok(Some(Pair {
+            left: arm(field(fields, 0)?, number(fields, 1)?, number(fields, 2)?)?,
+            right: arm(field(fields, 6)?, number(fields, 7)?, number(fields, 8)?)?,
+            // genomicSuperDups writes the minus strand as `_`
+            reverse_complemented: matches!(field(fields, 5)?, "-" | "_"),
+            identity: 100.0 * number::<f32>(fields, 25)?,
+            family: None,
+        }))
+    }
+}
+
+/// Reads BEDPE files. The score column is taken as the identity if it is
+/// lower than 1, and names of the form `FAMILY/MEMBER` group the pairs in
+/// families. Besides comments, only a first line whose second column is not
+/// a coordinate is taken as a header.
+#[derive(Default)]
+pub struct BEDPEImporter {
+    /// The column holding the identity as a fraction, if not the score
+    identity_column: Option<usize>,
+}
+impl BEDPEImporter {
+    /// SEDEF writes BEDPE files with the identity in its 21st column,
+    /// `fracMatch`
+    pub fn sedef() -> Self {
+        BEDPEImporter {
+            identity_column: Some(20),
+        }
+    }
+}
+impl Importer for BEDPEImporter {
+    fn is_header(&self, fields: &[&str]) -> bool {
+        fields.get(1).is_some_and(|f| f.parse::<usize>().is_err())
+    }
+
+    fn pair(&mut self, fields: &[&str]) -> Result<Option<Pair>> {
+        let identity = match self.identity_column {
+            Some(i) => 100.0 * number::<f32>(fields, i)?,
+            None => match fields.get(7).and_then(|s| s.parse::<f32>().ok()) {
+                Some(score) if (0.0..=1.0).contains(&score) => 100.0 * score,
+                _ => 0.0,
+            },
+        };
+        let reverse_complemented = match (fields.get(8), fields.get(9)) {
+            (Some(s1), Some(s2)) if *s1 != "." && *s2 != "." => s1 != s2,
+            _ => false,
+        };
+        let family = fields
+            .get(6)
+            .and_then(|name| name.rsplit_once('/'))
+            .map(|(family, _)| family.to_owned());
+
+        Ok(Some(Pair {
+            left: arm(field(fields, 0)?, number(fields, 1)?, number(fields, 2)?)?,
+            right: arm(field(fields, 3)?, number(fields, 4)?, number(fields, 5)?)?,
+            reverse_complemented,
+            identity,
+            family,
+        }))
+    }
+}
+
+/// Reads the PAF alignments of a genome against itself; self-alignments are
+/// skipped and the identity is computed from the number of matching bases
+pub struct PAFImporter;
+impl Importer for PAFImporter {
+    fn pair(&mut self, fields: &[&str]) -> Result<Option<Pair>> {
+        let matches = number::<f32>(fields, 9)?;
+        let block = number::<f32>(fields, 10)?;
+
+        Ok(Some(Pair {
+            left: arm(field(fields, 0)?, number(fields, 2)?, number(fields, 3)?)?,
+            right: arm(field(fields, 5)?, number(fields, 7)?, number(fields, 8)?)?,
+            reverse_complemented: field(fields, 4)? == "-",
+            identity: if block > 0.0 {
+                100.0 * matches / block
+            } else {
+                0.0
+            },
+            family: None,
+        }))
+    }
+}
@@ -0,0 +1,167 @@
+//! Spatial queries over the duplicons of a `RunResult`.
+//!
+//! A `RunResultIndex` is built once from a result, and stores the arms of its
+//! duplicons in an interval tree per fragment, so that the duplicons of a
+//! region can be found without scanning all the families.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use bio::data_structures::interval_tree::ArrayBackedIntervalTree;
+
+use crate::{regions::Region, structs::*};
+
+/// A duplicon of an indexed result, designated by the index of its family and
+/// its rank within it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DupliconId {
+    pub family: usize,
+    pub member: usize,
+}
+
+/// The arms standing on a fragment
+struct FragmentArms {
+    tree: ArrayBackedIntervalTree<usize, DupliconId>,
+    /// The arms, as (start, end, duplicon), sorted by start
+    by_start: Vec<(usize, usize, DupliconId)>,
+    /// The arms, as (end, start, duplicon), sorted by end
+    by_end: Vec<(usize, usize, DupliconId)>,
+}
+
+pub struct RunResultIndex<'a> {
+    result: &'a RunResult,
+    fragments: HashMap<&'a str, FragmentArms>,
+    mapped: HashSet<&'a str>,
+}
+impl<'a> RunResultIndex<'a> {
+    pub fn new(result: &'a RunResult) -> RunResultIndex<'a> {
+        let mut arms: HashMap<&str, Vec<(usize, usize, DupliconId)>> = HashMap::new();
+        for (family, sds) in result.families.iter().enumerate() {
+            for (member, sd) in sds.iter().enumerate() {
+                let id = DupliconId { family, member };
+                for (chr, start, length) in [
+                    (&sd.chr_left, sd.chr_left_position, sd.left_length),
+                    (&sd.chr_right, sd.chr_right_position, sd.right_length),
+                ] {
+                    arms.entry(chr.as_str())
+                        .or_default()
+                        .push((start, start + length, id));
+                }
+            }
+        }
+
+        let fragments = arms
+            .into_iter()
+            .map(|(chr, mut by_start)| {
+                let mut tree = by_start
+                    .iter()
+                    .map(|&(start, end, id)| (start..end, id))
+                    .collect::<ArrayBackedIntervalTree<_, _>>();
+                tree.index();
+                by_start.sort_unstable();
+                let mut by_end = by_start
+                    .iter()
+                    .map(|&(start, end, id)| (end, start, id))
+                    .collect::<Vec<_>>();
+                by_end.sort_unstable();
+                (
+                    chr,
+                    FragmentArms {
+                        tree,
+                        by_start,
+                        by_end,
+                    },
+                )
+            })
+            .collect();
+
+        RunResultIndex {
+            result,
+            fragments,
+            mapped: result.strand.map.iter().map(|c| c.name.as_str()).collect(),
+        }
+    }
+
+    /// Whether `chr` is a fragment of the indexed result
+    pub fn has_fragment(&self, chr: &str) -> bool {
+        self.mapped.contains(chr) || self.fragments.contains_key(chr)
+    }
+
+    pub fn duplicon(&self, id: DupliconId) -> &'a SD {
+        &self.result.families[id.family][id.member]
+    }
+
+    /// The duplicons having an arm overlapping [start, end[ on `chr`, sorted
+    /// and without duplicates
+    pub fn duplicons_overlapping(&self, chr: &str, start: usize, end: usize) -> Vec<DupliconId> {
+        if end <= start {
+            return Vec::new();
+        }
+        self.fragments
+            .get(chr)
+            .map(|arms| {
+                arms.tree
+                    .find(start..end)
+                    .iter()
+                    .map(|e| *e.data())
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The duplicons having an arm overlapping [start, end[ in global
+    /// coordinates, sorted and without duplicates
+    pub fn duplicons_overlapping_global(&self, start: usize, end: usize) -> Vec<DupliconId> {
+        self.result
+            .strand
+            .map
+            .iter()
+            .filter(|chr| chr.position < end && start < chr.position + chr.length)
+            .flat_map(|chr| {
+                self.duplicons_overlapping(
+                    &chr.name,
+                    start.saturating_sub(chr.position),
+                    (end - chr.position).min(chr.length),
+                )
+            })
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// The duplicon having the arm closest to `position` on `chr`, and the
+    /// distance between them; 0 if `position` falls within the arm
+    pub fn nearest_duplicon(&self, chr: &str, position: usize) -> Option<(DupliconId, usize)> {
+        let arms = self.fragments.get(chr)?;
+        if let Some(e) = arms.tree.find(position..position + 1).first() {
+            return Some((*e.data(), 0));
+        }
+
+        // The first arm starting after `position`, and the last arm ending
+        // before it
+        let after = arms
+            .by_start
+            .get(arms.by_start.partition_point(|a| a.0 <= position))
+            .map(|a| (a.2, a.0 - position));
+        let before = arms
+            .by_end
+            .partition_point(|a| a.0 <= position)
+            .checked_sub(1)
+            .map(|i| (arms.by_end[i].2, position + 1 - arms.by_end[i].0));
+        match (before, after) {
+            (Some(b), Some(a)) => Some(if a.1 < b.1 { a } else { b }),
+            (b, a) => b.or(a),
+        }
+    }
+
+    /// The indices of the families having an arm overlapping `region`, sorted
+    pub fn families_in(&self, region: &Region) -> Vec<usize> {
+        self.duplicons_overlapping(&region.chr, region.start, region.end)
+            .into_iter()
+            .map(|id| id.family)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+}
@@ -0,0 +1,330 @@
+//! Distribution of a search across several independent jobs.
+//!
+//! A plan splits the areas to probe into jobs of balanced sizes, each of them
+//! being restricted to its own regions. All the jobs share the same suffix
+//! array, built once when planning and stored in an index file. Once they are
+//! all done, their results are merged back into a single one.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_derive::*;
+
+use crate::divsufsort::{SAIdx, SuffixArray};
+use crate::regions::Region;
+use crate::structs::*;
+use crate::utils;
+
+pub const INDEX_MAGIC: &[u8; 4] = b"ASGI";
+pub const INDEX_VERSION: u64 = 1;
+
+/// The description of a planned set of jobs; the paths it contains are
+/// relative to its own location
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Manifest {
+    pub strands: Vec<String>,
+    pub index: String,
+    /// The regions originally requested, if any
+    pub regions: Option<Vec<Region>>,
+    pub jobs: Vec<Job>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Job {
+    pub name: String,
+    pub config: String,
+    pub output: String,
+    /// How many base pairs this job probes
+    pub probed: usize,
+    pub regions: Vec<Region>,
+}
+
+impl Manifest {
+    pub fn from_file(filename: &str) -> Result<Manifest> {
+        let f = File::open(filename).with_context(|| format!("Unable to open `{}`", filename))?;
+        serde_json::from_reader(BufReader::new(f))
+            .with_context(|| format!("Failed to parse `{}`", filename))
+    }
+
+    pub fn write(&self, filename: &str) -> Result<()> {
+        let f =
+            File::create(filename).with_context(|| format!("Unable to create `{}`", filename))?;
+        serde_json::to_writer_pretty(BufWriter::new(f), self)
+            .with_context(|| format!("Unable to write `{}`", filename))
+    }
+}
+
+/// Split the `(start, length)` chunks to process into at most `count` jobs
+/// probing roughly as many base pairs each, expressed as fragment-relative
+/// regions. Each chunk must lay within a single fragment of `map`.
+pub fn split_chunks(chunks: &[(usize, usize)], map: &[Start], count: usize) -> Vec<Vec<Region>> {
+    let total = chunks.iter().map(|c| c.1).sum::<usize>();
+    let target = total.div_ceil(count.max(1));
+
+    let mut jobs = Vec::new();
+    let mut current: Vec<Region> = Vec::new();
+    let mut filled = 0;
+    for &(start, length) in chunks {
+        let chr = &map[map.partition_point(|c| c.position + c.length <= start)];
+        let mut start = start;
+        let end = start + length;
+        while start < end {
+            let taken = (end - start).min(target - filled);
+            current.push(Region {
+                chr: chr.name.clone(),
+                start: start - chr.position,
+                end: start + taken - chr.position,
+            });
+            start += taken;
+            filled += taken;
+            if filled == target {
+                jobs.push(std::mem::take(&mut current));
+                filled = 0;
+            }
+        }
+    }
+    if !current.is_empty() {
+        jobs.push(current);
+    }
+    jobs
+}
+
+/// Save a suffix array along with the length and checksum of the sequence
+/// it has been built on
+pub fn write_index(filename: &str, data: &[u8], sa: &[SAIdx]) -> Result<()> {
+    let f = File::create(filename).with_context(|| format!("Unable to create `{}`", filename))?;
+    let mut out = BufWriter::new(f);
+    out.write_all(INDEX_MAGIC)?;
+    out.write_all(&INDEX_VERSION.to_le_bytes())?;
+    out.write_all(&(data.len() as u64).to_le_bytes())?;
+    out.write_all(&utils::fnv1a(data).to_le_bytes())?;
+    for x in sa {
+        out.write_all(&x.to_le_bytes())?;
+    }
+    out.flush()
+        .with_context(|| format!("Unable to write `{}`", filename))
+}
+
+/// Load a suffix array saved by `write_index`, ensuring that it has been
+/// built on `data`
+pub fn read_index(filename: &str, data: &[u8]) -> Result<SuffixArray> {
+    fn read_u64(r: &mut impl Read) -> Result<u64> {
+        let mut buffer = [0u8; 8];
+        r.read_exact(&mut buffer)
+            .context("Unexpected end of data")?;
+        Ok(u64::from_le_bytes(buffer))
+    }
+
+    let f = File::open(filename).with_context(|| format!("Unable to open `{}`", filename))?;
+    let mut reader = BufReader::new(f);
+    let mut magic = [0u8; 4];
+    reader
+        .read_exact(&mut magic)
+        .with_context(|| format!("Unable to read `{}`", filename))?;
+    if &magic != INDEX_MAGIC {
+        bail!("`{}` is not an ASGART index", filename);
+    }
+    let version = read_u64(&mut reader)?;
+    if version != INDEX_VERSION {
+        bail!(
+            "Unsupported index version {} (expected {})",
+            version,
+            INDEX_VERSION
+        );
+    }
+    let length = read_u64(&mut reader)? as usize;
+    let checksum = read_u64(&mut reader)?;
+    if length != data.len() || checksum != utils::fnv1a(data) {
+        bail!(
+            "`{}` has not been built from these sequences and settings",
+            filename
+        );
+    }
+
+    let mut sa = Vec::with_capacity(length);
+    let mut buffer = [0u8; 8];
+    for _ in 0..length {
+        reader
+            .read_exact(&mut buffer)
+            .with_context(|| format!("`{}` is truncated", filename))?;
+        sa.push(SAIdx::from_le_bytes(buffer));
+    }
+    Ok(sa)
+}
+
+/// Whether `a` and `b` overlap or are contiguous
+fn touch(a: (usize, usize), b: (usize, usize)) -> bool {
+    a.0 <= b.0 + b.1 && b.0 <= a.0 + a.1
+}
+
+/// Extend `x` so that it covers `y` as well
+fn fuse(x: &mut SD, y: &SD) {
+    let left_start = x.global_left_position.min(y.global_left_position);
+    let left_end =
+        (x.global_left_position + x.left_length).max(y.global_left_position + y.left_length);
+    let right_start = x.global_right_position.min(y.global_right_position);
+    let right_end =
+        (x.global_right_position + x.right_length).max(y.global_right_position + y.right_length);
+    if (left_start, left_end, right_start, right_end)
+        == (
+            x.global_left_position,
+            x.global_left_position + x.left_length,
+            x.global_right_position,
+            x.global_right_position + x.right_length,
+        )
+    {
+        return;
+    }
+
+    x.chr_left_position -= x.global_left_position - left_start;
+    x.chr_right_position -= x.global_right_position - right_start;
+    x.global_left_position = left_start;
+    x.global_right_position = right_start;
+    x.left_length = left_end - left_start;
+    x.right_length = right_end - right_start;
+    x.left_seq = None;
+    x.right_seq = None;
+}
+
+/// The representative of the family `i`, among families merged together
+fn root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Merge the results of jobs run on the same sequences. As a duplication
+/// spanning two jobs is found by both of them, or cut in two at their
+/// boundary, the duplicons whose both arms overlap or are contiguous on the
+/// same fragments are fused together, and so are the families they belong
+/// to.
+pub fn merge_results(results: Vec<RunResult>) -> Result<RunResult> {
+    let first = results
+        .first()
+        .ok_or_else(|| anyhow!("No results to merge"))?;
+    let strand = first.strand.clone();
+    let mut settings = first.settings.clone();
+    settings.regions = None;
+
+    let mut families: Vec<(usize, SD)> = Vec::new();
+    let mut family_offset = 0;
+    for result in results {
+        if result.strand.name != strand.name
+            || result.strand.map.len() != strand.map.len()
+            || result
+                .strand
+                .map
+                .iter()
+                .zip(strand.map.iter())
+                .any(|(a, b)| a.name != b.name || a.length != b.length)
+        {
+            bail!(
+                "Trying to merge results from different sources: `{}` and `{}`",
+                result.strand.name,
+                strand.name
+            );
+        }
+        // Each job probes its own regions
+        let job_settings = RunSettings {
+            regions: None,
+            ..result.settings.clone()
+        };
+        if !job_settings.is_compatible(&settings) {
+            bail!("Trying to merge results obtained with different settings");
+        }
+
+        let count = result.families.len();
+        families.extend(
+            result
+                .families
+                .into_iter()
+                .enumerate()
+                .flat_map(|(i, family)| family.into_iter().map(move |sd| (family_offset + i, sd))),
+        );
+        family_offset += count;
+    }
+
+    families.sort_by_key(|(_, sd)| (sd.global_left_position, sd.global_right_position));
+    let mut parents = (0..family_offset).collect::<Vec<_>>();
+    let mut kept: Vec<(usize, SD)> = Vec::new();
+    // The kept duplicons whose left arm may still overlap the current one
+    let mut active: Vec<usize> = Vec::new();
+    for (family, sd) in families {
+        active.retain(|&k| {
+            kept[k].1.global_left_position + kept[k].1.left_length >= sd.global_left_position
+        });
+        let same = active.iter().copied().find(|&k| {
+            let other = &kept[k].1;
+            other.reversed == sd.reversed
+                && other.complemented == sd.complemented
+                && other.chr_left == sd.chr_left
+                && other.chr_right == sd.chr_right
+                && touch(other.left_part(), sd.left_part())
+                && touch(other.right_part(), sd.right_part())
+        });
+        match same {
+            Some(k) => {
+                fuse(&mut kept[k].1, &sd);
+                let (a, b) = (root(&mut parents, family), root(&mut parents, kept[k].0));
+                parents[a.max(b)] = a.min(b);
+            }
+            None => {
+                active.push(kept.len());
+                kept.push((family, sd));
+            }
+        }
+    }
+
+    let mut merged: BTreeMap<usize, SDsFamily> = BTreeMap::new();
+    for (family, sd) in kept {
+        merged
+            .entry(root(&mut parents, family))
+            .or_default()
+            .push(sd);
+    }
+
+    Ok(RunResult {
+        strand,
+        settings,
+        families: merged
+            .into_values()
+            .map(|mut family| {
+                family.sort_by_key(|sd| sd.global_left_position);
+                family
+            })
+            .collect(),
+    })
+}
+
+/// Ensure that the result of `job` covers exactly the regions it was
+/// assigned
+pub fn check_job(job: &Job, result: &RunResult) -> Result<()> {
+    match result.settings.regions.as_ref() {
+        Some(regions) if *regions == job.regions => Ok(()),
+        Some(_) => bail!(
+            "{}: the regions probed differ from the planned ones",
+            job.name
+        ),
+        None => bail!(
+            "{}: the result has not been restricted to any region",
+            job.name
+        ),
+    }
+}
+
+/// Resolve `filename` relatively to the directory containing `manifest`
+pub fn resolve(manifest: &str, filename: &str) -> String {
+    Path::new(manifest)
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(filename)
+        .to_string_lossy()
+        .into_owned()
+}
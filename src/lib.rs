@@ -1,7 +1,21 @@
+pub mod aliases;
 pub mod automaton;
+pub mod binary;
+pub mod collapse;
+pub mod compare;
+pub mod config;
 pub mod divsufsort;
 pub mod exporters;
+pub mod filter;
+pub mod importers;
+pub mod index;
+pub mod jobs;
+pub mod liftover;
 pub mod plot;
+pub mod regions;
 pub mod searcher;
+pub mod simulate;
+pub mod sqlite;
+pub mod stats;
 pub mod structs;
 pub mod utils;
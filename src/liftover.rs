@@ -0,0 +1,245 @@
+//! Projection of duplicons from an assembly onto another one through UCSC
+//! chain files, as used by `liftOver`.
+//!
+//! The target of the chains is the assembly the duplicons have been found
+//! on, and their query the assembly to lift them to.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+use anyhow::{anyhow, Context, Result};
+use bio::data_structures::interval_tree::ArrayBackedIntervalTree;
+
+use crate::structs::*;
+
+/// The header of a chain, as far as the projection is concerned
+struct Chain {
+    score: f64,
+    q_name: String,
+    q_size: usize,
+    /// Whether the query coordinates are given on the reverse strand
+    q_reversed: bool,
+}
+
+/// An ungapped block of a chain, as (chain, query start) pairs indexed on
+/// their target span
+type Blocks = ArrayBackedIntervalTree<usize, (usize, usize)>;
+
+/// An arm projected on the target assembly
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiftedArm {
+    pub chr: String,
+    pub start: usize,
+    pub length: usize,
+    /// Whether the arm has been projected on the reverse strand
+    pub reversed: bool,
+}
+
+/// Why a duplicon could not be lifted over
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    /// An arm is not covered by any chain
+    Unmapped { chr: String, start: usize },
+    /// An arm is projected on a fragment missing from the target genome
+    UnknownFragment(String),
+    /// The length of an arm changed by more than the tolerance
+    LengthChanged { from: usize, to: usize },
+}
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rejection::Unmapped { chr, start } => write!(f, "{}:{} is not mapped", chr, start),
+            Rejection::UnknownFragment(chr) => {
+                write!(f, "`{}` is not in the target genome", chr)
+            }
+            Rejection::LengthChanged { from, to } => {
+                write!(f, "arm length changed from {}bp to {}bp", from, to)
+            }
+        }
+    }
+}
+
+pub struct Liftover {
+    chains: Vec<Chain>,
+    blocks: HashMap<String, Blocks>,
+}
+impl Liftover {
+    pub fn from_file(filename: &str) -> Result<Liftover> {
+        let f = File::open(filename).with_context(|| format!("Unable to open `{}`", filename))?;
+        Liftover::from_reader(BufReader::new(f), &format!("`{}`", filename))
+    }
+
+    /// Read the chains of `input`, named `source` in error messages
+    pub fn from_reader<R: BufRead>(input: R, source: &str) -> Result<Liftover> {
+        let mut chains = Vec::new();
+        let mut blocks: HashMap<String, Blocks> = HashMap::new();
+        // The target name and the target and query positions of the next
+        // block of the current chain
+        let mut current: Option<(String, usize, usize)> = None;
+
+        for (i, line) in input.lines().enumerate() {
+            let line = line.with_context(|| format!("Unable to read {}", source))?;
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            if fields.is_empty() || fields[0].starts_with('#') {
+                continue;
+            }
+            let number = |j: usize| -> Result<usize> {
+                fields
+                    .get(j)
+                    .and_then(|f| f.parse::<usize>().ok())
+                    .ok_or_else(|| {
+                        anyhow!("{}:L{} `{}`: invalid column {}", source, i + 1, line, j + 1)
+                    })
+            };
+
+            if fields[0] == "chain" {
+                if fields.len() < 12 {
+                    return Err(anyhow!(
+                        "{}:L{} `{}`: expected at least 12 columns, found {}",
+                        source,
+                        i + 1,
+                        line,
+                        fields.len()
+                    ));
+                }
+                if fields[4] != "+" {
+                    return Err(anyhow!(
+                        "{}:L{}: chains on the reverse strand of the target are not supported",
+                        source,
+                        i + 1
+                    ));
+                }
+                let score = fields[1]
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|s| s.is_finite())
+                    .ok_or_else(|| {
+                        anyhow!("{}:L{}: invalid score `{}`", source, i + 1, fields[1])
+                    })?;
+                chains.push(Chain {
+                    score,
+                    q_name: fields[7].to_owned(),
+                    q_size: number(8)?,
+                    q_reversed: fields[9] == "-",
+                });
+                current = Some((fields[2].to_owned(), number(5)?, number(10)?));
+            } else {
+                let (t_name, t_position, q_position) = current
+                    .as_mut()
+                    .ok_or_else(|| anyhow!("{}:L{}: block outside of a chain", source, i + 1))?;
+                let size = number(0)?;
+                blocks.entry(t_name.clone()).or_default().insert(
+                    *t_position..*t_position + size,
+                    (chains.len() - 1, *q_position),
+                );
+                if fields.len() >= 3 {
+                    *t_position += size + number(1)?;
+                    *q_position += size + number(2)?;
+                } else {
+                    current = None;
+                }
+            }
+        }
+        blocks.values_mut().for_each(|tree| tree.index());
+
+        Ok(Liftover { chains, blocks })
+    }
+
+    /// Project the `length`-long span starting at `start` on `chr` through
+    /// the chain covering most of it; the projection spans from the first to
+    /// the last base mapped by this chain
+    pub fn lift(&self, chr: &str, start: usize, length: usize) -> Option<LiftedArm> {
+        let end = start + length;
+        // For each chain, the number of mapped bases and the span they cover
+        // on the query
+        let mut mapped: HashMap<usize, (usize, usize, usize)> = HashMap::new();
+        for e in self.blocks.get(chr)?.find(start..end) {
+            let (chain, q_start) = *e.data();
+            let (t_start, t_end) = (e.interval().start, e.interval().end);
+            let from = start.max(t_start);
+            let to = end.min(t_end);
+            let (q_from, q_to) = (q_start + from - t_start, q_start + to - t_start);
+            let m = mapped.entry(chain).or_insert((0, q_from, q_to));
+            m.0 += to - from;
+            m.1 = m.1.min(q_from);
+            m.2 = m.2.max(q_to);
+        }
+
+        // Ties are broken by the score, then by the order of the chains
+        let (chain, (_, q_start, q_end)) = mapped.into_iter().max_by(|a, b| {
+            a.1 .0
+                .cmp(&b.1 .0)
+                .then(self.chains[a.0].score.total_cmp(&self.chains[b.0].score))
+                .then(b.0.cmp(&a.0))
+        })?;
+        let chain = &self.chains[chain];
+        Some(LiftedArm {
+            chr: chain.q_name.clone(),
+            start: if chain.q_reversed {
+                chain.q_size - q_end
+            } else {
+                q_start
+            },
+            length: q_end - q_start,
+            reversed: chain.q_reversed,
+        })
+    }
+}
+
+/// Lift `sd` over to `target`, its arms being rejected when their length
+/// changes by more than a `tolerance` fraction
+pub fn lift_sd(
+    liftover: &Liftover,
+    target: &StrandResult,
+    tolerance: f64,
+    sd: &SD,
+) -> std::result::Result<SD, Rejection> {
+    let lift = |chr: &str, start: usize, length: usize| {
+        let arm = liftover
+            .lift(chr, start, length)
+            .ok_or_else(|| Rejection::Unmapped {
+                chr: chr.to_owned(),
+                start,
+            })?;
+        let fragment = target
+            .find_chr(&arm.chr)
+            .ok_or_else(|| Rejection::UnknownFragment(arm.chr.clone()))?;
+        if (arm.length as f64 - length as f64).abs() > tolerance * length as f64 {
+            return Err(Rejection::LengthChanged {
+                from: length,
+                to: arm.length,
+            });
+        }
+        Ok((fragment.position, arm))
+    };
+    let (left_offset, left) = lift(&sd.chr_left, sd.chr_left_position, sd.left_length)?;
+    let (right_offset, right) = lift(&sd.chr_right, sd.chr_right_position, sd.right_length)?;
+
+    // The relative orientation of the arms changes if only one of them is
+    // projected on the reverse strand
+    let flipped = left.reversed != right.reversed;
+    let mut lifted = SD {
+        chr_left: left.chr,
+        chr_right: right.chr,
+        global_left_position: left_offset + left.start,
+        global_right_position: right_offset + right.start,
+        chr_left_position: left.start,
+        chr_right_position: right.start,
+        left_length: left.length,
+        right_length: right.length,
+        // The sequences of the target assembly may differ
+        left_seq: None,
+        right_seq: None,
+        identity: sd.identity,
+        reversed: sd.reversed != flipped,
+        complemented: sd.complemented != flipped,
+    };
+    if lifted.global_right_position < lifted.global_left_position {
+        lifted.swap_arms();
+    }
+    Ok(lifted)
+}
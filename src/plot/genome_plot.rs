@@ -172,7 +172,7 @@ impl GenomePlotter {
                 };
 
                 // left arm
-                if sd.chr_left != COLLAPSED_NAME {
+                if !self.result.strand.is_collapsed_bin(&sd.chr_left) {
                     if let Some(chr_left_index) = self.result.strand.find_chr_index(&sd.chr_left) {
                         let left = sd.chr_left_position;
                         let start = factor * left as f64;
@@ -203,7 +203,7 @@ impl GenomePlotter {
                 };
 
                 // right arm
-                if sd.chr_right != COLLAPSED_NAME {
+                if !self.result.strand.is_collapsed_bin(&sd.chr_right) {
                     if let Some(chr_right_index) = self.result.strand.find_chr_index(&sd.chr_right)
                     {
                         let right = sd.chr_right_position;
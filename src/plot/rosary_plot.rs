@@ -30,6 +30,7 @@ enum DrawCommand {
 pub struct RosaryPlotter {
     result: RunResult,
     settings: Settings,
+    #[allow(dead_code)]
     colorizer: Box<dyn Colorizer>,
 
     clustering_margin: usize,
@@ -0,0 +1,145 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde_derive::*;
+
+/// A genomic interval, expressed as a half-open, 0-based range relative to
+/// the start of its fragment, as in the BED format
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub chr: String,
+    pub start: usize,
+    pub end: usize,
+}
+impl Region {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.end <= self.start
+    }
+
+    /// The number of bases shared with the `length`-long span starting at
+    /// `start` on `chr`
+    pub fn overlap(&self, chr: &str, start: usize, length: usize) -> usize {
+        if chr != self.chr {
+            return 0;
+        }
+        std::cmp::min(self.end, start + length).saturating_sub(std::cmp::max(self.start, start))
+    }
+}
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}-{}", self.chr, self.start, self.end)
+    }
+}
+impl FromStr for Region {
+    type Err = anyhow::Error;
+
+    /// Parse a region written as `chr:start-end`, with 0-based, half-open
+    /// coordinates; thousands separators are allowed
+    fn from_str(s: &str) -> Result<Region> {
+        let (chr, span) = s
+            .rsplit_once(':')
+            .ok_or_else(|| anyhow!("`{}`: expected a region as chr:start-end", s))?;
+        let (start, end) = span
+            .split_once('-')
+            .ok_or_else(|| anyhow!("`{}`: expected a region as chr:start-end", s))?;
+        let position = |x: &str| {
+            x.trim()
+                .replace(',', "")
+                .parse::<usize>()
+                .with_context(|| format!("`{}`: invalid position `{}`", s, x))
+        };
+        let (start, end) = (position(start)?, position(end)?);
+        if chr.is_empty() {
+            return Err(anyhow!("`{}`: missing fragment name", s));
+        }
+        if end <= start {
+            return Err(anyhow!(
+                "`{}`: end ({}) should be greater than start ({})",
+                s,
+                end,
+                start
+            ));
+        }
+
+        Ok(Region {
+            chr: chr.to_owned(),
+            start,
+            end,
+        })
+    }
+}
+
+/// Read the intervals of a BED file; only the first three columns are used
+pub fn read_bed(filename: &str) -> Result<Vec<Region>> {
+    let f = File::open(filename).with_context(|| format!("Unable to open `{}`", filename))?;
+
+    let mut regions = Vec::new();
+    for (i, line) in BufReader::new(f).lines().enumerate() {
+        let line = line.with_context(|| format!("Unable to read `{}`", filename))?;
+        if line.trim().is_empty()
+            || line.starts_with('#')
+            || line.starts_with("track")
+            || line.starts_with("browser")
+        {
+            continue;
+        }
+
+        let fields = line.split('\t').collect::<Vec<_>>();
+        if fields.len() < 3 {
+            return Err(anyhow!(
+                "{}:L{} `{}`: expected at least three columns, found {}",
+                filename,
+                i + 1,
+                line,
+                fields.len()
+            ));
+        }
+        let start = fields[1]
+            .trim()
+            .parse::<usize>()
+            .with_context(|| format!("{}:L{}: invalid start `{}`", filename, i + 1, fields[1]))?;
+        let end = fields[2]
+            .trim()
+            .parse::<usize>()
+            .with_context(|| format!("{}:L{}: invalid end `{}`", filename, i + 1, fields[2]))?;
+        if end < start {
+            return Err(anyhow!(
+                "{}:L{}: end ({}) is lower than start ({})",
+                filename,
+                i + 1,
+                end,
+                start
+            ));
+        }
+
+        regions.push(Region {
+            chr: fields[0].trim().to_owned(),
+            start,
+            end,
+        });
+    }
+
+    Ok(regions)
+}
+
+/// Write regions as a three-column BED file
+pub fn write_bed(filename: &str, regions: &[Region]) -> Result<()> {
+    let mut out = BufWriter::new(
+        File::create(filename).with_context(|| format!("Unable to create `{}`", filename))?,
+    );
+    for r in regions {
+        writeln!(out, "{}\t{}\t{}", r.chr, r.start, r.end)
+            .with_context(|| format!("Unable to write `{}`", filename))?;
+    }
+    out.flush()
+        .with_context(|| format!("Unable to write `{}`", filename))
+}
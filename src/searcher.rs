@@ -1,22 +1,29 @@
 use std::collections::HashMap;
-use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use superslice::Ext;
 
 use crate::automaton::Segment;
 use crate::divsufsort::*;
-use crate::structs::ALPHABET;
+use crate::structs::{AmbiguityPolicy, ALPHABET};
 
 pub struct Searcher {
     cache: HashMap<u64, (usize, usize)>,
     offset: usize,
+    /// How many probes have been skipped for expanding to too many patterns
+    overflowing: AtomicUsize,
 }
 
 static CACHE_LEN: usize = 8;
+/// Maximal number of concrete patterns a wildcard pattern may expand to
+pub const MAX_EXPANSIONS: usize = 256;
 const SSE_STRIDE: usize = 16;
 const AVX_STRIDE: usize = 32;
 
 /// Like above but with 32 byte slices
+///
+/// # Safety
+/// Both slices must be at least 32 bytes long, and AVX2 must be available.
 #[cfg(target_arch = "x86_64")]
 #[target_feature(enable = "avx2")]
 pub unsafe fn avx_compare_mask(one: &[u8], two: &[u8]) -> i32 {
@@ -26,6 +33,8 @@ pub unsafe fn avx_compare_mask(one: &[u8], two: &[u8]) -> i32 {
     let mask = _mm256_cmpeq_epi8(onev, twov);
     !_mm256_movemask_epi8(mask)
 }
+/// # Safety
+/// Both slices must be at least 16 bytes long, and SSE4.2 must be available.
 #[cfg(target_arch = "x86_64")]
 #[target_feature(enable = "sse4.2")]
 pub unsafe fn sse_compare_mask(one: &[u8], two: &[u8]) -> i32 {
@@ -41,6 +50,8 @@ pub unsafe fn sse_compare_mask(one: &[u8], two: &[u8]) -> i32 {
     let mask = _mm_cmpeq_epi8(onev, twov);
     (!_mm_movemask_epi8(mask)) ^ HIGH_HALF_MASK as i32
 }
+/// # Safety
+/// AVX2 must be available on the running CPU.
 #[cfg(target_arch = "x86_64")]
 #[target_feature(enable = "avx2")]
 pub unsafe fn ne_idx_avx(one: &[u8], two: &[u8]) -> std::cmp::Ordering {
@@ -49,8 +60,8 @@ pub unsafe fn ne_idx_avx(one: &[u8], two: &[u8]) -> std::cmp::Ordering {
     while idx < min_len {
         let stride_len = AVX_STRIDE.min(min_len - idx);
         let mask = avx_compare_mask(
-            &one.get_unchecked(idx..idx + stride_len),
-            &two.get_unchecked(idx..idx + stride_len),
+            one.get_unchecked(idx..idx + stride_len),
+            two.get_unchecked(idx..idx + stride_len),
         );
         // at the end of the slice the mask might include garbage bytes, so
         // we ignore matches that are OOB
@@ -72,8 +83,8 @@ pub unsafe fn ne_idx_sse(one: &[u8], two: &[u8]) -> std::cmp::Ordering {
     while idx < min_len {
         let stride_len = SSE_STRIDE.min(min_len - idx);
         let mask = sse_compare_mask(
-            &one.get_unchecked(idx..idx + stride_len),
-            &two.get_unchecked(idx..idx + stride_len),
+            one.get_unchecked(idx..idx + stride_len),
+            two.get_unchecked(idx..idx + stride_len),
         );
         if mask != 0 && idx + (mask.trailing_zeros() as usize) < min_len {
             let i = idx + mask.trailing_zeros() as usize;
@@ -93,13 +104,14 @@ pub fn ne_idx_fallback(one: &[u8], two: &[u8]) -> Option<usize> {
 
 impl Searcher {
     fn indexize(p: &[u8]) -> u64 {
-        unsafe { mem::transmute::<[u8; 8], u64>([p[0], p[1], p[2], p[3], p[4], p[5], p[6], p[7]]) }
+        u64::from_ne_bytes([p[0], p[1], p[2], p[3], p[4], p[5], p[6], p[7]])
     }
 
     pub fn new(dna: &[u8], sa: &[SAIdx], offset: usize) -> Searcher {
         let mut s = Searcher {
             cache: HashMap::new(),
             offset,
+            overflowing: AtomicUsize::new(0),
         };
 
         unsafe {
@@ -178,4 +190,88 @@ impl Searcher {
             })
             .collect()
     }
+
+    /// Like `search`, but `pattern` may contain IUPAC ambiguity codes, which
+    /// are handled according to `policy`. Patterns expanding to more than
+    /// `MAX_EXPANSIONS` concrete ones are skipped and counted.
+    ///
+    /// Ambiguity codes only act as wildcards in the pattern: in the indexed
+    /// text, they only match themselves. A pair of copies of which only one
+    /// holds ambiguity codes is thus only found when probing from the latter.
+    pub fn search_ambiguous(
+        &self,
+        dna: &[u8],
+        sa: &[SAIdx],
+        pattern: &[u8],
+        policy: AmbiguityPolicy,
+    ) -> Vec<Segment> {
+        if !pattern.iter().any(|n| expand_nucleotide(*n).len() > 1) {
+            return self.search(dna, sa, pattern);
+        }
+
+        match policy {
+            AmbiguityPolicy::Mismatch => Vec::new(),
+            AmbiguityPolicy::Wildcard => expand_pattern(pattern)
+                .map(|patterns| {
+                    patterns
+                        .iter()
+                        .flat_map(|p| self.search(dna, sa, p))
+                        .collect()
+                })
+                .unwrap_or_else(|| {
+                    self.overflowing.fetch_add(1, Ordering::Relaxed);
+                    Vec::new()
+                }),
+        }
+    }
+
+    /// How many patterns have been skipped for expanding to more than
+    /// `MAX_EXPANSIONS` concrete ones
+    pub fn overflowing(&self) -> usize {
+        self.overflowing.load(Ordering::Relaxed)
+    }
+}
+
+/// The concrete nucleotides an IUPAC code stands for
+fn expand_nucleotide(n: u8) -> &'static [u8] {
+    match n {
+        b'R' => b"AG",
+        b'Y' => b"CT",
+        b'K' => b"GT",
+        b'M' => b"AC",
+        b'S' => b"CG",
+        b'W' => b"AT",
+        b'B' => b"CGT",
+        b'D' => b"AGT",
+        b'H' => b"ACT",
+        b'V' => b"ACG",
+        b'A' => b"A",
+        b'T' => b"T",
+        b'G' => b"G",
+        b'C' => b"C",
+        _ => b"N",
+    }
+}
+
+/// All the concrete patterns matched by `pattern`, or `None` if there are
+/// more than `MAX_EXPANSIONS` of them
+fn expand_pattern(pattern: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut patterns = vec![Vec::with_capacity(pattern.len())];
+    for n in pattern {
+        let choices = expand_nucleotide(*n);
+        if patterns.len() * choices.len() > MAX_EXPANSIONS {
+            return None;
+        }
+        patterns = patterns
+            .into_iter()
+            .flat_map(|p| {
+                choices.iter().map(move |c| {
+                    let mut p = p.clone();
+                    p.push(*c);
+                    p
+                })
+            })
+            .collect();
+    }
+    Some(patterns)
 }
@@ -0,0 +1,357 @@
+//! Synthetic genomes with known duplications.
+//!
+//! Random fragments are generated, then duplication families are planted in
+//! them: a source segment is copied, mutated and written over other places of
+//! the genome. The resulting `RunResult` lists every pair of members of each
+//! family, and serves as a ground truth to assess the results of a search.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use anyhow::{bail, Context, Result};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::compare::Comparison;
+use crate::config::Config;
+use crate::structs::*;
+use crate::utils;
+
+const NUCLEOTIDES: &[u8; 4] = b"ACGT";
+const MAX_PLACEMENT_ATTEMPTS: usize = 10_000;
+
+/// The orientation of a copy relative to its source
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    Direct,
+    Reversed,
+    Complemented,
+    ReverseComplemented,
+}
+impl Orientation {
+    pub fn reversed(&self) -> bool {
+        matches!(
+            self,
+            Orientation::Reversed | Orientation::ReverseComplemented
+        )
+    }
+
+    pub fn complemented(&self) -> bool {
+        matches!(
+            self,
+            Orientation::Complemented | Orientation::ReverseComplemented
+        )
+    }
+}
+
+/// Where the copies of a family are placed relative to their source
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Placement {
+    /// On the same fragment
+    Intra,
+    /// On other fragments
+    Inter,
+    /// Anywhere
+    Any,
+}
+
+#[derive(Clone, Debug)]
+pub struct SimulationSettings {
+    pub seed: u64,
+    pub fragments: usize,
+    pub fragment_length: usize,
+    pub families: usize,
+    /// Bounds of the length of the planted duplicons
+    pub length: (usize, usize),
+    /// Bounds of the count of members of a family, including its source
+    pub copies: (usize, usize),
+    /// Expected proportion of unsubstituted nucleotides in a copy
+    pub identity: f64,
+    /// Probability of an insertion or a deletion at each nucleotide of a copy
+    pub indel_rate: f64,
+    pub orientations: Vec<Orientation>,
+    pub placement: Placement,
+}
+
+pub struct Simulation {
+    /// The generated fragments, as `(name, sequence)`
+    pub fragments: Vec<(String, Vec<u8>)>,
+    /// All the pairs of duplicons in the same family
+    pub truth: RunResult,
+}
+
+struct Member {
+    fragment: usize,
+    position: usize,
+    length: usize,
+    orientation: Orientation,
+    /// The proportion of the nucleotides of the source kept in this copy
+    identity: f64,
+}
+
+/// A mutated copy of `source`, and how many of its nucleotides it kept
+fn mutate(rng: &mut StdRng, source: &[u8], identity: f64, indel_rate: f64) -> (Vec<u8>, usize) {
+    let mut r = Vec::with_capacity(source.len());
+    let mut kept = 0;
+    for &n in source {
+        if rng.gen_bool(indel_rate) {
+            if rng.gen_bool(0.5) {
+                r.push(*NUCLEOTIDES.choose(rng).unwrap());
+            } else {
+                continue;
+            }
+        }
+        if rng.gen_bool(1.0 - identity) {
+            r.push(
+                **NUCLEOTIDES
+                    .iter()
+                    .filter(|&&x| x != n)
+                    .collect::<Vec<_>>()
+                    .choose(rng)
+                    .unwrap(),
+            );
+        } else {
+            r.push(n);
+            kept += 1;
+        }
+    }
+    (r, kept)
+}
+
+impl Simulation {
+    pub fn new(settings: &SimulationSettings) -> Result<Simulation> {
+        if settings.fragments == 0 {
+            bail!("At least one fragment is required");
+        }
+        if settings.placement == Placement::Inter && settings.fragments < 2 {
+            bail!("Inter-fragment placement requires at least two fragments");
+        }
+        if settings.copies.0 < 2 || settings.copies.0 > settings.copies.1 {
+            bail!("Families should have at least two members");
+        }
+        if settings.length.0 == 0 || settings.length.0 > settings.length.1 {
+            bail!("Invalid duplicons length bounds");
+        }
+        if settings.orientations.is_empty() {
+            bail!("At least one orientation is required");
+        }
+        if !(0.0..=1.0).contains(&settings.identity) || !(0.0..=1.0).contains(&settings.indel_rate)
+        {
+            bail!("Identity and indel rate should be between 0 and 1");
+        }
+
+        let mut rng = StdRng::seed_from_u64(settings.seed);
+        let mut fragments = (0..settings.fragments)
+            .map(|i| {
+                (
+                    format!("chr{}", i + 1),
+                    (0..settings.fragment_length)
+                        .map(|_| *NUCLEOTIDES.choose(&mut rng).unwrap())
+                        .collect::<Vec<u8>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        // The (start, end) of the already planted duplicons in each fragment
+        let mut occupied = vec![Vec::<(usize, usize)>::new(); settings.fragments];
+        let mut place = |rng: &mut StdRng, candidates: &[usize], length: usize| {
+            for _ in 0..MAX_PLACEMENT_ATTEMPTS {
+                let fragment = *candidates.choose(rng).unwrap();
+                if length >= settings.fragment_length {
+                    break;
+                }
+                let position = rng.gen_range(0..settings.fragment_length - length);
+                if occupied[fragment]
+                    .iter()
+                    .all(|&(start, end)| position + length <= start || position >= end)
+                {
+                    occupied[fragment].push((position, position + length));
+                    return Ok((fragment, position));
+                }
+            }
+            bail!("Unable to plant all the duplications; try a larger genome")
+        };
+
+        let all_fragments = (0..settings.fragments).collect::<Vec<_>>();
+        let mut families = Vec::new();
+        for _ in 0..settings.families {
+            let length = rng.gen_range(settings.length.0..=settings.length.1);
+            let (fragment, position) = place(&mut rng, &all_fragments, length)?;
+            let source = fragments[fragment].1[position..position + length].to_vec();
+            let mut members = vec![Member {
+                fragment,
+                position,
+                length,
+                orientation: Orientation::Direct,
+                identity: 1.0,
+            }];
+
+            let candidates = match settings.placement {
+                Placement::Intra => vec![fragment],
+                Placement::Inter => all_fragments
+                    .iter()
+                    .copied()
+                    .filter(|&f| f != fragment)
+                    .collect(),
+                Placement::Any => all_fragments.clone(),
+            };
+            for _ in 1..rng.gen_range(settings.copies.0..=settings.copies.1) {
+                let orientation = *settings.orientations.choose(&mut rng).unwrap();
+                let (mut copy, kept) =
+                    mutate(&mut rng, &source, settings.identity, settings.indel_rate);
+                if orientation.complemented() {
+                    copy = utils::complemented(&copy);
+                }
+                if orientation.reversed() {
+                    copy.reverse();
+                }
+                let (target, position) = place(&mut rng, &candidates, copy.len())?;
+                fragments[target].1[position..position + copy.len()].copy_from_slice(&copy);
+                members.push(Member {
+                    fragment: target,
+                    position,
+                    length: copy.len(),
+                    orientation,
+                    identity: kept as f64 / length as f64,
+                });
+            }
+            families.push(members);
+        }
+
+        let map = fragments
+            .iter()
+            .enumerate()
+            .map(|(i, (name, seq))| Start {
+                name: name.clone(),
+                position: i * settings.fragment_length,
+                length: seq.len(),
+                checksum: None,
+            })
+            .collect::<Vec<_>>();
+        let global = |m: &Member| map[m.fragment].position + m.position;
+
+        let families = families
+            .iter()
+            .map(|members| {
+                let mut family = Vec::new();
+                for (i, a) in members.iter().enumerate() {
+                    for b in members.iter().skip(i + 1) {
+                        let (left, right) = if global(a) <= global(b) {
+                            (a, b)
+                        } else {
+                            (b, a)
+                        };
+                        family.push(SD {
+                            chr_left: map[left.fragment].name.clone(),
+                            chr_right: map[right.fragment].name.clone(),
+                            global_left_position: global(left),
+                            global_right_position: global(right),
+                            chr_left_position: left.position,
+                            chr_right_position: right.position,
+                            left_length: left.length,
+                            right_length: right.length,
+                            left_seq: None,
+                            right_seq: None,
+                            // Copies diverge independently from their source
+                            identity: (100.0 * a.identity * b.identity) as f32,
+                            reversed: a.orientation.reversed() != b.orientation.reversed(),
+                            complemented: a.orientation.complemented()
+                                != b.orientation.complemented(),
+                        });
+                    }
+                }
+                family.sort_by_key(|sd| sd.global_left_position);
+                family
+            })
+            .collect::<Vec<_>>();
+
+        let mut run_settings = Config::default().into_settings()?;
+        run_settings.min_duplication_length = settings.length.0;
+        Ok(Simulation {
+            truth: RunResult {
+                strand: StrandResult {
+                    name: format!("simulated-{}", settings.seed),
+                    length: map.iter().map(|c| c.length).sum(),
+                    map,
+                    collapsed: Vec::new(),
+                },
+                settings: run_settings,
+                families,
+            },
+            fragments,
+        })
+    }
+
+    pub fn write_fasta(&self, filename: &str) -> Result<()> {
+        let mut out = BufWriter::new(
+            File::create(filename).with_context(|| format!("Unable to create `{}`", filename))?,
+        );
+        for (name, seq) in &self.fragments {
+            writeln!(out, ">{}", name)?;
+            for line in seq.chunks(80) {
+                out.write_all(line)?;
+                writeln!(out)?;
+            }
+        }
+        out.flush()
+            .with_context(|| format!("Unable to write `{}`", filename))
+    }
+}
+
+/// How well a result matches a ground truth
+#[derive(Debug)]
+pub struct Evaluation {
+    /// How many duplicons of the truth have been found
+    pub found: usize,
+    /// How many duplicons the truth contains
+    pub expected: usize,
+    /// How many reported duplicons match the truth
+    pub correct: usize,
+    /// How many duplicons have been reported
+    pub reported: usize,
+}
+impl Evaluation {
+    /// Compare the duplicons of `result` with the ones of `truth` sharing the
+    /// given orientation. Two duplicons match if their arms overlap one
+    /// another by at least `min_overlap` of their length, as in `Comparison`.
+    pub fn new(
+        truth: &RunResult,
+        result: &RunResult,
+        reversed: bool,
+        complemented: bool,
+        min_overlap: f64,
+    ) -> Evaluation {
+        let expected = truth
+            .families
+            .iter()
+            .flatten()
+            .filter(|sd| sd.reversed == reversed && sd.complemented == complemented)
+            .collect::<Vec<_>>();
+        let reported = result.families.iter().flatten().collect::<Vec<_>>();
+
+        let comparison = Comparison::from_duplicons(expected, reported, min_overlap);
+        let matched = |sds: &[(&SD, bool)]| sds.iter().filter(|(_, matched)| *matched).count();
+        Evaluation {
+            found: matched(&comparison.reference),
+            expected: comparison.reference.len(),
+            correct: matched(&comparison.query),
+            reported: comparison.query.len(),
+        }
+    }
+
+    pub fn recall(&self) -> f64 {
+        if self.expected == 0 {
+            1.0
+        } else {
+            self.found as f64 / self.expected as f64
+        }
+    }
+
+    pub fn precision(&self) -> f64 {
+        if self.reported == 0 {
+            1.0
+        } else {
+            self.correct as f64 / self.reported as f64
+        }
+    }
+}
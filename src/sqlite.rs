@@ -0,0 +1,337 @@
+//! Storage of `RunResult`s in SQLite databases.
+//!
+//! The database holds the following tables:
+//!   - `meta`, mapping `version`, `strand_name`, `strand_length` and
+//!     `settings` (as JSON) to their values;
+//!   - `fragments`, the fragments map, plus the fragments only referenced by
+//!     duplicons, flagged by a null `position`;
+//!   - `collapsed`, the original fragments if some of them have been
+//!     collapsed, with the fragment they lie in and their offset there;
+//!   - `families`, with their index and their number of duplicons;
+//!   - `duplicons`, with the same fields as `SD`, and their family;
+//!   - `arms`, an R*Tree indexing the arms of the duplicons on their fragment
+//!     and their fragment-relative coordinates, as a half-open interval.
+//!
+//! The R*Tree stores its bounds as 32-bit floats, which are exact up to 2^24;
+//! past it, SQLite rounds them outwards, so that a query on `arms` may return
+//! a few arms ending just before or starting just after the queried region,
+//! but never misses one. The exact coordinates are those of `duplicons`.
+//!
+//! For instance, the duplicons having an arm overlapping chr1:1000-2000 can be
+//! found with:
+//! ```sql
+//! SELECT d.* FROM arms a
+//!   JOIN fragments f ON f.id BETWEEN a.fragment_min AND a.fragment_max
+//!   JOIN duplicons d ON d.id = a.duplicon
+//!   WHERE f.name = 'chr1' AND a.start_min < 2000 AND a.end_max > 1000;
+//! ```
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::structs::*;
+
+pub const MAGIC: &[u8; 16] = b"SQLite format 3\0";
+pub const VERSION: i64 = 2;
+
+const SCHEMA: &str = "
+CREATE TABLE meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+CREATE TABLE fragments (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    position INTEGER,
+    length INTEGER,
+    checksum INTEGER
+);
+CREATE TABLE collapsed (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    length INTEGER NOT NULL,
+    checksum INTEGER,
+    into_fragment TEXT NOT NULL,
+    offset INTEGER NOT NULL
+);
+CREATE TABLE families (id INTEGER PRIMARY KEY, size INTEGER NOT NULL);
+CREATE TABLE duplicons (
+    id INTEGER PRIMARY KEY,
+    family INTEGER NOT NULL REFERENCES families(id),
+    chr_left TEXT NOT NULL,
+    chr_right TEXT NOT NULL,
+    global_left_position INTEGER NOT NULL,
+    global_right_position INTEGER NOT NULL,
+    chr_left_position INTEGER NOT NULL,
+    chr_right_position INTEGER NOT NULL,
+    left_length INTEGER NOT NULL,
+    right_length INTEGER NOT NULL,
+    left_seq TEXT,
+    right_seq TEXT,
+    identity REAL NOT NULL,
+    reversed INTEGER NOT NULL,
+    complemented INTEGER NOT NULL
+);
+CREATE INDEX duplicons_family ON duplicons(family);
+CREATE VIRTUAL TABLE arms USING rtree(
+    id, fragment_min, fragment_max, start_min, end_max,
+    +duplicon INTEGER, +side TEXT
+);
+";
+
+/// Whether `prefix` is the beginning of an SQLite database
+pub fn is_sqlite(prefix: &[u8]) -> bool {
+    prefix.starts_with(MAGIC)
+}
+
+/// Writes a `RunResult` into an SQLite database, family by family, within a
+/// single transaction
+pub struct Writer {
+    connection: Connection,
+    fragments: HashMap<String, i64>,
+    next_duplicon: i64,
+}
+impl Writer {
+    /// Create the schema in the new database `filename`, and store the
+    /// metadata and fragments map of a result
+    pub fn create(filename: &str, strand: &StrandResult, settings: &RunSettings) -> Result<Writer> {
+        let connection = Connection::open(filename)
+            .with_context(|| format!("Unable to create `{}`", filename))?;
+        connection.execute_batch("PRAGMA journal_mode = OFF; PRAGMA synchronous = OFF;")?;
+        connection.execute_batch(SCHEMA)?;
+        connection.execute_batch("BEGIN")?;
+
+        let mut writer = Writer {
+            connection,
+            fragments: HashMap::new(),
+            next_duplicon: 0,
+        };
+        for (key, value) in [
+            ("version", VERSION.to_string()),
+            ("strand_name", strand.name.clone()),
+            ("strand_length", strand.length.to_string()),
+            ("settings", serde_json::to_string(settings)?),
+        ] {
+            writer.connection.execute(
+                "INSERT INTO meta (key, value) VALUES (?1, ?2)",
+                params![key, value],
+            )?;
+        }
+        for chr in &strand.map {
+            writer.connection.execute(
+                "INSERT INTO fragments (id, name, position, length, checksum) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    writer.fragments.len() as i64,
+                    chr.name,
+                    chr.position as i64,
+                    chr.length as i64,
+                    // SQLite integers are signed
+                    chr.checksum.map(|c| c as i64),
+                ],
+            )?;
+            writer
+                .fragments
+                .insert(chr.name.clone(), writer.fragments.len() as i64);
+        }
+        for (i, c) in strand.collapsed.iter().enumerate() {
+            writer.connection.execute(
+                "INSERT INTO collapsed VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    i as i64,
+                    c.fragment.name,
+                    c.fragment.position as i64,
+                    c.fragment.length as i64,
+                    c.fragment.checksum.map(|c| c as i64),
+                    c.into,
+                    c.offset as i64,
+                ],
+            )?;
+        }
+        Ok(writer)
+    }
+
+    fn fragment(&mut self, name: &str) -> Result<i64> {
+        if let Some(id) = self.fragments.get(name) {
+            return Ok(*id);
+        }
+        let id = self.fragments.len() as i64;
+        self.connection.execute(
+            "INSERT INTO fragments (id, name) VALUES (?1, ?2)",
+            params![id, name],
+        )?;
+        self.fragments.insert(name.to_owned(), id);
+        Ok(id)
+    }
+
+    pub fn family(&mut self, i: usize, family: &SDsFamily) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO families (id, size) VALUES (?1, ?2)",
+            params![i as i64, family.len() as i64],
+        )?;
+        for sd in family {
+            let id = self.next_duplicon;
+            self.next_duplicon += 1;
+            self.connection
+                .prepare_cached(
+                    "INSERT INTO duplicons VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+                )?
+                .execute(params![
+                    id,
+                    i as i64,
+                    sd.chr_left,
+                    sd.chr_right,
+                    sd.global_left_position as i64,
+                    sd.global_right_position as i64,
+                    sd.chr_left_position as i64,
+                    sd.chr_right_position as i64,
+                    sd.left_length as i64,
+                    sd.right_length as i64,
+                    sd.left_seq,
+                    sd.right_seq,
+                    sd.identity,
+                    sd.reversed,
+                    sd.complemented,
+                ])?;
+
+            for (side, chr, start, length) in [
+                ("left", &sd.chr_left, sd.chr_left_position, sd.left_length),
+                (
+                    "right",
+                    &sd.chr_right,
+                    sd.chr_right_position,
+                    sd.right_length,
+                ),
+            ] {
+                let fragment = self.fragment(chr)?;
+                self.connection
+                    .prepare_cached(
+                        "INSERT INTO arms (fragment_min, fragment_max, start_min, end_max, duplicon, side) VALUES (?1, ?1, ?2, ?3, ?4, ?5)",
+                    )?
+                    .execute(params![fragment, start as i64, (start + length) as i64, id, side])?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<()> {
+        self.connection.execute_batch("COMMIT")?;
+        self.connection
+            .close()
+            .map_err(|(_, e)| e)
+            .context("Unable to close database")
+    }
+}
+
+/// Read back a `RunResult` stored in the SQLite database `filename`
+pub fn read(filename: &str) -> Result<RunResult> {
+    let connection =
+        Connection::open_with_flags(filename, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|| format!("Cannot read data from `{}`", filename))?;
+    let meta = |key: &str| -> Result<String> {
+        connection
+            .query_row("SELECT value FROM meta WHERE key = ?1", [key], |r| r.get(0))
+            .optional()?
+            .ok_or_else(|| {
+                anyhow!(
+                    "`{}` is not an ASGART database: no `{}` found",
+                    filename,
+                    key
+                )
+            })
+    };
+
+    let version = meta("version")?.parse::<i64>()?;
+    if version > VERSION {
+        return Err(anyhow!(
+            "`{}` has been written by a newer version of ASGART (format {}, expected at most {})",
+            filename,
+            version,
+            VERSION
+        ));
+    }
+
+    let map = connection
+        .prepare("SELECT name, position, length, checksum FROM fragments WHERE position IS NOT NULL ORDER BY id")?
+        .query_map([], |r| {
+            Ok(Start {
+                name: r.get(0)?,
+                position: r.get::<_, i64>(1)? as usize,
+                length: r.get::<_, i64>(2)? as usize,
+                checksum: r.get::<_, Option<i64>>(3)?.map(|c| c as u64),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    // Version 1 did not store collapsed fragments
+    let collapsed = if version >= 2 {
+        connection
+            .prepare("SELECT name, position, length, checksum, into_fragment, offset FROM collapsed ORDER BY id")?
+            .query_map([], |r| {
+                Ok(CollapsedFragment {
+                    fragment: Start {
+                        name: r.get(0)?,
+                        position: r.get::<_, i64>(1)? as usize,
+                        length: r.get::<_, i64>(2)? as usize,
+                        checksum: r.get::<_, Option<i64>>(3)?.map(|c| c as u64),
+                    },
+                    into: r.get(4)?,
+                    offset: r.get::<_, i64>(5)? as usize,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?
+    } else {
+        Vec::new()
+    };
+
+    let mut families: Vec<SDsFamily> = Vec::new();
+    let mut family_ids = HashMap::new();
+    for family in connection
+        .prepare("SELECT id, size FROM families ORDER BY id")?
+        .query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, i64>(1)?)))?
+    {
+        let (id, size) = family?;
+        family_ids.insert(id, families.len());
+        families.push(Vec::with_capacity(size as usize));
+    }
+    let mut statement = connection.prepare(
+        "SELECT family, chr_left, chr_right, global_left_position, global_right_position,
+                chr_left_position, chr_right_position, left_length, right_length,
+                left_seq, right_seq, identity, reversed, complemented
+         FROM duplicons ORDER BY id",
+    )?;
+    let mut rows = statement.query([])?;
+    while let Some(r) = rows.next()? {
+        let family = r.get::<_, i64>(0)?;
+        let sd = SD {
+            chr_left: r.get(1)?,
+            chr_right: r.get(2)?,
+            global_left_position: r.get::<_, i64>(3)? as usize,
+            global_right_position: r.get::<_, i64>(4)? as usize,
+            chr_left_position: r.get::<_, i64>(5)? as usize,
+            chr_right_position: r.get::<_, i64>(6)? as usize,
+            left_length: r.get::<_, i64>(7)? as usize,
+            right_length: r.get::<_, i64>(8)? as usize,
+            left_seq: r.get(9)?,
+            right_seq: r.get(10)?,
+            identity: r.get::<_, f64>(11)? as f32,
+            reversed: r.get(12)?,
+            complemented: r.get(13)?,
+        };
+        family_ids
+            .get(&family)
+            .map(|&i| &mut families[i])
+            .ok_or_else(|| anyhow!("`{}`: duplicon of unknown family {}", filename, family))?
+            .push(sd);
+    }
+
+    Ok(RunResult {
+        strand: StrandResult {
+            name: meta("strand_name")?,
+            length: meta("strand_length")?.parse()?,
+            map,
+            collapsed,
+        },
+        settings: serde_json::from_str(&meta("settings")?)
+            .with_context(|| format!("`{}`: invalid settings", filename))?,
+        families,
+    })
+}
@@ -0,0 +1,230 @@
+//! Summary statistics over a set of duplications.
+//!
+//! Statistics are accumulated family by family, so that they can be computed
+//! while streaming large results.
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use serde_derive::Serialize;
+
+use crate::structs::*;
+
+/// The lower bounds of the bins of the length histogram, in bp
+pub const LENGTH_BINS: [f64; 11] = [0.0, 1e3, 2e3, 5e3, 1e4, 2e4, 5e4, 1e5, 2e5, 5e5, 1e6];
+
+/// The lower bounds of the bins of the identity histogram, in percents
+pub const IDENTITY_BINS: [f64; 12] = [
+    0.0, 90.0, 91.0, 92.0, 93.0, 94.0, 95.0, 96.0, 97.0, 98.0, 99.0, 100.0,
+];
+
+/// A bin of a histogram, spanning [from, to[, or [from, ∞[ for the last one
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Bin {
+    pub from: f64,
+    pub to: Option<f64>,
+    pub count: usize,
+}
+
+fn histogram(bounds: &[f64]) -> Vec<Bin> {
+    bounds
+        .iter()
+        .enumerate()
+        .map(|(i, &from)| Bin {
+            from,
+            to: bounds.get(i + 1).copied(),
+            count: 0,
+        })
+        .collect()
+}
+
+fn count(histogram: &mut [Bin], x: f64) {
+    if let Some(bin) = histogram.iter_mut().rev().find(|b| b.from <= x) {
+        bin.count += 1;
+    }
+}
+
+/// The share of a fragment covered by duplicons
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Coverage {
+    pub name: String,
+    /// The length of the fragment, if it is in the fragments map
+    pub length: Option<usize>,
+    /// The number of arms standing on the fragment
+    pub arms: usize,
+    /// The number of bases covered by at least an arm
+    pub covered: usize,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub families: usize,
+    pub duplicons: usize,
+    pub intra: usize,
+    pub inter: usize,
+    pub direct: usize,
+    /// Reversed but not complemented
+    pub reversed: usize,
+    /// Complemented but not reversed
+    pub complemented: usize,
+    /// Both reversed and complemented
+    pub palindromic: usize,
+    /// The length of the genome, as given by the fragments map
+    pub genome_length: usize,
+    /// The number of bases of the genome covered by at least an arm
+    pub covered: usize,
+    /// The coverage of each fragment bearing arms or in the fragments map
+    pub fragments: Vec<Coverage>,
+    /// The histogram of the lengths of the longest arm of the duplicons
+    pub lengths: Vec<Bin>,
+    /// The histogram of the identities of the duplicons for which it has
+    /// been computed
+    pub identities: Vec<Bin>,
+    /// The number of duplicons whose identity has not been computed
+    pub unknown_identity: usize,
+    /// The number of families of each size
+    pub family_sizes: BTreeMap<usize, usize>,
+}
+
+/// Accumulates the statistics of families
+pub struct StatsBuilder {
+    stats: Stats,
+    /// The arms on each fragment, as (start, end)
+    arms: HashMap<String, Vec<(usize, usize)>>,
+}
+impl Default for StatsBuilder {
+    fn default() -> Self {
+        StatsBuilder {
+            stats: Stats {
+                families: 0,
+                duplicons: 0,
+                intra: 0,
+                inter: 0,
+                direct: 0,
+                reversed: 0,
+                complemented: 0,
+                palindromic: 0,
+                genome_length: 0,
+                covered: 0,
+                fragments: Vec::new(),
+                lengths: histogram(&LENGTH_BINS),
+                identities: histogram(&IDENTITY_BINS),
+                unknown_identity: 0,
+                family_sizes: BTreeMap::new(),
+            },
+            arms: HashMap::new(),
+        }
+    }
+}
+impl StatsBuilder {
+    pub fn add_family(&mut self, family: &SDsFamily) {
+        let stats = &mut self.stats;
+        stats.families += 1;
+        *stats.family_sizes.entry(family.len()).or_default() += 1;
+        for sd in family {
+            stats.duplicons += 1;
+            if sd.chr_left == sd.chr_right {
+                stats.intra += 1;
+            } else {
+                stats.inter += 1;
+            }
+            match (sd.reversed, sd.complemented) {
+                (false, false) => stats.direct += 1,
+                (true, false) => stats.reversed += 1,
+                (false, true) => stats.complemented += 1,
+                (true, true) => stats.palindromic += 1,
+            }
+            count(
+                &mut stats.lengths,
+                sd.left_length.max(sd.right_length) as f64,
+            );
+            if sd.identity > 0.0 {
+                count(&mut stats.identities, f64::from(sd.identity));
+            } else {
+                stats.unknown_identity += 1;
+            }
+
+            for (chr, start, length) in [
+                (&sd.chr_left, sd.chr_left_position, sd.left_length),
+                (&sd.chr_right, sd.chr_right_position, sd.right_length),
+            ] {
+                self.arms
+                    .entry(chr.clone())
+                    .or_default()
+                    .push((start, start + length));
+            }
+        }
+    }
+
+    /// Compute the coverage of the fragments of `strand`, followed by the
+    /// fragments absent from its map
+    pub fn finish(mut self, strand: &StrandResult) -> Stats {
+        let mut names = strand
+            .map
+            .iter()
+            .map(|c| c.name.clone())
+            .collect::<Vec<_>>();
+        let mut others = self
+            .arms
+            .keys()
+            .filter(|n| !strand.has_chr(n))
+            .cloned()
+            .collect::<Vec<_>>();
+        others.sort();
+        names.extend(others);
+
+        for name in names {
+            let mut arms = self.arms.remove(&name).unwrap_or_default();
+            arms.sort_unstable();
+            // Sum the lengths of the unions of overlapping arms
+            let mut covered = 0;
+            let mut current: Option<(usize, usize)> = None;
+            for &(start, end) in &arms {
+                match current.as_mut() {
+                    Some((_, current_end)) if start <= *current_end => {
+                        *current_end = (*current_end).max(end)
+                    }
+                    _ => {
+                        if let Some((s, e)) = current {
+                            covered += e - s;
+                        }
+                        current = Some((start, end));
+                    }
+                }
+            }
+            if let Some((s, e)) = current {
+                covered += e - s;
+            }
+
+            self.stats.covered += covered;
+            self.stats.fragments.push(Coverage {
+                length: strand.find_chr(&name).map(|c| c.length),
+                name,
+                arms: arms.len(),
+                covered,
+            });
+        }
+        self.stats.genome_length = strand.length;
+        self.stats
+    }
+}
+
+impl Stats {
+    pub fn new(result: &RunResult) -> Stats {
+        let mut builder = StatsBuilder::default();
+        for family in &result.families {
+            builder.add_family(family);
+        }
+        builder.finish(&result.strand)
+    }
+
+    /// Compute the statistics of a stream of families without loading them
+    pub fn from_stream(stream: FamilyStream) -> Result<Stats> {
+        let strand = stream.strand.clone();
+        let mut builder = StatsBuilder::default();
+        for family in stream {
+            builder.add_family(&family?);
+        }
+        Ok(builder.finish(&strand))
+    }
+}
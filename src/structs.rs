@@ -1,14 +1,38 @@
 use ::rayon::prelude::*;
 use anyhow::{anyhow, Context, Result};
 use regex::Regex;
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
 use serde_derive::*;
 use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
-use std::io::Read;
+use std::io::{BufReader, Read};
+use std::sync::mpsc::{self, SyncSender};
+use std::thread;
+
+use crate::{
+    aliases::Aliases,
+    binary::{self, BinaryReader},
+    collapse::{self, CollapseRule},
+    filter::Filter,
+    liftover::{self, Liftover, Rejection},
+    regions::Region,
+    sqlite,
+};
 
 pub const COLLAPSED_NAME: &str = "ASGART_COLLAPSED";
 pub const ALPHABET: [u8; 5] = [b'A', b'T', b'G', b'C', b'N'];
 pub const ALPHABET_MASKED: [u8; 5] = [b'a', b't', b'g', b'c', b'n'];
+/// IUPAC codes standing for more than a single nucleotide, N excepted
+pub const AMBIGUITY_CODES: [u8; 10] = [b'R', b'Y', b'K', b'M', b'S', b'W', b'B', b'D', b'H', b'V'];
+
+pub const DEFAULT_N_THRESHOLD: usize = 5000;
+pub const DEFAULT_MAX_N_CONTENT: f32 = 0.2;
+pub const DEFAULT_MAX_ARMS: usize = 200;
+pub const DEFAULT_RELATIVE_GAP: f64 = 0.1;
+
+/// How many parsed families may be buffered ahead of a `FamilyStream` consumer
+const STREAM_BUFFER: usize = 64;
 
 lazy_static::lazy_static! {
     static ref TR: HashMap<u8, u8> = maplit::hashmap!{
@@ -22,9 +46,41 @@ lazy_static::lazy_static! {
         b'g' => b'c',
         b'c' => b'g',
         b'n' => b'n',
+
+        b'R' => b'Y',
+        b'Y' => b'R',
+        b'K' => b'M',
+        b'M' => b'K',
+        b'S' => b'S',
+        b'W' => b'W',
+        b'B' => b'V',
+        b'V' => b'B',
+        b'D' => b'H',
+        b'H' => b'D',
+        b'r' => b'y',
+        b'y' => b'r',
+        b'k' => b'm',
+        b'm' => b'k',
+        b's' => b's',
+        b'w' => b'w',
+        b'b' => b'v',
+        b'v' => b'b',
+        b'd' => b'h',
+        b'h' => b'd',
     };
 }
 
+/// Whether `filename` is an SQLite database rather than JSON or binary results
+fn is_sqlite_file(filename: &str) -> Result<bool> {
+    let f =
+        File::open(filename).with_context(|| format!("Cannot read data from `{}`", filename))?;
+    let mut prefix = Vec::with_capacity(sqlite::MAGIC.len());
+    f.take(sqlite::MAGIC.len() as u64)
+        .read_to_end(&mut prefix)
+        .with_context(|| format!("Cannot read data from `{}`", filename))?;
+    Ok(sqlite::is_sqlite(&prefix))
+}
+
 fn complement(seq: &mut [u8]) {
     for n in seq.iter_mut() {
         *n = *TR
@@ -33,35 +89,114 @@ fn complement(seq: &mut [u8]) {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+/// How probes containing IUPAC ambiguity codes are handled
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AmbiguityPolicy {
+    /// Ambiguity codes match nothing, and such probes are skipped
+    #[default]
+    Mismatch,
+    /// Ambiguity codes match any of the nucleotides they stand for
+    Wildcard,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct RunSettings {
     pub probe_size: usize,
     pub max_gap_size: u32,
     pub min_duplication_length: usize,
     pub max_cardinality: usize,
     pub trim: Option<(usize, usize)>,
+    /// If set, only these regions have been probed for duplications
+    #[serde(default)]
+    pub regions: Option<Vec<Region>>,
+    /// If set, these regions have been hard-masked and never searched
+    #[serde(default)]
+    pub excluded: Option<Vec<Region>>,
 
-    #[serde(skip_serializing)]
+    /// Whether reversed duplications have been looked for
     #[serde(default)]
     pub reverse: bool,
-    #[serde(skip_serializing)]
+    /// Whether complemented duplications have been looked for
     #[serde(default)]
     pub complement: bool,
     pub skip_masked: bool,
+    #[serde(default)]
+    pub ambiguity: AmbiguityPolicy,
+    /// Stretches of N longer than this split fragments into independent chunks
+    #[serde(default = "default_n_threshold")]
+    pub n_threshold: usize,
+    /// Duplicons with a larger proportion of N are discarded
+    #[serde(default = "default_max_n_content")]
+    pub max_n_content: f32,
+    /// Above this many arms, the inactive and too short ones are pruned
+    #[serde(default = "default_max_arms")]
+    pub max_arms: usize,
+    /// Distance allowed when extending an arm, relative to its length
+    #[serde(default = "default_relative_gap")]
+    pub relative_gap: f64,
+    /// The preset these settings are based upon, if any
+    #[serde(default)]
+    pub preset: Option<String>,
 
     #[serde(skip_serializing)]
     #[serde(default)]
     pub threads_count: usize,
-    #[serde(skip_serializing)]
+    /// Whether the Levenshtein distance between arms has been computed
     #[serde(default)]
     pub compute_score: bool,
 }
 
+impl RunSettings {
+    /// Whether results obtained with these settings and `other` are
+    /// interchangeable; the excluded regions are left out, as they are
+    /// reflected in the fragments checksums
+    pub fn is_compatible(&self, other: &RunSettings) -> bool {
+        let comparable = |settings: &RunSettings| {
+            let mut value = serde_json::to_value(settings).unwrap_or_default();
+            if let Some(value) = value.as_object_mut() {
+                value.remove("preset");
+                value.remove("excluded");
+            }
+            value
+        };
+        comparable(self) == comparable(other)
+    }
+}
+
+fn default_n_threshold() -> usize {
+    DEFAULT_N_THRESHOLD
+}
+fn default_max_n_content() -> f32 {
+    DEFAULT_MAX_N_CONTENT
+}
+fn default_max_arms() -> usize {
+    DEFAULT_MAX_ARMS
+}
+fn default_relative_gap() -> f64 {
+    DEFAULT_RELATIVE_GAP
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Start {
     pub name: String,
     pub position: usize,
     pub length: usize,
+    /// The FNV-1a hash of the fragment, as processed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<u64>,
+}
+
+/// Where a fragment of the original map lies once fragments have been
+/// collapsed
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CollapsedFragment {
+    /// The fragment, as it stood in the original map
+    pub fragment: Start,
+    /// The fragment it now lies in, either itself or a collapsed bin
+    pub into: String,
+    /// Its position in `into`
+    pub offset: usize,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -69,8 +204,20 @@ pub struct StrandResult {
     pub name: String,
     pub length: usize,
     pub map: Vec<Start>,
+    /// The original fragments, if some of them have been collapsed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub collapsed: Vec<CollapsedFragment>,
 }
 impl StrandResult {
+    /// Whether `name` is a bin of collapsed fragments
+    pub fn is_collapsed_bin(&self, name: &str) -> bool {
+        name == COLLAPSED_NAME
+            || self
+                .collapsed
+                .iter()
+                .any(|c| c.into == name && c.fragment.name != name)
+    }
+
     pub fn has_chr(&self, name: &str) -> bool {
         self.map.iter().any(|chr| chr.name == name)
     }
@@ -79,6 +226,19 @@ impl StrandResult {
         self.map.iter().find(|chr| chr.name == name)
     }
 
+    /// The fragments of `self` that are found unchanged in `other`
+    pub fn unchanged_fragments<'a>(&'a self, other: &StrandResult) -> Vec<&'a Start> {
+        self.map
+            .iter()
+            .filter(|c| {
+                c.checksum.is_some()
+                    && other
+                        .find_chr(&c.name)
+                        .is_some_and(|o| o.length == c.length && o.checksum == c.checksum)
+            })
+            .collect()
+    }
+
     pub fn find_chr_index(&self, name: &str) -> Option<usize> {
         self.map.iter().position(|chr| chr.name == name)
     }
@@ -90,6 +250,13 @@ impl StrandResult {
     }
 }
 
+/// Which arms of a duplicon must overlap a region for it to be kept
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegionOverlap {
+    AnyArm,
+    BothArms,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RunResult {
     pub strand: StrandResult,
@@ -98,17 +265,33 @@ pub struct RunResult {
 }
 impl RunResult {
     pub fn from_stdin() -> Result<RunResult> {
-        serde_json::from_reader(std::io::stdin())
-            .with_context(|| "Failed to parse JSON data from STDIN")
+        RunResult::from_reader(std::io::stdin().lock())
+            .with_context(|| "Failed to parse data from STDIN")
+    }
+
+    /// Read either JSON or binary results from `reader`
+    pub fn from_reader<R: Read>(reader: R) -> Result<RunResult> {
+        let (is_binary, reader) = binary::sniff(reader)?;
+        if is_binary {
+            let (strand, settings, families) = BinaryReader::new(BufReader::new(reader))?;
+            Ok(RunResult {
+                strand,
+                settings,
+                families: families.collect::<Result<Vec<_>>>()?,
+            })
+        } else {
+            serde_json::from_reader(BufReader::new(reader)).context("Invalid JSON data")
+        }
     }
 
     pub fn from_file(filename: &str) -> Result<RunResult> {
-        let mut f = File::open(filename)
+        if is_sqlite_file(filename)? {
+            return RunResult::from_sqlite(filename);
+        }
+        let f = File::open(filename)
             .with_context(|| format!("Cannot read data from `{}`", filename))?;
-        let mut s = String::new();
-        let _ = f.read_to_string(&mut s);
-        serde_json::from_str(&s)
-            .with_context(|| format!("Failed to parse JSON data from `{}`", filename))
+        RunResult::from_reader(f)
+            .with_context(|| format!("Failed to parse data from `{}`", filename))
     }
 
     pub fn from_files(filenames: &[String]) -> Result<RunResult> {
@@ -128,7 +311,7 @@ impl RunResult {
         }
 
         let r = RunResult {
-            settings: results[0].settings,
+            settings: results[0].settings.clone(),
             strand: results[0].strand.clone(),
             families: results
                 .iter()
@@ -140,6 +323,55 @@ impl RunResult {
         Ok(r)
     }
 
+    pub fn stream_stdin() -> Result<FamilyStream> {
+        FamilyStream::from_reader(std::io::stdin(), "STDIN")
+    }
+
+    /// Read results from an SQLite database written by `SQLiteExporter`
+    pub fn from_sqlite(filename: &str) -> Result<RunResult> {
+        sqlite::read(filename)
+    }
+
+    /// Databases cannot be streamed, and are thus loaded in a whole
+    pub fn stream_file(filename: &str) -> Result<FamilyStream> {
+        if is_sqlite_file(filename)? {
+            return Ok(RunResult::from_sqlite(filename)?.into());
+        }
+        let f = File::open(filename)
+            .with_context(|| format!("Cannot read data from `{}`", filename))?;
+        FamilyStream::from_reader(f, &format!("`{}`", filename))
+    }
+
+    /// Lazily chain the families of several result files, which must stem
+    /// from the same source
+    pub fn stream_files(filenames: &[String]) -> Result<FamilyStream> {
+        let mut streams = filenames
+            .iter()
+            .map(|filename| RunResult::stream_file(filename))
+            .collect::<Result<Vec<_>>>()?;
+        if streams.is_empty() {
+            return Err(anyhow!("No input files provided"));
+        }
+
+        for stream in &streams {
+            if stream.strand.name != streams[0].strand.name {
+                return Err(anyhow!(
+                    "Trying to combine ASGART files from different sources: `{}` and `{}`",
+                    stream.strand.name,
+                    streams[0].strand.name,
+                ));
+            }
+        }
+
+        let mut first = streams.remove(0);
+        first.families = Box::new(
+            first
+                .families
+                .chain(streams.into_iter().flat_map(|stream| stream.families)),
+        );
+        Ok(first)
+    }
+
     pub fn remove_direct(&mut self) {
         self.families
             .iter_mut()
@@ -176,11 +408,12 @@ impl RunResult {
     }
 
     pub fn remove_inter_relaxed(&mut self) {
+        let strand = &self.strand;
         self.families.iter_mut().for_each(|family| {
             family.retain(|sd| {
                 (sd.chr_left == sd.chr_right)
-                    || sd.chr_left == COLLAPSED_NAME
-                    || sd.chr_right == COLLAPSED_NAME
+                    || strand.is_collapsed_bin(&sd.chr_left)
+                    || strand.is_collapsed_bin(&sd.chr_right)
             })
         });
         self.families.retain(|f| !f.is_empty());
@@ -197,6 +430,25 @@ impl RunResult {
         self.families.retain(|family| family.len() <= m)
     }
 
+    /// Only keep the duplicons satisfying `filter`; family-level variables
+    /// are evaluated on the families before filtering
+    pub fn filter_duplicons(&mut self, filter: &Filter) {
+        self.families.iter_mut().for_each(|family| {
+            let mut keep = family
+                .iter()
+                .map(|sd| filter.matches(sd, family))
+                .collect::<Vec<_>>()
+                .into_iter();
+            family.retain(|_| keep.next().unwrap());
+        });
+        self.families.retain(|f| !f.is_empty());
+    }
+
+    /// Only keep the families satisfying the family-level `filter`
+    pub fn filter_families(&mut self, filter: &Filter) {
+        self.families.retain(|family| filter.matches_family(family))
+    }
+
     /// Ensure that
     /// 1. No empty families are kept
     /// 2. Only used fragments are kept
@@ -227,6 +479,165 @@ impl RunResult {
         }
     }
 
+    /// The duplicons whose both legs stand on fragments found unchanged in
+    /// `strand`, with their global coordinates remapped to its layout
+    pub fn unchanged_families(&self, strand: &StrandResult) -> Vec<SDsFamily> {
+        let unchanged = self.strand.unchanged_fragments(strand);
+        let position = |name: &str| {
+            unchanged
+                .iter()
+                .any(|c| c.name == name)
+                .then(|| strand.find_chr(name).map(|c| c.position))
+                .flatten()
+        };
+
+        self.families
+            .iter()
+            .map(|family| {
+                family
+                    .iter()
+                    .filter_map(|sd| {
+                        let left = position(&sd.chr_left)?;
+                        let right = position(&sd.chr_right)?;
+                        Some(SD {
+                            global_left_position: left + sd.chr_left_position,
+                            global_right_position: right + sd.chr_right_position,
+                            ..sd.clone()
+                        })
+                    })
+                    .collect::<SDsFamily>()
+            })
+            .filter(|family| !family.is_empty())
+            .collect()
+    }
+
+    /// Keep the duplicons overlapping `regions` according to `overlap`. If
+    /// `clip` is set, the fragments map is replaced by the regions, each
+    /// named `chr:start-end`, and the arms are clipped and rebased to the
+    /// region they overlap the most; as arms out of the regions could not be
+    /// placed, both arms must then overlap the regions, whatever `overlap`.
+    pub fn keep_regions(
+        &mut self,
+        regions: &[Region],
+        overlap: RegionOverlap,
+        clip: bool,
+    ) -> Result<()> {
+        for r in regions {
+            let chr = self
+                .strand
+                .find_chr(&r.chr)
+                .ok_or_else(|| anyhow!("Region {}: unknown fragment `{}`", r, r.chr))?;
+            if r.end > chr.length {
+                return Err(anyhow!(
+                    "Region {} extends beyond the end of `{}` ({}bp)",
+                    r,
+                    chr.name,
+                    chr.length
+                ));
+            }
+        }
+        // Merge overlapping regions, so that each base belongs to at most one
+        let mut sorted = regions.to_vec();
+        sorted.sort_by_key(|r| (self.strand.find_chr_index(&r.chr), r.start));
+        let mut merged: Vec<Region> = Vec::new();
+        for r in sorted {
+            match merged.last_mut() {
+                Some(last) if last.chr == r.chr && r.start <= last.end => {
+                    last.end = last.end.max(r.end)
+                }
+                _ => merged.push(r),
+            }
+        }
+
+        let in_regions = |chr: &str, start: usize, length: usize| {
+            merged.iter().any(|r| r.overlap(chr, start, length) > 0)
+        };
+        self.families.iter_mut().for_each(|family| {
+            family.retain(|sd| {
+                let left = in_regions(&sd.chr_left, sd.chr_left_position, sd.left_length);
+                let right = in_regions(&sd.chr_right, sd.chr_right_position, sd.right_length);
+                match overlap {
+                    RegionOverlap::AnyArm if !clip => left || right,
+                    RegionOverlap::AnyArm => left && right,
+                    RegionOverlap::BothArms => left && right,
+                }
+            })
+        });
+        self.families.retain(|f| !f.is_empty());
+
+        if clip {
+            self.clip_regions(&merged);
+        }
+        Ok(())
+    }
+
+    /// Replace the fragments map by `regions`, sorted and disjoint, and
+    /// rebase the arms, which all overlap them, on the regions
+    fn clip_regions(&mut self, regions: &[Region]) {
+        fn clip_arm(
+            regions: &[Region],
+            chr: &mut String,
+            position: &mut usize,
+            length: &mut usize,
+            seq: &mut Option<String>,
+        ) {
+            let r = regions
+                .iter()
+                .max_by_key(|r| r.overlap(chr, *position, *length))
+                .unwrap();
+            let start = (*position).max(r.start);
+            let end = (*position + *length).min(r.end);
+            if start != *position || end - start != *length {
+                *seq = None;
+            }
+            *chr = r.to_string();
+            *position = start - r.start;
+            *length = end - start;
+        }
+
+        let mut i = 0;
+        self.strand.map = regions
+            .iter()
+            .map(|r| {
+                i += r.len();
+                Start {
+                    name: r.to_string(),
+                    position: i - r.len(),
+                    length: r.len(),
+                    checksum: None,
+                }
+            })
+            .collect();
+        self.strand.length = i;
+        // Clipped regions cannot be expanded back
+        self.strand.collapsed.clear();
+
+        for sd in self.families.iter_mut().flatten() {
+            clip_arm(
+                regions,
+                &mut sd.chr_left,
+                &mut sd.chr_left_position,
+                &mut sd.left_length,
+                &mut sd.left_seq,
+            );
+            clip_arm(
+                regions,
+                &mut sd.chr_right,
+                &mut sd.chr_right_position,
+                &mut sd.right_length,
+                &mut sd.right_seq,
+            );
+            sd.global_left_position =
+                self.strand.find_chr(&sd.chr_left).unwrap().position + sd.chr_left_position;
+            sd.global_right_position =
+                self.strand.find_chr(&sd.chr_right).unwrap().position + sd.chr_right_position;
+            // Keep the left arm first in the new layout
+            if sd.global_right_position < sd.global_left_position {
+                sd.swap_arms();
+            }
+        }
+    }
+
     /// Given a list of fragments, keep the duplicons for which AT LEAST a leg
     /// stands on one of them
     pub fn keep_fragments<T: AsRef<str>>(&mut self, to_keep: &[T]) {
@@ -347,71 +758,385 @@ impl RunResult {
         Ok(())
     }
 
-    pub fn flatten(&mut self) {
-        if self.strand.map.len() < 2 {
-            return;
+    /// Lift the duplicons over to the `target` genome, which replaces the
+    /// fragments map, through `liftover`; the duplicons having an arm that
+    /// cannot be lifted or whose length changes by more than a `tolerance`
+    /// fraction are removed, and returned with the index of their family
+    pub fn liftover(
+        &mut self,
+        liftover: &Liftover,
+        target: &StrandResult,
+        tolerance: f64,
+    ) -> Vec<(usize, SD, Rejection)> {
+        let mut rejected = Vec::new();
+        for (i, family) in self.families.iter_mut().enumerate() {
+            *family = family
+                .drain(..)
+                .filter_map(
+                    |sd| match liftover::lift_sd(liftover, target, tolerance, &sd) {
+                        Ok(lifted) => Some(lifted),
+                        Err(rejection) => {
+                            rejected.push((i, sd, rejection));
+                            None
+                        }
+                    },
+                )
+                .collect();
         }
-        let n = self.strand.map.len() as f64;
-        let lengths = self
-            .strand
-            .map
-            .iter()
-            .map(|c| c.length as f64)
-            .collect::<Vec<_>>();
-        let avg = lengths.iter().sum::<f64>() / n;
-        let std =
-            (1.0 / (n - 1.0) * lengths.iter().map(|x| (x - avg).powf(2.0)).sum::<f64>()).sqrt();
-
-        let mut to_flatten = self
-            .strand
-            .map
-            .iter().filter(|&c| c.length as f64 <= avg + std && c.name.len() > 2).cloned() // Try not to remove normal but small scaffolds/chromosomes
-            .clone()
-            .collect::<Vec<_>>();
-        let to_flatten_len = to_flatten.iter().map(|c| c.length).sum::<usize>();
-        let mut to_keep = self
-            .strand
-            .map
-            .iter().filter(|&c| !to_flatten.iter().any(|r| c.name == r.name)).cloned()
-            .collect::<Vec<_>>();
-        let to_keep_len = to_keep.iter().map(|c| c.length).sum::<usize>();
+        self.families.retain(|f| !f.is_empty());
+        self.strand = target.clone();
+        rejected
+    }
 
-        let mut i = 0;
-        for c in to_keep.iter_mut() {
-            c.position = i;
-            i += c.length;
+    /// Rename the fragments having an alias, in the map and in the
+    /// duplicons; fails if two fragments would end up with the same name
+    pub fn rename_fragments(&mut self, aliases: &Aliases) -> Result<()> {
+        let rename = |name: &mut String| {
+            if let Some(to) = aliases.canonical(name) {
+                *name = to.to_owned();
+            }
+        };
+
+        let mut names = HashMap::new();
+        for chr in self.strand.map.iter_mut() {
+            let from = chr.name.clone();
+            rename(&mut chr.name);
+            if let Some(other) = names.insert(chr.name.clone(), from.clone()) {
+                return Err(anyhow!(
+                    "Both `{}` and `{}` would be renamed to `{}`",
+                    other,
+                    from,
+                    chr.name
+                ));
+            }
         }
-        for c in to_flatten.iter_mut() {
-            c.position = i;
-            i += c.length;
+        for c in self.strand.collapsed.iter_mut() {
+            rename(&mut c.fragment.name);
+            rename(&mut c.into);
         }
+        for sd in self.families.iter_mut().flatten() {
+            rename(&mut sd.chr_left);
+            rename(&mut sd.chr_right);
+        }
+        Ok(())
+    }
 
-        let to_flatten_positions = to_flatten
-            .iter()
-            .map(|c| (c.name.clone(), c.position))
-            .collect::<HashMap<_, _>>();
+    /// Merge the fragments matched by `rules` into their collapsed bins,
+    /// which are laid out after the remaining fragments, and record where
+    /// each original fragment went so that `unflatten` can restore it
+    pub fn flatten(&mut self, rules: &[CollapseRule]) -> Result<()> {
+        if !self.strand.collapsed.is_empty() {
+            return Err(anyhow!("Fragments have already been collapsed"));
+        }
+        let (bins, assigned) = collapse::assign(&self.strand.map, rules);
+        if assigned.iter().all(Option::is_none) {
+            return Ok(());
+        }
+        for bin in &bins {
+            if let Some(i) = self.strand.find_chr_index(bin) {
+                if assigned[i].is_none() {
+                    return Err(anyhow!("`{}` is both a fragment and a bin", bin));
+                }
+            }
+        }
 
-        self.strand.map = to_keep;
-        self.strand.map.push(Start {
-            name: COLLAPSED_NAME.to_string(),
-            position: to_keep_len + 1,
-            length: to_flatten_len,
-        });
+        // Kept fragments come first, followed by the non-empty bins, each
+        // made of its fragments in their original order
+        let mut map = Vec::new();
+        let mut position = 0;
+        for (chr, bin) in self.strand.map.iter().zip(&assigned) {
+            if bin.is_none() {
+                self.strand.collapsed.push(CollapsedFragment {
+                    fragment: chr.clone(),
+                    into: chr.name.clone(),
+                    offset: 0,
+                });
+                map.push(Start {
+                    position,
+                    ..chr.clone()
+                });
+                position += chr.length;
+            }
+        }
+        for (b, bin) in bins.iter().enumerate() {
+            let mut length = 0;
+            for (chr, _) in self
+                .strand
+                .map
+                .iter()
+                .zip(&assigned)
+                .filter(|(_, a)| **a == Some(b))
+            {
+                self.strand.collapsed.push(CollapsedFragment {
+                    fragment: chr.clone(),
+                    into: bin.clone(),
+                    offset: length,
+                });
+                length += chr.length;
+            }
+            if length > 0 {
+                map.push(Start {
+                    name: bin.clone(),
+                    position,
+                    length,
+                    checksum: None,
+                });
+                position += length;
+            }
+        }
+        // Keep the original order in the record
+        self.strand
+            .collapsed
+            .sort_by_key(|c| (c.fragment.position, c.fragment.name.clone()));
+        self.strand.map = map;
 
+        let strand = &self.strand;
+        let moves = strand
+            .collapsed
+            .iter()
+            .map(|c| {
+                (
+                    c.fragment.name.as_str(),
+                    (
+                        c.into.as_str(),
+                        c.offset,
+                        strand.find_chr(&c.into).unwrap().position,
+                    ),
+                )
+            })
+            .collect::<HashMap<_, _>>();
         self.families.par_iter_mut().for_each(|family| {
             family.iter_mut().for_each(|sd| {
-                let left_match = to_flatten.iter().any(|n| *n.name == sd.chr_left);
-                let right_match = to_flatten.iter().any(|n| *n.name == sd.chr_right);
-                if left_match {
-                    sd.chr_left_position += to_flatten_positions[&sd.chr_left];
-                    sd.chr_left = COLLAPSED_NAME.to_string();
+                // Arms on fragments absent from the map are left untouched
+                if let Some((into, offset, start)) = moves.get(sd.chr_left.as_str()) {
+                    sd.chr_left = into.to_string();
+                    sd.chr_left_position += offset;
+                    sd.global_left_position = start + sd.chr_left_position;
                 }
-                if right_match {
-                    sd.chr_right_position += to_flatten_positions[&sd.chr_right];
-                    sd.chr_right = COLLAPSED_NAME.to_string();
+                if let Some((into, offset, start)) = moves.get(sd.chr_right.as_str()) {
+                    sd.chr_right = into.to_string();
+                    sd.chr_right_position += offset;
+                    sd.global_right_position = start + sd.chr_right_position;
+                }
+                if sd.global_right_position < sd.global_left_position {
+                    sd.swap_arms();
                 }
             })
         });
+        Ok(())
+    }
+
+    /// Restore the fragments merged by `flatten`, and the coordinates of the
+    /// duplicons lying on them
+    pub fn unflatten(&mut self) -> Result<()> {
+        if self.strand.collapsed.is_empty() {
+            return Ok(());
+        }
+        let collapsed = std::mem::take(&mut self.strand.collapsed);
+        let mut by_fragment: HashMap<&str, Vec<&CollapsedFragment>> = HashMap::new();
+        for c in &collapsed {
+            by_fragment.entry(c.into.as_str()).or_default().push(c);
+        }
+        // Find the original fragment an arm lies in
+        let restore = |chr: &mut String, position: &mut usize, global: &mut usize| -> Result<()> {
+            if let Some(candidates) = by_fragment.get(chr.as_str()) {
+                let c = candidates
+                    .iter()
+                    .find(|c| *position >= c.offset && *position < c.offset + c.fragment.length)
+                    .ok_or_else(|| anyhow!("{}:{} lies in no collapsed fragment", chr, position))?;
+                *chr = c.fragment.name.clone();
+                *position -= c.offset;
+                *global = c.fragment.position + *position;
+            }
+            Ok(())
+        };
+
+        for sd in self.families.iter_mut().flatten() {
+            restore(
+                &mut sd.chr_left,
+                &mut sd.chr_left_position,
+                &mut sd.global_left_position,
+            )?;
+            restore(
+                &mut sd.chr_right,
+                &mut sd.chr_right_position,
+                &mut sd.global_right_position,
+            )?;
+            if sd.global_right_position < sd.global_left_position {
+                sd.swap_arms();
+            }
+        }
+        self.strand.map = collapsed.into_iter().map(|c| c.fragment).collect();
+        Ok(())
+    }
+}
+
+/// A `RunResult` whose families are read one at a time from the underlying
+/// source rather than loaded in a whole.
+pub struct FamilyStream {
+    pub strand: StrandResult,
+    pub settings: RunSettings,
+    families: Box<dyn Iterator<Item = Result<SDsFamily>>>,
+}
+impl FamilyStream {
+    /// Stream the results provided by `reader`. Binary results are decoded on
+    /// the fly; JSON results are parsed in a background thread, and their
+    /// `strand` and `settings` must precede `families`, which is always the
+    /// case for ASGART-generated files.
+    pub fn from_reader<R: Read + Send + 'static>(reader: R, source: &str) -> Result<FamilyStream> {
+        let (is_binary, reader) = binary::sniff(reader)?;
+        let source = source.to_owned();
+        if is_binary {
+            let (strand, settings, families) = BinaryReader::new(BufReader::new(reader))
+                .with_context(|| format!("Failed to parse data from {}", source))?;
+            return Ok(FamilyStream {
+                strand,
+                settings,
+                families: Box::new(families.map(move |family| {
+                    family.with_context(|| format!("Failed to parse data from {}", source))
+                })),
+            });
+        }
+
+        let (tx, rx) = mpsc::sync_channel(STREAM_BUFFER);
+        thread::spawn(move || {
+            let mut de = serde_json::Deserializer::from_reader(BufReader::new(reader));
+            let r = de
+                .deserialize_map(ResultVisitor { tx: &tx })
+                .and_then(|_| de.end());
+            if let Err(e) = r {
+                let _ = tx.send(StreamItem::Error(e.into()));
+            }
+        });
+
+        let (strand, settings) = match rx.recv() {
+            Ok(StreamItem::Header(header)) => *header,
+            Ok(StreamItem::Error(e)) => {
+                return Err(e.context(format!("Failed to parse JSON data from {}", source)))
+            }
+            _ => return Err(anyhow!("Failed to parse JSON data from {}", source)),
+        };
+
+        Ok(FamilyStream {
+            strand,
+            settings,
+            families: Box::new(rx.into_iter().map(move |item| match item {
+                StreamItem::Family(family) => Ok(family),
+                StreamItem::Error(e) => {
+                    Err(e.context(format!("Failed to parse JSON data from {}", source)))
+                }
+                StreamItem::Header(_) => unreachable!(),
+            })),
+        })
+    }
+
+    /// Load all the remaining families into a `RunResult`
+    pub fn collect_result(self) -> Result<RunResult> {
+        Ok(RunResult {
+            strand: self.strand,
+            settings: self.settings,
+            families: self.families.collect::<Result<Vec<_>>>()?,
+        })
+    }
+}
+impl From<RunResult> for FamilyStream {
+    fn from(result: RunResult) -> FamilyStream {
+        FamilyStream {
+            strand: result.strand,
+            settings: result.settings,
+            families: Box::new(result.families.into_iter().map(Ok)),
+        }
+    }
+}
+impl Iterator for FamilyStream {
+    type Item = Result<SDsFamily>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.families.next()
+    }
+}
+
+enum StreamItem {
+    Header(Box<(StrandResult, RunSettings)>),
+    Family(SDsFamily),
+    Error(anyhow::Error),
+}
+
+struct ResultVisitor<'a> {
+    tx: &'a SyncSender<StreamItem>,
+}
+impl<'de> Visitor<'de> for ResultVisitor<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an ASGART result")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<(), A::Error> {
+        let mut strand: Option<StrandResult> = None;
+        let mut settings: Option<RunSettings> = None;
+        let mut header_sent = false;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "strand" => strand = Some(map.next_value()?),
+                "settings" => settings = Some(map.next_value()?),
+                "families" => {
+                    let header = match (strand.take(), settings.take()) {
+                        (Some(strand), Some(settings)) => Box::new((strand, settings)),
+                        _ => {
+                            return Err(de::Error::custom(
+                                "`families` must come after `strand` and `settings`",
+                            ))
+                        }
+                    };
+                    self.tx
+                        .send(StreamItem::Header(header))
+                        .map_err(|_| de::Error::custom("family stream closed"))?;
+                    header_sent = true;
+                    map.next_value_seed(FamiliesSeed { tx: self.tx })?;
+                }
+                _ => {
+                    map.next_value::<de::IgnoredAny>()?;
+                }
+            }
+        }
+
+        if !header_sent {
+            let header = Box::new((
+                strand.ok_or_else(|| de::Error::missing_field("strand"))?,
+                settings.ok_or_else(|| de::Error::missing_field("settings"))?,
+            ));
+            let _ = self.tx.send(StreamItem::Header(header));
+        }
+        Ok(())
+    }
+}
+
+struct FamiliesSeed<'a> {
+    tx: &'a SyncSender<StreamItem>,
+}
+impl<'de> DeserializeSeed<'de> for FamiliesSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> std::result::Result<(), D::Error> {
+        d.deserialize_seq(self)
+    }
+}
+impl<'de> Visitor<'de> for FamiliesSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a list of duplication families")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<(), A::Error> {
+        while let Some(family) = seq.next_element::<SDsFamily>()? {
+            self.tx
+                .send(StreamItem::Family(family))
+                .map_err(|_| de::Error::custom("family stream closed"))?;
+        }
+        Ok(())
     }
 }
 
@@ -492,6 +1217,18 @@ pub struct SD {
     pub complemented: bool,
 }
 impl SD {
+    /// Exchange the left and right arms, sequences included
+    pub fn swap_arms(&mut self) {
+        std::mem::swap(&mut self.chr_left, &mut self.chr_right);
+        std::mem::swap(
+            &mut self.global_left_position,
+            &mut self.global_right_position,
+        );
+        std::mem::swap(&mut self.chr_left_position, &mut self.chr_right_position);
+        std::mem::swap(&mut self.left_length, &mut self.right_length);
+        std::mem::swap(&mut self.left_seq, &mut self.right_seq);
+    }
+
     pub fn left_part(&self) -> (usize, usize) {
         (self.global_left_position, self.left_length)
     }
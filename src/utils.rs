@@ -12,6 +12,28 @@ pub fn complement_nucleotide(n: u8) -> u8 {
         b'c' => b'g',
         b'n' => b'n',
 
+        b'R' => b'Y',
+        b'Y' => b'R',
+        b'K' => b'M',
+        b'M' => b'K',
+        b'S' => b'S',
+        b'W' => b'W',
+        b'B' => b'V',
+        b'V' => b'B',
+        b'D' => b'H',
+        b'H' => b'D',
+
+        b'r' => b'y',
+        b'y' => b'r',
+        b'k' => b'm',
+        b'm' => b'k',
+        b's' => b's',
+        b'w' => b'w',
+        b'b' => b'v',
+        b'v' => b'b',
+        b'd' => b'h',
+        b'h' => b'd',
+
         _ => b'N',
     }
 }
@@ -47,3 +69,10 @@ pub fn make_out_filename(
             new
         })
 }
+
+/// The 64-bit FNV-1a hash of `data`
+pub fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |h, &b| {
+        (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
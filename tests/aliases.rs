@@ -0,0 +1,88 @@
+//! Check the reading of alias tables and the renaming of fragments.
+
+mod common;
+
+use asgart::{aliases::Aliases, structs::RunResult};
+
+use common::write;
+
+const REPORT: &str = "# Assembly name:  GRCh38.p14
+# Sequence-Name\tSequence-Role\tAssigned-Molecule\tAssigned-Molecule-Location/Type\tGenBank-Accn\tRelationship\tRefSeq-Accn\tAssembly-Unit\tSequence-Length\tUCSC-style-name
+1\tassembled-molecule\t1\tChromosome\tCM000663.2\t=\tNC_000001.11\tPrimary Assembly\t248956422\tchr1
+HSCHR1_CTG1_UNLOCALIZED\tunlocalized-scaffold\t1\tChromosome\tKI270706.1\t=\tNT_187361.1\tPrimary Assembly\t175055\tchr1_KI270706v1_random
+HG2095_PATCH\tfix-patch\t1\tChromosome\tKN196472.1\t=\tNW_009646194.1\tPATCHES\t186494\tna
+";
+
+fn result() -> RunResult {
+    common::result(
+        &[("NC_000001.11", 1_000), ("NT_187361.1", 1_000)],
+        vec![vec![common::sd(
+            ("NC_000001.11", 10, 100),
+            ("NT_187361.1", 10, 100),
+        )]],
+    )
+}
+
+#[test]
+fn assembly_report() {
+    let filename = write("report.txt", REPORT);
+
+    let aliases = Aliases::from_file(&filename, "ucsc").unwrap();
+    assert_eq!(aliases.canonical("NC_000001.11"), Some("chr1"));
+    assert_eq!(aliases.canonical("1"), Some("chr1"));
+    assert_eq!(aliases.canonical("CM000663.2"), Some("chr1"));
+    assert_eq!(
+        aliases.canonical("KI270706.1"),
+        Some("chr1_KI270706v1_random")
+    );
+    // No UCSC name: fall back on the sequence name
+    assert_eq!(aliases.canonical("NW_009646194.1"), Some("HG2095_PATCH"));
+    assert_eq!(aliases.canonical("chr2"), None);
+
+    let aliases = Aliases::from_file(&filename, "refseq").unwrap();
+    assert_eq!(aliases.canonical("chr1"), Some("NC_000001.11"));
+
+    let mut r = result();
+    let aliases = Aliases::from_file(&filename, "ucsc").unwrap();
+    r.rename_fragments(&aliases).unwrap();
+    assert_eq!(r.strand.map[0].name, "chr1");
+    assert_eq!(r.strand.map[1].name, "chr1_KI270706v1_random");
+    assert_eq!(r.families[0][0].chr_left, "chr1");
+    assert_eq!(r.families[0][0].chr_right, "chr1_KI270706v1_random");
+    assert!(r.strand.find_chr("chr1").is_some());
+}
+
+#[test]
+fn two_columns() {
+    let filename = write("aliases.tsv", "NC_000001.11\tchr1\n1\tchr1\n\n# chrY\n");
+    let aliases = Aliases::from_file(&filename, "ucsc").unwrap();
+    assert_eq!(aliases.canonical("1"), Some("chr1"));
+    assert_eq!(aliases.canonical("NC_000001.11"), Some("chr1"));
+
+    // Features named after any alias are found in the results
+    let r = result();
+    assert_eq!(
+        aliases.resolve(&r.strand, "1").map(|c| c.name.as_str()),
+        Some("NC_000001.11")
+    );
+    assert_eq!(
+        aliases.resolve(&r.strand, "chr1").map(|c| c.name.as_str()),
+        Some("NC_000001.11")
+    );
+    assert!(aliases.resolve(&r.strand, "chr2").is_none());
+
+    for content in ["a\tb\na\tc\n", "a\tb\nb\tc\n", "a\n"] {
+        assert!(
+            Aliases::from_file(&write("invalid.tsv", content), "ucsc").is_err(),
+            "{}",
+            content
+        );
+    }
+
+    // Two fragments may not be renamed alike
+    let mut r = result();
+    let filename = write("collision.tsv", "NC_000001.11\tchr1\nNT_187361.1\tchr1\n");
+    assert!(r
+        .rename_fragments(&Aliases::from_file(&filename, "ucsc").unwrap())
+        .is_err());
+}
@@ -0,0 +1,87 @@
+//! Check the binary encoding of results.
+
+mod common;
+
+use asgart::{
+    binary,
+    exporters::{BinaryExporter, Exporter, JSONExporter},
+    structs::{RunResult, SD},
+};
+
+fn result() -> RunResult {
+    let mut result = common::result(
+        &[("chr1", 10_000), ("chr2", 8_000)],
+        vec![
+            vec![
+                SD {
+                    identity: 97.25,
+                    left_seq: Some("ACGT".to_owned()),
+                    right_seq: Some("ACGA".to_owned()),
+                    ..common::sd(("chr1", 100, 4), ("chr2", 400, 4))
+                },
+                SD {
+                    reversed: true,
+                    complemented: true,
+                    ..common::sd(("chr1", 2_000, 300), ("chr1", 5_000, 310))
+                },
+            ],
+            // An arm on a fragment missing from the map
+            vec![common::sd(("chr2", 10, 50), ("chrUn", 20, 50))],
+        ],
+    );
+    result.strand.map[0].checksum = Some(u64::MAX);
+    result
+}
+
+fn save(exporter: &mut dyn Exporter, result: &RunResult) -> Vec<u8> {
+    let mut out = Vec::new();
+    exporter.save(result, &mut out).unwrap();
+    out
+}
+
+#[test]
+fn json_round_trip() {
+    let json = save(&mut JSONExporter, &result());
+    let binary = save(
+        &mut BinaryExporter::new(),
+        &RunResult::from_reader(json.as_slice()).unwrap(),
+    );
+    assert!(binary.starts_with(binary::MAGIC));
+    let back = save(
+        &mut JSONExporter,
+        &RunResult::from_reader(binary.as_slice()).unwrap(),
+    );
+    assert_eq!(
+        String::from_utf8(back).unwrap(),
+        String::from_utf8(json).unwrap()
+    );
+}
+
+#[test]
+fn truncated() {
+    let binary = save(&mut BinaryExporter::new(), &result());
+    for end in 0..binary.len() {
+        assert!(
+            RunResult::from_reader(&binary[..end]).is_err(),
+            "truncated at {}",
+            end
+        );
+    }
+}
+
+#[test]
+fn oversized_counts() {
+    // Counts are not trusted to preallocate the fragments map
+    let mut header = Vec::new();
+    header.extend_from_slice(binary::MAGIC);
+    binary::write_varint(&mut header, binary::VERSION).unwrap();
+    binary::write_str(&mut header, "test").unwrap();
+    binary::write_varint(&mut header, 0).unwrap();
+    binary::write_str(
+        &mut header,
+        &serde_json::to_string(&result().settings).unwrap(),
+    )
+    .unwrap();
+    binary::write_varint(&mut header, u64::MAX >> 8).unwrap();
+    assert!(RunResult::from_reader(header.as_slice()).is_err());
+}
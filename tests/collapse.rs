@@ -0,0 +1,163 @@
+//! Check the collapsing of fragments into bins and its reversal.
+
+mod common;
+
+use std::io::Cursor;
+
+use asgart::{
+    collapse::{CollapseRule, Criterion},
+    exporters::{BinaryExporter, Exporter, JSONExporter},
+    structs::{FamilyStream, RunResult, COLLAPSED_NAME},
+};
+
+use common::sd;
+
+const FRAGMENTS: [(&str, usize); 5] = [
+    ("chr1", 10_000),
+    ("chrUn_1", 1_000),
+    ("chr2", 8_000),
+    ("chr1_random", 500),
+    ("chrUn_2", 2_000),
+];
+
+fn result() -> RunResult {
+    let mut result = common::result(
+        &FRAGMENTS,
+        vec![vec![
+            sd(("chr1", 100, 100), ("chrUn_2", 300, 200)),
+            sd(("chrUn_1", 50, 100), ("chr2", 400, 200)),
+            sd(("chr1_random", 10, 100), ("chrUn_2", 20, 200)),
+        ]],
+    );
+    for chr in result.strand.map.iter_mut() {
+        chr.checksum = Some(chr.length as u64);
+    }
+    result
+}
+
+fn rules(specs: &[&str]) -> Vec<CollapseRule> {
+    specs.iter().map(|s| s.parse().unwrap()).collect()
+}
+
+fn arms(r: &RunResult) -> Vec<(String, usize, usize, String, usize, usize)> {
+    r.families
+        .iter()
+        .flatten()
+        .map(|sd| {
+            (
+                sd.chr_left.clone(),
+                sd.chr_left_position,
+                sd.global_left_position,
+                sd.chr_right.clone(),
+                sd.chr_right_position,
+                sd.global_right_position,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn rules_parsing() {
+    let rule = "length<5000".parse::<CollapseRule>().unwrap();
+    assert_eq!(rule.bin, COLLAPSED_NAME);
+    assert!(matches!(rule.criterion, Criterion::ShorterThan(5000)));
+
+    let rule = "alt=regex:_alt=$".parse::<CollapseRule>().unwrap();
+    assert_eq!(rule.bin, "alt");
+    assert!(matches!(rule.criterion, Criterion::Matching(_)));
+
+    // The `=` belongs to the regex
+    let rule = "regex:a=b".parse::<CollapseRule>().unwrap();
+    assert_eq!(rule.bin, COLLAPSED_NAME);
+
+    let rule = "unplaced=names:chrUn_1,chrUn_2"
+        .parse::<CollapseRule>()
+        .unwrap();
+    assert!(matches!(rule.criterion, Criterion::Named(ref names) if names.len() == 2));
+
+    for invalid in ["length<big", "regex:(", "=auto", "chrUn"] {
+        assert!(invalid.parse::<CollapseRule>().is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn flatten() {
+    let original = result();
+    let mut r = result();
+    r.flatten(&rules(&[
+        "unplaced=regex:^chrUn",
+        "random=regex:_random$",
+        "unplaced=length<100",
+    ]))
+    .unwrap();
+
+    assert_eq!(
+        r.strand
+            .map
+            .iter()
+            .map(|c| (c.name.as_str(), c.position, c.length))
+            .collect::<Vec<_>>(),
+        vec![
+            ("chr1", 0, 10_000),
+            ("chr2", 10_000, 8_000),
+            ("unplaced", 18_000, 3_000),
+            ("random", 21_000, 500),
+        ]
+    );
+    assert!(r.strand.is_collapsed_bin("unplaced"));
+    assert!(!r.strand.is_collapsed_bin("chr2"));
+    assert_eq!(
+        arms(&r),
+        vec![
+            ("chr1".into(), 100, 100, "unplaced".into(), 1_300, 19_300),
+            ("chr2".into(), 400, 10_400, "unplaced".into(), 50, 18_050),
+            // The arms have been swapped to keep the left one first
+            (
+                "unplaced".into(),
+                1_020,
+                19_020,
+                "random".into(),
+                10,
+                21_010
+            ),
+        ]
+    );
+    assert!(r.flatten(&rules(&["auto"])).is_err());
+
+    // The record survives the exports
+    for exporter in [
+        &mut JSONExporter as &mut dyn Exporter,
+        &mut BinaryExporter::new(),
+    ] {
+        let mut out = Vec::new();
+        exporter.save(&r, &mut out).unwrap();
+        let mut read = FamilyStream::from_reader(Cursor::new(out), "test")
+            .unwrap()
+            .collect_result()
+            .unwrap();
+        assert_eq!(read.strand.collapsed.len(), FRAGMENTS.len());
+        read.unflatten().unwrap();
+        assert_eq!(arms(&read), arms(&original));
+    }
+
+    r.unflatten().unwrap();
+    assert!(r.strand.collapsed.is_empty());
+    assert_eq!(
+        r.strand
+            .map
+            .iter()
+            .map(|c| (c.name.as_str(), c.position, c.checksum))
+            .collect::<Vec<_>>(),
+        original
+            .strand
+            .map
+            .iter()
+            .map(|c| (c.name.as_str(), c.position, c.checksum))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(arms(&r), arms(&original));
+
+    // A bin may not be named after a kept fragment
+    let mut r = result();
+    assert!(r.flatten(&rules(&["chr1=names:chr2"])).is_err());
+}
@@ -0,0 +1,90 @@
+//! Fixtures shared by the integration tests.
+#![allow(dead_code)]
+
+use std::fs;
+
+use asgart::{
+    config::Config,
+    structs::{RunResult, SDsFamily, Start, StrandResult, SD},
+};
+
+/// Write `content` to the temporary file `name`, and return its path
+pub fn write(name: &str, content: &str) -> String {
+    let path = std::env::temp_dir().join(format!("asgart-test-{}-{}", std::process::id(), name));
+    fs::write(&path, content).unwrap();
+    path.to_str().unwrap().to_owned()
+}
+
+/// A reproducible random sequence of `length` concrete nucleotides
+pub fn random_dna(length: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..length)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            b"ACGT"[(state >> 62) as usize]
+        })
+        .collect()
+}
+
+/// A strand made of the `(name, length)` fragments, laid out in order
+pub fn strand(fragments: &[(&str, usize)]) -> StrandResult {
+    let mut position = 0;
+    StrandResult {
+        name: "test".to_owned(),
+        length: fragments.iter().map(|f| f.1).sum(),
+        map: fragments
+            .iter()
+            .map(|(name, length)| {
+                position += length;
+                Start {
+                    name: name.to_string(),
+                    position: position - length,
+                    length: *length,
+                    checksum: None,
+                }
+            })
+            .collect(),
+        collapsed: Vec::new(),
+    }
+}
+
+/// A direct duplicon between the `(fragment, position, length)` arms `left`
+/// and `right`; its global positions are set by `result`
+pub fn sd(left: (&str, usize, usize), right: (&str, usize, usize)) -> SD {
+    SD {
+        chr_left: left.0.to_owned(),
+        chr_right: right.0.to_owned(),
+        global_left_position: left.1,
+        global_right_position: right.1,
+        chr_left_position: left.1,
+        chr_right_position: right.1,
+        left_length: left.2,
+        right_length: right.2,
+        left_seq: None,
+        right_seq: None,
+        identity: 99.0,
+        reversed: false,
+        complemented: false,
+    }
+}
+
+/// A result over the `(name, length)` fragments, whose duplicons get their
+/// global positions from the fragments they stand on, if they are known
+pub fn result(fragments: &[(&str, usize)], mut families: Vec<SDsFamily>) -> RunResult {
+    let strand = strand(fragments);
+    for sd in families.iter_mut().flatten() {
+        if let Some(chr) = strand.find_chr(&sd.chr_left) {
+            sd.global_left_position = chr.position + sd.chr_left_position;
+        }
+        if let Some(chr) = strand.find_chr(&sd.chr_right) {
+            sd.global_right_position = chr.position + sd.chr_right_position;
+        }
+    }
+    RunResult {
+        strand,
+        settings: Config::default().into_settings().unwrap(),
+        families,
+    }
+}
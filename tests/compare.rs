@@ -0,0 +1,73 @@
+//! Check the matching of duplicons between two results.
+
+use asgart::{
+    compare::{self, Comparison},
+    simulate::{Orientation, Placement, Simulation, SimulationSettings},
+    structs::RunResult,
+};
+
+fn truth() -> RunResult {
+    Simulation::new(&SimulationSettings {
+        seed: 7,
+        fragments: 3,
+        fragment_length: 20_000,
+        families: 5,
+        length: (1000, 2000),
+        copies: (2, 3),
+        identity: 0.99,
+        indel_rate: 0.0,
+        orientations: vec![Orientation::Direct, Orientation::ReverseComplemented],
+        placement: Placement::Any,
+    })
+    .unwrap()
+    .truth
+}
+
+#[test]
+fn identical_results() {
+    let truth = truth();
+    let comparison = Comparison::new(&truth, &truth, 0.9);
+    assert!(!comparison.reference.is_empty());
+    assert_eq!(comparison.recall(), 1.0);
+    assert_eq!(comparison.precision(), 1.0);
+    for (name, f) in comparison.per_fragment() {
+        assert_eq!(f.found, f.reference, "{}", name);
+        assert_eq!(f.correct, f.query, "{}", name);
+    }
+}
+
+#[test]
+fn swapped_arms() {
+    let truth = truth();
+    let mut swapped = compare::filter_result(&truth, |_| true);
+    for sd in swapped.families.iter_mut().flatten() {
+        std::mem::swap(&mut sd.chr_left, &mut sd.chr_right);
+        std::mem::swap(&mut sd.chr_left_position, &mut sd.chr_right_position);
+        std::mem::swap(&mut sd.left_length, &mut sd.right_length);
+    }
+    let comparison = Comparison::new(&truth, &swapped, 0.9);
+    assert_eq!(comparison.recall(), 1.0);
+    assert_eq!(comparison.precision(), 1.0);
+}
+
+#[test]
+fn partial_results() {
+    let truth = truth();
+    let mut i = 0;
+    let half = compare::filter_result(&truth, |_| {
+        i += 1;
+        i % 2 == 0
+    });
+    let comparison = Comparison::new(&truth, &half, 0.5);
+    assert_eq!(comparison.precision(), 1.0);
+    let missed = comparison.reference.iter().filter(|(_, m)| !m).count();
+    assert_eq!(missed, comparison.reference.len() - comparison.query.len());
+
+    let mut shifted = compare::filter_result(&truth, |_| true);
+    for sd in shifted.families.iter_mut().flatten() {
+        sd.chr_left_position += 1_000_000;
+    }
+    let comparison = Comparison::new(&truth, &shifted, 0.5);
+    assert_eq!(comparison.recall(), 0.0);
+    assert_eq!(comparison.precision(), 0.0);
+}
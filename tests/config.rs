@@ -0,0 +1,105 @@
+//! Check how the settings of a run are assembled from the command line, the
+//! configuration file and the presets.
+
+mod common;
+
+use std::process::Command;
+
+use asgart::{
+    config::{Config, PRESETS},
+    simulate::{Orientation, Placement, Simulation, SimulationSettings},
+    structs::RunResult,
+};
+
+#[test]
+fn presets() {
+    for (name, _) in PRESETS {
+        Config::from_preset(name).unwrap().into_settings().unwrap();
+    }
+    assert!(Config::from_preset("mammoth").is_err());
+}
+
+#[test]
+fn precedence() {
+    let file = "preset = \"plant-polyploid\"\nmin_length = 2000\nreverse = true\n"
+        .parse::<Config>()
+        .unwrap();
+    let cli = Config {
+        min_length: Some(3000),
+        reverse: Some(false),
+        ..Default::default()
+    };
+    let settings = cli.or(file).into_settings().unwrap();
+    assert_eq!(settings.min_duplication_length, 3000);
+    assert!(!settings.reverse);
+    // Left to the preset
+    assert_eq!(settings.probe_size, 25);
+    assert!(settings.skip_masked);
+    assert!(!settings.complement);
+}
+
+#[test]
+fn command_line() {
+    let simulation = Simulation::new(&SimulationSettings {
+        seed: 5,
+        fragments: 1,
+        fragment_length: 20_000,
+        families: 2,
+        length: (2500, 3000),
+        copies: (2, 2),
+        identity: 1.0,
+        indel_rate: 0.0,
+        orientations: vec![Orientation::ReverseComplemented],
+        placement: Placement::Any,
+    })
+    .unwrap();
+    let fasta = common::write("config.fa", "");
+    simulation.write_fasta(&fasta).unwrap();
+    let config = common::write(
+        "config.toml",
+        "reverse = true\ncomplement = true\nskip_masked = false\nmin_length = 2000\n",
+    );
+    let out = common::write("config.json", "");
+
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_asgart"))
+            .arg(&fasta)
+            .args(["--config", &config, "--threads", "1", "--out", &out])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let result = RunResult::from_file(&out).unwrap();
+        let palindromes = result
+            .families
+            .iter()
+            .flatten()
+            .filter(|sd| sd.reversed && sd.complemented)
+            .count();
+        (result.settings, palindromes)
+    };
+
+    let (s, palindromes) = run(&["--preset", "bacterial"]);
+    assert!(palindromes > 0);
+    assert!(s.reverse && s.complement && !s.skip_masked);
+    assert_eq!((s.min_duplication_length, s.probe_size), (2000, 16));
+
+    // The last of a flag and its negation wins
+    let (s, palindromes) = run(&["-R", "--no-reverse", "--no-skip-masked", "-S"]);
+    assert_eq!(palindromes, 0);
+    assert!(!s.reverse && s.complement && s.skip_masked);
+
+    let unknown = Command::new(env!("CARGO_BIN_EXE_asgart"))
+        .args([&fasta, "--preset", "mammoth"])
+        .output()
+        .unwrap();
+    assert!(!unknown.status.success());
+
+    for f in [fasta, config, out] {
+        std::fs::remove_file(f).unwrap();
+    }
+}
@@ -0,0 +1,403 @@
+//! Check the tabulated exports of results.
+
+mod common;
+
+use std::fs;
+
+use asgart::{
+    compare::Comparison,
+    exporters::{
+        BEDExporter, BEDPEExporter, ChainExporter, DelimitedExporter, Exporter, GFAExporter,
+        PAFExporter, SQLiteExporter, VCFExporter, MAX_ALIGNED_LENGTH,
+    },
+    importers,
+    plot::colorizers::TypeColorizer,
+    simulate::{Orientation, Placement, Simulation, SimulationSettings},
+    structs::RunResult,
+};
+
+fn truth() -> RunResult {
+    Simulation::new(&SimulationSettings {
+        seed: 11,
+        fragments: 2,
+        fragment_length: 20_000,
+        families: 4,
+        length: (1000, 2000),
+        copies: (2, 3),
+        identity: 0.99,
+        indel_rate: 0.0,
+        orientations: vec![Orientation::Direct, Orientation::ReverseComplemented],
+        placement: Placement::Any,
+    })
+    .unwrap()
+    .truth
+}
+
+#[test]
+fn bedpe_round_trip() {
+    let truth = truth();
+    let mut bedpe = Vec::new();
+    BEDPEExporter.save(&truth, &mut bedpe).unwrap();
+
+    let dir = std::env::temp_dir();
+    let genome = dir.join(format!("asgart-export-{}.genome", std::process::id()));
+    let genome = genome.to_str().unwrap();
+    fs::write(
+        genome,
+        truth
+            .strand
+            .map
+            .iter()
+            .map(|chr| format!("{}\t{}\n", chr.name, chr.length))
+            .collect::<String>(),
+    )
+    .unwrap();
+    let strand = importers::read_genome(genome).unwrap();
+    fs::remove_file(genome).unwrap();
+
+    let imported = importers::from_format("bedpe")
+        .unwrap()
+        .import(&mut bedpe.as_slice(), &strand, "BEDPE")
+        .unwrap();
+    assert_eq!(imported.families.len(), truth.families.len());
+    let comparison = Comparison::new(&truth, &imported, 1.0);
+    assert_eq!(comparison.recall(), 1.0);
+    assert_eq!(comparison.precision(), 1.0);
+    for (t, i) in truth
+        .families
+        .iter()
+        .flatten()
+        .zip(imported.families.iter().flatten())
+    {
+        assert_eq!(t.reversed, i.reversed);
+    }
+}
+
+#[test]
+fn bed_lines() {
+    let truth = truth();
+    let count = truth.families.iter().flatten().count();
+    let intra = truth
+        .families
+        .iter()
+        .flatten()
+        .filter(|sd| sd.chr_left == sd.chr_right)
+        .count();
+
+    for (mut exporter, lines, columns) in [
+        (
+            BEDExporter::bed9(Box::new(TypeColorizer::new((1., 0., 0.), (0., 0., 1.)))),
+            2 * count,
+            9,
+        ),
+        (
+            BEDExporter::bed12(Box::new(TypeColorizer::new((1., 0., 0.), (0., 0., 1.)))),
+            2 * count - intra,
+            12,
+        ),
+    ] {
+        let mut out = Vec::new();
+        exporter.save(&truth, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let features = out.lines().skip(1).collect::<Vec<_>>();
+        assert_eq!(features.len(), lines);
+        for feature in features {
+            let fields = feature.split('\t').collect::<Vec<_>>();
+            assert_eq!(fields.len(), columns, "{}", feature);
+            assert!(
+                fields[8] == "255,0,0" || fields[8] == "0,0,255",
+                "{}",
+                feature
+            );
+        }
+    }
+}
+
+#[test]
+fn paf_records() {
+    let mut truth = truth();
+    let sd = &mut truth.families[0][0];
+    sd.identity = 90.0;
+    sd.reversed = false;
+    sd.complemented = false;
+    sd.left_seq = Some("ACGTACGTAC".to_owned());
+    sd.right_seq = Some("ACGTTACGTAC".to_owned());
+
+    let mut out = Vec::new();
+    PAFExporter::new().save(&truth, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let records = out.lines().collect::<Vec<_>>();
+    assert_eq!(records.len(), truth.families.iter().flatten().count());
+
+    let aligned = records[0].split('\t').collect::<Vec<_>>();
+    assert_eq!(aligned[1], truth.strand.map[0].length.to_string());
+    assert!(
+        ["cg:Z:3M1D7M", "cg:Z:4M1D6M"].contains(&aligned[12]),
+        "{}",
+        aligned[12]
+    );
+    assert_eq!((aligned[9], aligned[10]), ("10", "11"));
+    for record in &records[1..] {
+        let fields = record.split('\t').collect::<Vec<_>>();
+        assert_eq!(fields.len(), 12, "{}", record);
+        assert!(fields[9].parse::<usize>().unwrap() <= fields[10].parse::<usize>().unwrap());
+    }
+}
+
+#[test]
+fn long_arms_not_aligned() {
+    let mut truth = truth();
+    let sd = &mut truth.families[0][0];
+    sd.reversed = false;
+    sd.complemented = false;
+    sd.left_length = MAX_ALIGNED_LENGTH + 1;
+    sd.right_length = MAX_ALIGNED_LENGTH + 1;
+    sd.left_seq = Some("A".repeat(sd.left_length));
+    sd.right_seq = Some("A".repeat(sd.right_length));
+
+    let mut out = Vec::new();
+    PAFExporter::new().save(&truth, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(out.lines().next().unwrap().split('\t').count(), 12);
+
+    let mut out = Vec::new();
+    ChainExporter::new().save(&truth, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let chain = out
+        .split("\n\n")
+        .next()
+        .unwrap()
+        .lines()
+        .collect::<Vec<_>>();
+    assert_eq!(chain[1..], [(MAX_ALIGNED_LENGTH + 1).to_string()]);
+}
+
+#[test]
+fn vcf_records() {
+    let truth = truth();
+    let mut out = Vec::new();
+    VCFExporter.save(&truth, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.starts_with("##fileformat=VCFv4.3\n"));
+    for chr in &truth.strand.map {
+        assert!(out.contains(&format!("##contig=<ID={},length={}>", chr.name, chr.length)));
+    }
+    let records = out
+        .lines()
+        .filter(|l| !l.starts_with('#'))
+        .collect::<Vec<_>>();
+    let sds = truth.families.iter().flatten().collect::<Vec<_>>();
+    let inter = sds.iter().filter(|sd| sd.chr_left != sd.chr_right).count();
+    assert_eq!(records.len(), sds.len() + inter);
+
+    for record in records {
+        let fields = record.split('\t').collect::<Vec<_>>();
+        assert_eq!(fields.len(), 8, "{}", record);
+        match fields[4] {
+            "<DUP>" => assert!(fields[7].starts_with("SVTYPE=DUP;")),
+            "<INV>" => assert!(fields[7].starts_with("SVTYPE=INV;")),
+            _ => {
+                assert!(fields[7].starts_with("SVTYPE=BND;MATEID="), "{}", record);
+                assert!(fields[4].contains('[') || fields[4].contains(']'));
+            }
+        }
+    }
+}
+
+#[test]
+fn chain_blocks() {
+    let mut truth = truth();
+    let sd = &mut truth.families[0][0];
+    sd.reversed = false;
+    sd.complemented = false;
+    sd.left_seq = Some("ACGTACGTAC".to_owned());
+    sd.right_seq = Some("ACGTTACGTAC".to_owned());
+
+    let mut out = Vec::new();
+    ChainExporter::new().save(&truth, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let chains = out
+        .split("\n\n")
+        .filter(|c| !c.is_empty())
+        .collect::<Vec<_>>();
+    assert_eq!(chains.len(), truth.families.iter().flatten().count());
+
+    for (chain, sd) in chains.iter().zip(truth.families.iter().flatten()) {
+        let lines = chain.lines().collect::<Vec<_>>();
+        let header = lines[0].split(' ').collect::<Vec<_>>();
+        assert_eq!(header.len(), 13, "{}", lines[0]);
+        let number = |i: usize| header[i].parse::<usize>().unwrap();
+        assert_eq!(
+            header[9],
+            if sd.reversed || sd.complemented {
+                "-"
+            } else {
+                "+"
+            }
+        );
+
+        let (mut t_span, mut q_span) = (0, 0);
+        for block in &lines[1..] {
+            let fields = block
+                .split('\t')
+                .map(|f| f.parse::<usize>().unwrap())
+                .collect::<Vec<_>>();
+            t_span += fields[0] + fields.get(1).unwrap_or(&0);
+            q_span += fields[0] + fields.get(2).unwrap_or(&0);
+        }
+        assert_eq!(number(6) - number(5), t_span);
+        assert_eq!(number(11) - number(10), q_span);
+        assert!(number(6) <= number(3) && number(11) <= number(8));
+    }
+
+    let aligned = chains[0].lines().collect::<Vec<_>>();
+    assert_eq!(aligned.len(), 3);
+    assert_eq!(aligned[0].split(' ').nth(1), Some("1000"));
+}
+
+#[test]
+fn delimited_rows() {
+    let mut truth = truth();
+    truth.families[0][0].chr_left = "chr,1".to_owned();
+    let count = truth.families.iter().flatten().count();
+
+    let mut out = Vec::new();
+    DelimitedExporter::new(
+        ',',
+        &["family", "chr_left", "left_length", "orientation"],
+        false,
+    )
+    .unwrap()
+    .save(&truth, &mut out)
+    .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let rows = out.lines().collect::<Vec<_>>();
+    assert_eq!(rows.len(), count + 1);
+    assert_eq!(rows[0], "family,chr_left,left_length,orientation");
+    assert!(rows[1].starts_with("0,\"chr,1\","), "{}", rows[1]);
+
+    let mut out = Vec::new();
+    DelimitedExporter::new('\t', &[] as &[&str], true)
+        .unwrap()
+        .save(&truth, &mut out)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let rows = out.lines().collect::<Vec<_>>();
+    assert_eq!(rows.len(), 2 * count + 1);
+    assert!(rows.iter().all(|r| r.split('\t').count() == 10));
+
+    assert!(DelimitedExporter::new('\t', &["chr_left"], true).is_err());
+}
+
+#[test]
+fn gfa_graph() {
+    let truth = truth();
+    let mut out = Vec::new();
+    GFAExporter::new().save(&truth, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    let lines = out.lines().collect::<Vec<_>>();
+    let segments = lines
+        .iter()
+        .filter(|l| l.starts_with("S\t"))
+        .map(|l| l.split('\t').nth(1).unwrap())
+        .collect::<std::collections::HashSet<_>>();
+    assert_eq!(lines[0], "H\tVN:Z:1.0");
+    assert!(!lines.iter().any(|l| l.starts_with("J\t")));
+    let (duplicons, links): (Vec<&&str>, Vec<_>) = lines
+        .iter()
+        .filter(|l| l.starts_with("L\t"))
+        .partition(|l| l.contains("\tfm:i:"));
+    assert_eq!(links.len(), segments.len() - truth.strand.map.len());
+
+    let total = lines
+        .iter()
+        .filter(|l| l.starts_with("S\t"))
+        .map(|l| l.rsplit("LN:i:").next().unwrap().parse::<usize>().unwrap())
+        .sum::<usize>();
+    assert_eq!(total, truth.strand.length);
+
+    assert_eq!(duplicons.len(), truth.families.iter().flatten().count());
+    for link in duplicons {
+        let fields = link.split('\t').collect::<Vec<_>>();
+        assert!(segments.contains(fields[1]) && segments.contains(fields[3]));
+        assert!(fields[7].starts_with("fm:i:"));
+    }
+}
+
+#[test]
+fn sqlite_round_trip() {
+    let truth = truth();
+    let path = std::env::temp_dir().join(format!("asgart-export-{}.sqlite", std::process::id()));
+    let mut out = fs::File::create(&path).unwrap();
+    SQLiteExporter::new().save(&truth, &mut out).unwrap();
+    drop(out);
+    let filename = path.to_str().unwrap();
+
+    let read = RunResult::from_file(filename).unwrap();
+    assert_eq!(read.strand.name, truth.strand.name);
+    assert_eq!(read.strand.map.len(), truth.strand.map.len());
+    assert_eq!(read.families.len(), truth.families.len());
+    for (a, b) in read
+        .families
+        .iter()
+        .flatten()
+        .zip(truth.families.iter().flatten())
+    {
+        assert_eq!(
+            (&a.chr_left, a.chr_left_position, a.right_length, a.reversed),
+            (&b.chr_left, b.chr_left_position, b.right_length, b.reversed)
+        );
+    }
+
+    let sd = &truth.families[0][0];
+    let connection = rusqlite::Connection::open(filename).unwrap();
+    let hits: i64 = connection
+        .query_row(
+            "SELECT COUNT(*) FROM arms a JOIN fragments f ON f.id = a.fragment_min
+             WHERE f.name = ?1 AND a.start_min < ?2 AND a.end_max > ?3",
+            rusqlite::params![
+                sd.chr_left,
+                (sd.chr_left_position + sd.left_length) as i64,
+                sd.chr_left_position as i64
+            ],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert!(hits >= 1);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn sqlite_large_coordinates() {
+    // Past 2^32, arms must still be indexed and found
+    let result = common::result(
+        &[("chr1", 5_000_000_000)],
+        vec![vec![common::sd(
+            ("chr1", 4_294_967_000, 1_000),
+            ("chr1", 4_900_000_000, 1_000),
+        )]],
+    );
+    let path =
+        std::env::temp_dir().join(format!("asgart-export-large-{}.sqlite", std::process::id()));
+    let mut out = fs::File::create(&path).unwrap();
+    SQLiteExporter::new().save(&result, &mut out).unwrap();
+    drop(out);
+
+    let connection = rusqlite::Connection::open(&path).unwrap();
+    let sides = connection
+        .prepare("SELECT side FROM arms WHERE start_min < ?1 AND end_max > ?2")
+        .unwrap()
+        .query_map(rusqlite::params![4_294_967_500i64, 4_294_967_400i64], |r| {
+            r.get::<_, String>(0)
+        })
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(sides, vec!["left"]);
+
+    let read = RunResult::from_file(path.to_str().unwrap()).unwrap();
+    assert_eq!(read.families[0][0].chr_right_position, 4_900_000_000);
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,106 @@
+//! Check the parsing and evaluation of filter expressions.
+
+mod common;
+
+use asgart::{
+    filter::Filter,
+    structs::{RunResult, SD},
+};
+
+use common::sd;
+
+fn result() -> RunResult {
+    common::result(
+        &[("chrX", 20_000), ("chrY", 20_000)],
+        vec![
+            vec![
+                SD {
+                    identity: 98.0,
+                    ..sd(("chrX", 100, 12_000), ("chrX", 5_000, 12_010))
+                },
+                sd(("chrX", 100, 2_000), ("chrY", 5_000, 2_010)),
+                SD {
+                    identity: 90.0,
+                    reversed: true,
+                    complemented: true,
+                    ..sd(("chrY", 100, 15_000), ("chrY", 5_000, 15_010))
+                },
+            ],
+            vec![SD {
+                identity: 100.0,
+                reversed: true,
+                complemented: true,
+                ..sd(("chrX", 100, 500), ("chrX", 5_000, 510))
+            }],
+        ],
+    )
+}
+
+fn lengths(result: &RunResult) -> Vec<usize> {
+    result
+        .families
+        .iter()
+        .flatten()
+        .map(|sd| sd.left_length)
+        .collect()
+}
+
+#[test]
+fn duplicons() {
+    let mut r = result();
+    r.filter_duplicons(
+        &Filter::duplicons(
+            "identity > 0.95 && max(left_length, right_length) >= 10000 && (chr_left =~ 'chrY' || reversed)",
+        )
+        .unwrap(),
+    );
+    assert!(r.families.is_empty());
+
+    let mut r = result();
+    r.filter_duplicons(
+        &Filter::duplicons("identity >= 0.9 && (chr_left =~ '^chrY$' || inter) && !direct")
+            .unwrap(),
+    );
+    assert_eq!(lengths(&r), vec![15_000]);
+
+    let mut r = result();
+    r.filter_duplicons(
+        &Filter::duplicons("intra && family_size > 1 && -left_length + 2 * 1_000 < 0").unwrap(),
+    );
+    assert_eq!(lengths(&r), vec![12_000, 15_000]);
+
+    let mut r = result();
+    r.filter_duplicons(
+        &Filter::duplicons("chr_right !~ 'Y' || abs(right_length - left_length) != 10").unwrap(),
+    );
+    assert_eq!(lengths(&r), vec![12_000, 500]);
+}
+
+#[test]
+fn families() {
+    let mut r = result();
+    r.filter_families(&Filter::families("family_size <= 2").unwrap());
+    assert_eq!(lengths(&r), vec![500]);
+}
+
+#[test]
+fn invalid_expressions() {
+    for expr in [
+        "",
+        "identity",
+        "identity = 0.5",
+        "identity > 'a'",
+        "chr_left < 3",
+        "chr_left =~ chr_right",
+        "chr_left =~ '('",
+        "reversed && (inter",
+        "length > 0",
+        "max() > 1",
+        "abs(1, 2) > 1",
+        "inter intra",
+        "chr_left == 'chrX",
+    ] {
+        assert!(Filter::duplicons(expr).is_err(), "{}", expr);
+    }
+    assert!(Filter::families("identity > 0.5").is_err());
+}
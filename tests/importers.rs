@@ -0,0 +1,133 @@
+//! Check the import of third-party duplication catalogues.
+
+mod common;
+
+use std::fs;
+
+use asgart::importers;
+
+use common::write;
+
+fn genome() -> String {
+    write(
+        "genome.fai",
+        "chr1\t10000\t6\t60\t61\nchr2\t5000\t10179\t60\t61\n",
+    )
+}
+
+#[test]
+fn superdups() {
+    let genome = genome();
+    let table = write(
+        "superdups.txt",
+        "585\tchr1\t100\t1100\tchr2:200\t0\t+\tchr2\t200\t1200\t5000\t1\t1000\tN/A\tN/A\t1\t1\tx\t1000\t0\t0\t1000\t990\t10\t5\t5\t0.99\t0.99\t0.01\t0.01\n\
+         585\tchr2\t200\t1200\tchr1:100\t0\t+\tchr1\t100\t1100\t10000\t1\t1000\tN/A\tN/A\t1\t1\tx\t1000\t0\t0\t1000\t990\t10\t5\t5\t0.99\t0.99\t0.01\t0.01\n\
+         585\tchr1\t3000\t4000\tchr1:6000\t0\t_\tchr1\t6000\t7000\t10000\t2\t1000\tN/A\tN/A\t1\t1\tx\t1000\t0\t0\t1000\t950\t50\t25\t25\t0.95\t0.95\t0.05\t0.05\n",
+    );
+    let result =
+        importers::import_files("superdups", std::slice::from_ref(&table), &genome).unwrap();
+    fs::remove_file(table).unwrap();
+    fs::remove_file(genome).unwrap();
+
+    assert_eq!(result.strand.length, 15000);
+    assert_eq!(result.families.len(), 2);
+    let sd = &result.families[0][0];
+    assert_eq!((sd.chr_left.as_str(), sd.chr_left_position), ("chr1", 100));
+    assert_eq!(
+        (sd.chr_right.as_str(), sd.global_right_position),
+        ("chr2", 10200)
+    );
+    assert!(!sd.reversed && (sd.identity - 99.0).abs() < 1e-3);
+    assert!(result.families[1][0].reversed && result.families[1][0].complemented);
+}
+
+#[test]
+fn bedpe_families() {
+    let genome = genome();
+    let bedpe = write(
+        "families.bedpe",
+        "chr1\t0\t500\tchr1\t2000\t2500\tSD#0/0\t0.98\t+\t+\n\
+         chr1\t0\t500\tchr2\t0\t500\tSD#0/1\t0.97\t+\t-\n\
+         chr2\t1000\t1500\tchr2\t3000\t3500\tSD#1/0\t0.95\t+\t+\n",
+    );
+    let result = importers::import_files("bedpe", std::slice::from_ref(&bedpe), &genome).unwrap();
+    fs::remove_file(bedpe).unwrap();
+    fs::remove_file(genome).unwrap();
+
+    assert_eq!(result.families.len(), 2);
+    assert_eq!(result.families[0].len(), 2);
+    assert!(result.families[0][1].reversed);
+}
+
+#[test]
+fn bedpe_headers() {
+    let genome = genome();
+    let import = |name: &str, content: &str| {
+        let bedpe = write(name, content);
+        let result = importers::import_files("bedpe", std::slice::from_ref(&bedpe), &genome);
+        fs::remove_file(bedpe).unwrap();
+        result
+    };
+    let record = "chr1\t0\t500\tchr1\t2000\t2500\tSD#0/0\t0.98\t+\t+\n";
+
+    for header in [
+        "#chrom1\tstart1\tend1\n",
+        "track name=sds\n",
+        "chrom1\tstart1\tend1\tchrom2\tstart2\tend2\n",
+    ] {
+        let result = import("header.bedpe", &format!("{}{}", header, record)).unwrap();
+        assert_eq!(result.families.len(), 1, "{}", header);
+    }
+
+    // Past the first line, unparsable rows are errors
+    let error = import(
+        "invalid.bedpe",
+        &format!("{}chr1\tzero\t500\tchr2\t0\t500\n", record),
+    )
+    .unwrap_err();
+    assert!(format!("{:#}", error).contains(":L2"), "{:#}", error);
+    fs::remove_file(genome).unwrap();
+}
+
+#[test]
+fn sedef() {
+    let genome = genome();
+    let bedpe = write(
+        "sedef.bedpe",
+        "#chr1\tstart1\tend1\tchr2\tstart2\tend2\tname\tscore\tstrand1\tstrand2\tmax_len\taln_len\tcomment\tindel_a\tindel_b\talnB\tmatchB\tmismatchB\ttransitionsB\ttransversions\tfracMatch\tfracMatchIndel\tjck\tk2K\taln_gaps\tuppercaseA\tuppercaseB\tuppercaseMatches\taln_matches\taln_mismatches\taln_gaps\taln_gap_bases\tcigar\tfilter_score\n\
+         chr1\t100\t1100\tchr2\t200\t1210\t.\t0\t+\t-\t1010\t1010\tOK\t0\t10\t1010\t970\t30\t20\t10\t0.97\t0.96\t0.1\t0.03\t10\t1000\t1010\t970\t970\t30\t1\t10\t1000M10D\t0\n",
+    );
+    let result = importers::import_files("sedef", std::slice::from_ref(&bedpe), &genome).unwrap();
+    fs::remove_file(bedpe).unwrap();
+    fs::remove_file(genome).unwrap();
+
+    assert_eq!(result.families.len(), 1);
+    let sd = &result.families[0][0];
+    assert_eq!((sd.chr_right.as_str(), sd.right_length), ("chr2", 1010));
+    assert!(sd.reversed && sd.complemented);
+    // The identity is read from `fracMatch`, not `fracMatchIndel`
+    assert!((sd.identity - 97.0).abs() < 1e-3, "{}", sd.identity);
+}
+
+#[test]
+fn paf_self_alignments() {
+    let genome = genome();
+    let paf = write(
+        "self.paf",
+        "chr1\t10000\t0\t10000\t+\tchr1\t10000\t0\t10000\t10000\t10000\t60\n\
+         chr1\t10000\t100\t1100\t-\tchr2\t5000\t200\t1200\t900\t1000\t60\n",
+    );
+    let result = importers::import_files("paf", std::slice::from_ref(&paf), &genome).unwrap();
+    fs::remove_file(paf).unwrap();
+
+    assert_eq!(result.families.len(), 1);
+    assert!((result.families[0][0].identity - 90.0).abs() < 1e-3);
+
+    let out_of_bounds = write(
+        "oob.paf",
+        "chr2\t5000\t4500\t5500\t+\tchr1\t10000\t0\t1000\t900\t1000\t60\n",
+    );
+    assert!(importers::import_files("paf", std::slice::from_ref(&out_of_bounds), &genome).is_err());
+    fs::remove_file(out_of_bounds).unwrap();
+    fs::remove_file(genome).unwrap();
+}
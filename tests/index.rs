@@ -0,0 +1,81 @@
+//! Check the spatial queries over the duplicons of a result.
+
+mod common;
+
+use asgart::{
+    index::{DupliconId, RunResultIndex},
+    regions::Region,
+    structs::RunResult,
+};
+
+use common::sd;
+
+fn result() -> RunResult {
+    common::result(
+        &[("chr1", 10_000), ("chr2", 10_000)],
+        vec![
+            vec![
+                sd(("chr1", 1_000, 500), ("chr1", 5_000, 500)),
+                sd(("chr1", 1_200, 500), ("chr2", 2_000, 500)),
+            ],
+            vec![sd(("chr2", 100, 500), ("chr2", 8_000, 500))],
+        ],
+    )
+}
+
+fn id(family: usize, member: usize) -> DupliconId {
+    DupliconId { family, member }
+}
+
+#[test]
+fn overlapping() {
+    let result = result();
+    let index = RunResultIndex::new(&result);
+
+    assert_eq!(
+        index.duplicons_overlapping("chr1", 1_400, 1_600),
+        vec![id(0, 0), id(0, 1)]
+    );
+    assert_eq!(
+        index.duplicons_overlapping("chr1", 1_500, 1_600),
+        vec![id(0, 1)]
+    );
+    assert!(index.duplicons_overlapping("chr1", 1_700, 5_000).is_empty());
+    assert!(index.duplicons_overlapping("chr3", 0, 5_000).is_empty());
+    assert_eq!(
+        index.duplicons_overlapping_global(9_000, 10_200),
+        vec![id(1, 0)]
+    );
+    assert_eq!(
+        index.duplicons_overlapping_global(5_400, 12_001),
+        vec![id(0, 0), id(0, 1), id(1, 0)]
+    );
+    assert_eq!(index.duplicon(id(0, 1)).chr_right, "chr2");
+    assert!(index.has_fragment("chr2") && !index.has_fragment("chr3"));
+}
+
+#[test]
+fn nearest() {
+    let result = result();
+    let index = RunResultIndex::new(&result);
+
+    assert_eq!(index.nearest_duplicon("chr1", 1_100), Some((id(0, 0), 0)));
+    assert_eq!(index.nearest_duplicon("chr1", 900), Some((id(0, 0), 100)));
+    assert_eq!(index.nearest_duplicon("chr1", 5_600), Some((id(0, 0), 101)));
+    assert_eq!(
+        index.nearest_duplicon("chr2", 5_000),
+        Some((id(0, 1), 2_501))
+    );
+    assert_eq!(index.nearest_duplicon("chr3", 0), None);
+}
+
+#[test]
+fn families() {
+    let result = result();
+    let index = RunResultIndex::new(&result);
+
+    let region = |s: &str| s.parse::<Region>().unwrap();
+    assert_eq!(index.families_in(&region("chr2:0-3000")), vec![0, 1]);
+    assert_eq!(index.families_in(&region("chr2:7000-9000")), vec![1]);
+    assert!(index.families_in(&region("chr1:6000-9000")).is_empty());
+}
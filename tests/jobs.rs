@@ -0,0 +1,214 @@
+//! Check the splitting of a search into jobs and the merging of their
+//! results.
+
+mod common;
+
+use asgart::{
+    jobs::{merge_results, split_chunks},
+    regions::Region,
+    structs::{RunResult, SD},
+};
+
+const FRAGMENTS: [(&str, usize); 2] = [("chr1", 1_000), ("chr2", 500)];
+
+fn region(chr: &str, start: usize, end: usize) -> Region {
+    Region {
+        chr: chr.to_owned(),
+        start,
+        end,
+    }
+}
+
+fn job(regions: Vec<Region>, families: Vec<Vec<SD>>) -> RunResult {
+    let mut result = common::result(&FRAGMENTS, families);
+    result.settings.regions = Some(regions);
+    result
+}
+
+#[test]
+fn split() {
+    let map = common::strand(&FRAGMENTS).map;
+    let chunks = [(0, 1_000), (1_000, 500)];
+
+    assert_eq!(
+        split_chunks(&chunks, &map, 3),
+        vec![
+            vec![region("chr1", 0, 500)],
+            vec![region("chr1", 500, 1_000)],
+            vec![region("chr2", 0, 500)],
+        ]
+    );
+    // Jobs may span several fragments
+    assert_eq!(
+        split_chunks(&chunks, &map, 2),
+        vec![
+            vec![region("chr1", 0, 750)],
+            vec![region("chr1", 750, 1_000), region("chr2", 0, 500)],
+        ]
+    );
+    assert_eq!(
+        split_chunks(&[(200, 300)], &map, 1),
+        vec![vec![region("chr1", 200, 500)]]
+    );
+    for count in [1, 4, 7, 2_000] {
+        let split = split_chunks(&chunks, &map, count);
+        assert!(split.len() <= count);
+        assert_eq!(
+            split
+                .iter()
+                .flatten()
+                .map(|r| r.end - r.start)
+                .sum::<usize>(),
+            1_500
+        );
+    }
+}
+
+#[test]
+fn merge() {
+    let straddling = |left: (usize, usize), right: (usize, usize)| SD {
+        left_seq: Some("A".repeat(left.1)),
+        right_seq: Some("A".repeat(right.1)),
+        ..common::sd(("chr1", left.0, left.1), ("chr2", right.0, right.1))
+    };
+    let first = job(
+        vec![region("chr1", 0, 500)],
+        vec![vec![straddling((400, 300), (100, 300))]],
+    );
+    let second = job(
+        vec![region("chr1", 500, 1_000)],
+        vec![
+            vec![straddling((450, 350), (150, 300))],
+            vec![common::sd(("chr1", 600, 100), ("chr1", 900, 100))],
+        ],
+    );
+
+    let merged = merge_results(vec![first, second]).unwrap();
+    assert!(merged.settings.regions.is_none());
+    assert_eq!(merged.families.len(), 2);
+    // Found by both jobs, the duplication crossing their boundary is fused
+    let fused = &merged.families[0][0];
+    assert_eq!(merged.families[0].len(), 1);
+    assert_eq!(
+        (
+            fused.chr_left_position,
+            fused.left_length,
+            fused.chr_right_position,
+            fused.right_length
+        ),
+        (400, 400, 100, 350)
+    );
+    assert_eq!(
+        (fused.global_left_position, fused.global_right_position),
+        (400, 1_100)
+    );
+    assert!(fused.left_seq.is_none() && fused.right_seq.is_none());
+    assert_eq!(merged.families[1][0].chr_left_position, 600);
+
+    // As are the pieces of a duplication cut at the boundary
+    let merged = merge_results(vec![
+        job(
+            vec![region("chr1", 0, 500)],
+            vec![vec![straddling((300, 200), (100, 200))]],
+        ),
+        job(
+            vec![region("chr1", 500, 1_000)],
+            vec![vec![straddling((500, 250), (300, 250))]],
+        ),
+    ])
+    .unwrap();
+    assert_eq!(merged.families.len(), 1);
+    let fused = &merged.families[0][0];
+    assert_eq!(
+        (
+            fused.chr_left_position,
+            fused.left_length,
+            fused.chr_right_position,
+            fused.right_length
+        ),
+        (300, 450, 100, 450)
+    );
+
+    // A family found by both jobs is merged whole
+    let merged = merge_results(vec![
+        job(
+            vec![region("chr1", 0, 500)],
+            vec![vec![
+                common::sd(("chr1", 100, 100), ("chr2", 300, 100)),
+                straddling((400, 300), (100, 300)),
+            ]],
+        ),
+        job(
+            vec![region("chr1", 500, 1_000)],
+            vec![
+                vec![common::sd(("chr1", 600, 100), ("chr1", 900, 100))],
+                vec![
+                    straddling((450, 350), (150, 300)),
+                    common::sd(("chr1", 520, 50), ("chr1", 800, 50)),
+                ],
+            ],
+        ),
+    ])
+    .unwrap();
+    assert_eq!(
+        merged
+            .families
+            .iter()
+            .map(|family| family
+                .iter()
+                .map(|sd| sd.chr_left_position)
+                .collect::<Vec<_>>())
+            .collect::<Vec<_>>(),
+        vec![vec![100, 400, 520], vec![600]]
+    );
+
+    // Nor are arms meeting across a fragment boundary
+    let merged = merge_results(vec![
+        job(
+            vec![region("chr1", 0, 500)],
+            vec![vec![common::sd(("chr1", 400, 100), ("chr2", 0, 100))]],
+        ),
+        job(
+            vec![region("chr1", 500, 1_000)],
+            vec![vec![common::sd(("chr1", 500, 100), ("chr1", 900, 100))]],
+        ),
+    ])
+    .unwrap();
+    assert_eq!(merged.families.iter().flatten().count(), 2);
+
+    // Opposite orientations are not fused
+    let reversed = job(
+        vec![region("chr1", 500, 1_000)],
+        vec![vec![SD {
+            reversed: true,
+            ..straddling((450, 350), (150, 300))
+        }]],
+    );
+    let merged = merge_results(vec![
+        job(
+            vec![region("chr1", 0, 500)],
+            vec![vec![straddling((400, 300), (100, 300))]],
+        ),
+        reversed,
+    ])
+    .unwrap();
+    assert_eq!(merged.families.iter().flatten().count(), 2);
+}
+
+#[test]
+fn merge_incompatible() {
+    assert!(merge_results(Vec::new()).is_err());
+
+    let mut other = job(vec![region("chr1", 500, 1_000)], Vec::new());
+    other.settings.max_cardinality += 1;
+    assert!(merge_results(vec![job(vec![region("chr1", 0, 500)], Vec::new()), other]).is_err());
+
+    let mut other = job(vec![region("chr1", 500, 1_000)], Vec::new());
+    other.strand.map[1].length += 1;
+    assert!(merge_results(vec![job(vec![region("chr1", 0, 500)], Vec::new()), other]).is_err());
+
+    // Neither the regions nor the preset matter
+    let mut other = job(vec![region("chr1", 500, 1_000)], Vec::new());
+    other.settings.preset = Some("human-sd".to_owned());
+    assert!(merge_results(vec![job(vec![region("chr1", 0, 500)], Vec::new()), other]).is_ok());
+}
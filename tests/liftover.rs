@@ -0,0 +1,130 @@
+//! Check the projection of duplicons through chain files.
+
+use std::io::Cursor;
+
+mod common;
+
+use asgart::liftover::{LiftedArm, Liftover, Rejection};
+
+use common::{sd, strand};
+
+const CHAINS: &str = "\
+chain 5000 chr1 10000 + 0 5000 chrA 12000 + 1000 6100 1
+3000 0 100
+2000
+
+chain 4000 chr1 10000 + 5000 10000 chrB 8000 - 0 5000 2
+5000
+
+chain 1000 chr3 1000 + 0 1000 chrC 1000 + 0 1000 3
+1000
+";
+
+fn liftover() -> Liftover {
+    Liftover::from_reader(Cursor::new(CHAINS), "chains").unwrap()
+}
+
+#[test]
+fn arms() {
+    let liftover = liftover();
+    let arm = |chr: &str, start, length, reversed| LiftedArm {
+        chr: chr.to_owned(),
+        start,
+        length,
+        reversed,
+    };
+
+    assert_eq!(
+        liftover.lift("chr1", 100, 500),
+        Some(arm("chrA", 1100, 500, false))
+    );
+    // Spanning the 100bp insertion in the query
+    assert_eq!(
+        liftover.lift("chr1", 2900, 200),
+        Some(arm("chrA", 3900, 300, false))
+    );
+    assert_eq!(
+        liftover.lift("chr1", 6000, 500),
+        Some(arm("chrB", 6500, 500, true))
+    );
+    // The chain mapping most of the span wins
+    assert_eq!(
+        liftover.lift("chr1", 4900, 400),
+        Some(arm("chrB", 7700, 300, true))
+    );
+    assert_eq!(liftover.lift("chr2", 0, 100), None);
+
+    assert!(Liftover::from_reader(Cursor::new("100 0 0\n"), "chains").is_err());
+    assert!(Liftover::from_reader(
+        Cursor::new("chain nan chr1 10 + 0 10 chrA 10 + 0 10 1\n10\n"),
+        "chains"
+    )
+    .is_err());
+    assert!(Liftover::from_reader(Cursor::new("chain 1 chr1 10 + 0 10\n"), "chains").is_err());
+}
+
+#[test]
+fn results() {
+    let mut result = common::result(
+        &[("chr1", 10_000), ("chr2", 1_000), ("chr3", 1_000)],
+        vec![
+            vec![
+                sd(("chr1", 100, 500), ("chr1", 6_000, 500)),
+                sd(("chr1", 2_900, 200), ("chr1", 7_000, 200)),
+            ],
+            vec![sd(("chr1", 100, 500), ("chr2", 100, 500))],
+            vec![sd(("chr1", 100, 500), ("chr3", 200, 500))],
+        ],
+    );
+    let target = strand(&[("chrA", 12_000), ("chrB", 8_000)]);
+
+    let rejected = result.liftover(&liftover(), &target, 0.1);
+    assert_eq!(
+        result
+            .strand
+            .map
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["chrA", "chrB"]
+    );
+    assert_eq!(result.families.len(), 1);
+    let lifted = &result.families[0][0];
+    assert_eq!(
+        (
+            lifted.chr_left.as_str(),
+            lifted.chr_left_position,
+            lifted.global_left_position
+        ),
+        ("chrA", 1_100, 1_100)
+    );
+    assert_eq!(
+        (
+            lifted.chr_right.as_str(),
+            lifted.chr_right_position,
+            lifted.global_right_position
+        ),
+        ("chrB", 6_500, 18_500)
+    );
+    // Only the right arm has been projected on the reverse strand
+    assert!(lifted.reversed && lifted.complemented);
+
+    assert_eq!(
+        rejected
+            .iter()
+            .map(|(family, _, rejection)| (*family, rejection.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            (0, Rejection::LengthChanged { from: 200, to: 300 }),
+            (
+                1,
+                Rejection::Unmapped {
+                    chr: "chr2".to_owned(),
+                    start: 100
+                }
+            ),
+            (2, Rejection::UnknownFragment("chrC".to_owned())),
+        ]
+    );
+    assert_eq!(rejected[0].1.chr_left_position, 2_900);
+}
@@ -0,0 +1,129 @@
+//! Check the filtering of results by genomic regions.
+
+mod common;
+
+use asgart::{
+    regions::Region,
+    structs::{RegionOverlap, RunResult, SD},
+};
+
+use common::sd;
+
+fn result() -> RunResult {
+    common::result(
+        &[("chr1", 10_000), ("chr2", 10_000)],
+        vec![
+            vec![sd(("chr1", 1_000, 500), ("chr1", 5_000, 500))],
+            vec![SD {
+                left_seq: Some("A".repeat(500)),
+                right_seq: Some("A".repeat(500)),
+                ..sd(("chr1", 1_900, 500), ("chr2", 2_000, 500))
+            }],
+            vec![sd(("chr2", 100, 500), ("chr2", 8_000, 500))],
+        ],
+    )
+}
+
+#[test]
+fn parse() {
+    assert_eq!(
+        "chr15:20,000,000-32000000".parse::<Region>().unwrap(),
+        Region {
+            chr: "chr15".to_owned(),
+            start: 20_000_000,
+            end: 32_000_000
+        }
+    );
+    assert_eq!("HLA:A:1-2".parse::<Region>().unwrap().chr, "HLA:A");
+    for s in ["chr1", "chr1:10", ":1-2", "chr1:5-1", "chr1:a-10"] {
+        assert!(s.parse::<Region>().is_err(), "{}", s);
+    }
+}
+
+#[test]
+fn overlaps() {
+    let regions = vec!["chr1:0-2000".parse::<Region>().unwrap()];
+
+    let mut r = result();
+    r.keep_regions(&regions, RegionOverlap::AnyArm, false)
+        .unwrap();
+    assert_eq!(r.families.len(), 2);
+    assert_eq!(r.strand.map.len(), 2);
+
+    let mut r = result();
+    r.keep_regions(&regions, RegionOverlap::BothArms, false)
+        .unwrap();
+    assert!(r.families.is_empty());
+
+    let mut r = result();
+    let regions = vec![
+        "chr1:0-1500".parse::<Region>().unwrap(),
+        "chr1:1200-2100".parse::<Region>().unwrap(),
+        "chr2:0-4000".parse::<Region>().unwrap(),
+    ];
+    r.keep_regions(&regions, RegionOverlap::BothArms, false)
+        .unwrap();
+    assert_eq!(r.families.len(), 1);
+    assert_eq!(r.families[0][0].chr_left_position, 1_900);
+
+    let mut r = result();
+    assert!(r
+        .keep_regions(
+            &["chr3:0-10".parse().unwrap()],
+            RegionOverlap::AnyArm,
+            false
+        )
+        .is_err());
+    assert!(r
+        .keep_regions(
+            &["chr2:0-10001".parse().unwrap()],
+            RegionOverlap::AnyArm,
+            false
+        )
+        .is_err());
+}
+
+#[test]
+fn clip() {
+    let regions = vec![
+        "chr2:1000-3000".parse::<Region>().unwrap(),
+        "chr1:0-1200".parse::<Region>().unwrap(),
+        "chr1:1100-2200".parse::<Region>().unwrap(),
+    ];
+    let mut r = result();
+    r.keep_regions(&regions, RegionOverlap::AnyArm, true)
+        .unwrap();
+
+    assert_eq!(
+        r.strand
+            .map
+            .iter()
+            .map(|c| (c.name.as_str(), c.position, c.length))
+            .collect::<Vec<_>>(),
+        vec![("chr1:0-2200", 0, 2200), ("chr2:1000-3000", 2200, 2000)]
+    );
+    assert_eq!(r.strand.length, 4200);
+    assert_eq!(r.families.len(), 1);
+
+    let sd = &r.families[0][0];
+    assert_eq!(sd.chr_left, "chr1:0-2200");
+    assert_eq!(
+        (
+            sd.chr_left_position,
+            sd.left_length,
+            sd.global_left_position
+        ),
+        (1_900, 300, 1_900)
+    );
+    assert!(sd.left_seq.is_none());
+    assert_eq!(sd.chr_right, "chr2:1000-3000");
+    assert_eq!(
+        (
+            sd.chr_right_position,
+            sd.right_length,
+            sd.global_right_position
+        ),
+        (1_000, 500, 3_200)
+    );
+    assert_eq!(sd.right_seq.as_ref().map(|s| s.len()), Some(500));
+}
@@ -0,0 +1,233 @@
+//! Check the lookup of probes in the suffix array, notably of the probes
+//! holding IUPAC ambiguity codes.
+
+mod common;
+
+use std::process::Command;
+
+use asgart::{
+    divsufsort::{divsufsort64, SuffixArray},
+    searcher::Searcher,
+    structs::{AmbiguityPolicy, RunResult, AMBIGUITY_CODES},
+    utils::complement_nucleotide,
+};
+
+fn index(dna: &[u8]) -> (SuffixArray, Searcher) {
+    let mut sa = vec![0; dna.len()];
+    unsafe {
+        divsufsort64(dna.as_ptr(), sa.as_mut_ptr(), dna.len() as i64);
+    }
+    let searcher = Searcher::new(dna, &sa, 0);
+    (sa, searcher)
+}
+
+fn starts(dna: &[u8], sa: &SuffixArray, searcher: &Searcher, pattern: &[u8]) -> [Vec<usize>; 2] {
+    [AmbiguityPolicy::Mismatch, AmbiguityPolicy::Wildcard].map(|policy| {
+        let mut starts = searcher
+            .search_ambiguous(dna, sa, pattern, policy)
+            .iter()
+            .map(|m| m.start)
+            .collect::<Vec<_>>();
+        starts.sort_unstable();
+        starts
+    })
+}
+
+/// A copy of `pattern` where the `count` first nucleotides are replaced by
+/// a three-way ambiguity code standing for them
+fn blur(pattern: &[u8], count: usize) -> Vec<u8> {
+    let mut pattern = pattern.to_vec();
+    for n in pattern.iter_mut().take(count) {
+        *n = match *n {
+            b'A' => b'D',
+            b'C' => b'B',
+            b'G' => b'V',
+            _ => b'H',
+        };
+    }
+    pattern
+}
+
+#[test]
+fn ambiguous_probes() {
+    let mut dna = common::random_dna(20_000, 1);
+    let site = dna[1_000..1_030].to_vec();
+    dna[5_000..5_030].copy_from_slice(&site);
+    dna[1_003] = b'A';
+    dna[5_003] = b'G';
+    let (sa, searcher) = index(&dna);
+
+    // Concrete probes are looked up as they are, whatever the policy
+    let concrete = &dna[1_000..1_030];
+    let expected = searcher
+        .search(&dna, &sa, concrete)
+        .iter()
+        .map(|m| m.start)
+        .collect::<Vec<_>>();
+    assert_eq!(expected, vec![1_000]);
+    assert_eq!(
+        starts(&dna, &sa, &searcher, concrete),
+        [vec![1_000], vec![1_000]]
+    );
+
+    // An ambiguity code in the cached prefix of a probe is either skipped or
+    // expanded, but never reaches the cache
+    let mut probe = dna[1_000..1_030].to_vec();
+    probe[3] = b'R';
+    assert_eq!(
+        starts(&dna, &sa, &searcher, &probe),
+        [vec![], vec![1_000, 5_000]]
+    );
+    probe[3] = b'Y';
+    assert_eq!(
+        starts(&dna, &sa, &searcher, &probe),
+        [Vec::<usize>::new(), vec![]]
+    );
+}
+
+#[test]
+#[should_panic(expected = "Unable to find")]
+fn unexpanded_probe() {
+    let dna = common::random_dna(1_000, 2);
+    let (sa, searcher) = index(&dna);
+    let mut probe = dna[100..120].to_vec();
+    probe[0] = b'R';
+    searcher.search(&dna, &sa, &probe);
+}
+
+#[test]
+fn expansions_limit() {
+    let dna = common::random_dna(20_000, 3);
+    let (sa, searcher) = index(&dna);
+    let site = &dna[7_000..7_030];
+
+    // 3^5 = 243 concrete patterns are looked up, 3^6 = 729 are too many and
+    // the probe is skipped
+    assert!(starts(&dna, &sa, &searcher, &blur(site, 5))[1].contains(&7_000));
+    assert_eq!(searcher.overflowing(), 0);
+    assert!(starts(&dna, &sa, &searcher, &blur(site, 6))[1].is_empty());
+    assert_eq!(searcher.overflowing(), 1);
+    // Even past the cached prefix
+    let mut tail = site.to_vec();
+    tail[20..26].copy_from_slice(&blur(&site[20..26], 6));
+    assert!(starts(&dna, &sa, &searcher, &tail)[1].is_empty());
+    assert_eq!(searcher.overflowing(), 2);
+}
+
+#[test]
+fn ambiguous_sequence() {
+    let mut dna = common::random_dna(20_000, 4);
+    dna[3_010] = b'A';
+    let site = dna[3_000..3_030].to_vec();
+    dna[9_000..9_030].copy_from_slice(&site);
+    dna[9_010] = b'R';
+    let (sa, searcher) = index(&dna);
+
+    // Ambiguity codes in the sequence only match themselves
+    for n in [b'A', b'G'] {
+        let mut probe = site.clone();
+        probe[10] = n;
+        for found in starts(&dna, &sa, &searcher, &probe) {
+            assert!(!found.contains(&9_000), "{:?}", found);
+        }
+    }
+    // So that only the probes taken from the ambiguous copy find the pair
+    assert_eq!(
+        starts(&dna, &sa, &searcher, &dna[9_000..9_030])[1],
+        vec![3_000]
+    );
+    assert!(!starts(&dna, &sa, &searcher, &dna[3_000..3_030])[1].contains(&9_000));
+}
+
+#[test]
+fn complemented_ambiguity() {
+    for n in AMBIGUITY_CODES {
+        let c = complement_nucleotide(n);
+        assert!(AMBIGUITY_CODES.contains(&c), "{}", n as char);
+        assert_eq!(complement_nucleotide(c), n);
+        assert_eq!(
+            complement_nucleotide(n.to_ascii_lowercase()),
+            c.to_ascii_lowercase()
+        );
+    }
+    // Self-complementary codes are those standing for complementary pairs
+    assert_eq!(complement_nucleotide(b'S'), b'S');
+    assert_eq!(complement_nucleotide(b'W'), b'W');
+
+    // A reverse-complemented ambiguous probe finds the reverse-complemented
+    // sites
+    let mut dna = common::random_dna(20_000, 5);
+    let site = dna[2_000..2_030].to_vec();
+    let rc = site
+        .iter()
+        .rev()
+        .map(|n| complement_nucleotide(*n))
+        .collect::<Vec<_>>();
+    dna[12_000..12_030].copy_from_slice(&rc);
+    let (sa, searcher) = index(&dna);
+
+    let mut probe = site;
+    probe[5] = if b"AG".contains(&probe[5]) {
+        b'R'
+    } else {
+        b'Y'
+    };
+    let rc_probe = probe
+        .iter()
+        .rev()
+        .map(|n| complement_nucleotide(*n))
+        .collect::<Vec<_>>();
+    assert_eq!(starts(&dna, &sa, &searcher, &probe)[1], vec![2_000]);
+    assert_eq!(starts(&dna, &sa, &searcher, &rc_probe)[1], vec![12_000]);
+}
+
+#[test]
+fn ambiguous_genome() {
+    // A duplication whose first copy is sprinkled with ambiguity codes
+    let mut dna = common::random_dna(30_000, 6);
+    let copy = dna[5_000..8_000].to_vec();
+    dna[20_000..23_000].copy_from_slice(&copy);
+    for i in (5_003..8_000).step_by(97) {
+        dna[i] = if b"AG".contains(&dna[i]) { b'R' } else { b'Y' };
+    }
+    let fasta = common::write(
+        "ambiguous.fa",
+        &format!(">chr1\n{}\n", String::from_utf8(dna).unwrap()),
+    );
+    let out = common::write("ambiguous.json", "");
+
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_asgart"))
+            .args([&fasta, "--threads", "2", "--out", &out])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{:?}: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        RunResult::from_file(&out).unwrap()
+    };
+
+    for policy in ["mismatch", "wildcard"] {
+        // Ambiguous probes are also met on the complemented strand
+        run(&["--ambiguity", policy, "-R", "-C"]);
+        let result = run(&["--ambiguity", policy]);
+        assert!(
+            result.families.iter().flatten().any(|sd| {
+                sd.chr_left_position < 5_500
+                    && sd.chr_left_position + sd.left_length > 7_500
+                    && sd.chr_right_position < 20_500
+            }),
+            "{}: {:?}",
+            policy,
+            result.families
+        );
+    }
+
+    for f in [fasta, out] {
+        std::fs::remove_file(f).unwrap();
+    }
+}
@@ -0,0 +1,361 @@
+//! Assess the recall and precision of ASGART on simulated genomes with known
+//! duplications.
+
+mod common;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use asgart::{
+    compare,
+    exporters::{Exporter, JSONExporter},
+    regions::{self, Region},
+    simulate::{Evaluation, Orientation, Placement, Simulation, SimulationSettings},
+    structs::{RunResult, SD},
+};
+
+const MIN_OVERLAP: f64 = 0.5;
+
+fn settings(seed: u64) -> SimulationSettings {
+    SimulationSettings {
+        seed,
+        fragments: 3,
+        fragment_length: 40_000,
+        families: 4,
+        length: (1500, 3000),
+        copies: (2, 3),
+        identity: 0.99,
+        indel_rate: 0.0005,
+        orientations: vec![Orientation::Direct],
+        placement: Placement::Any,
+    }
+}
+
+/// Run ASGART with `args` on `fasta`, writing its result in `dir`
+fn run(dir: &Path, fasta: &Path, args: &[&str]) -> RunResult {
+    let out = dir.join("result.json");
+    let output = Command::new(env!("CARGO_BIN_EXE_asgart"))
+        .arg(fasta)
+        .args(args)
+        .args(["--threads", "2", "--out"])
+        .arg(&out)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    RunResult::from_file(out.to_str().unwrap()).unwrap()
+}
+
+/// Simulate a genome with `settings` in a temporary directory, and return
+/// the simulation, the directory and the FASTA file
+fn simulate(name: &str, settings: &SimulationSettings) -> (Simulation, PathBuf, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("asgart-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let fasta = dir.join("genome.fa");
+    let simulation = Simulation::new(settings).unwrap();
+    simulation.write_fasta(fasta.to_str().unwrap()).unwrap();
+    (simulation, dir, fasta)
+}
+
+/// Run ASGART with `args` on the genome simulated with `settings`, and compare
+/// its result to the truth for duplications of the given orientation
+fn evaluate(
+    name: &str,
+    settings: &SimulationSettings,
+    args: &[&str],
+    reversed: bool,
+    complemented: bool,
+) -> Evaluation {
+    let (simulation, dir, fasta) = simulate(name, settings);
+    let result = run(&dir, &fasta, args);
+    fs::remove_dir_all(&dir).unwrap();
+
+    Evaluation::new(
+        &simulation.truth,
+        &result,
+        reversed,
+        complemented,
+        MIN_OVERLAP,
+    )
+}
+
+fn check(evaluation: &Evaluation, min_recall: f64, min_precision: f64) {
+    assert!(evaluation.expected > 0, "{:?}", evaluation);
+    assert!(evaluation.recall() >= min_recall, "{:?}", evaluation);
+    assert!(evaluation.precision() >= min_precision, "{:?}", evaluation);
+}
+
+#[test]
+fn direct_duplications() {
+    let e = evaluate("direct", &settings(1), &[], false, false);
+    check(&e, 0.9, 0.9);
+}
+
+#[test]
+fn intra_fragment_duplications() {
+    let settings = SimulationSettings {
+        placement: Placement::Intra,
+        ..settings(2)
+    };
+    let e = evaluate("intra", &settings, &[], false, false);
+    check(&e, 0.9, 0.9);
+}
+
+#[test]
+fn inter_fragment_duplications() {
+    let settings = SimulationSettings {
+        placement: Placement::Inter,
+        copies: (3, 4),
+        ..settings(3)
+    };
+    let e = evaluate("inter", &settings, &[], false, false);
+    check(&e, 0.9, 0.9);
+}
+
+#[test]
+fn reverse_complemented_duplications() {
+    let settings = SimulationSettings {
+        orientations: vec![Orientation::ReverseComplemented],
+        copies: (2, 2),
+        ..settings(4)
+    };
+    let e = evaluate("rc", &settings, &["-R", "-C"], true, true);
+    check(&e, 0.9, 0.9);
+}
+
+#[test]
+fn diverged_duplications() {
+    let settings = SimulationSettings {
+        identity: 0.95,
+        indel_rate: 0.002,
+        ..settings(5)
+    };
+    let e = evaluate("diverged", &settings, &["--gap-size", "200"], false, false);
+    check(&e, 0.75, 0.9);
+}
+
+#[test]
+fn no_duplications() {
+    let settings = SimulationSettings {
+        families: 0,
+        ..settings(6)
+    };
+    let e = evaluate("none", &settings, &[], false, false);
+    assert_eq!(e.reported, 0, "{:?}", e);
+}
+
+#[test]
+fn truth_identity() {
+    let truth = |identity| {
+        Simulation::new(&SimulationSettings {
+            identity,
+            indel_rate: 0.0,
+            copies: (3, 3),
+            ..settings(7)
+        })
+        .unwrap()
+        .truth
+    };
+
+    assert!(truth(1.0)
+        .families
+        .iter()
+        .flatten()
+        .all(|sd| sd.identity == 100.0));
+    // Each family holds a source and two copies: two pairs involve the
+    // source, and one pair two diverged copies
+    for family in truth(0.9).families {
+        let mut identities = family.iter().map(|sd| sd.identity).collect::<Vec<_>>();
+        identities.sort_by(f32::total_cmp);
+        assert!(
+            identities[1] > 85.0 && identities[2] < 95.0,
+            "{:?}",
+            identities
+        );
+        assert!(
+            identities[0] > 70.0 && identities[0] < 90.0,
+            "{:?}",
+            identities
+        );
+    }
+}
+
+#[test]
+fn restricted_to_regions() {
+    let (simulation, dir, fasta) = simulate("regions", &settings(8));
+    let planted = &simulation.truth.families[0][0];
+    let region = Region {
+        chr: planted.chr_left.clone(),
+        start: planted.chr_left_position,
+        end: planted.chr_left_position + planted.left_length,
+    };
+    let bed = dir.join("regions.bed");
+    regions::write_bed(bed.to_str().unwrap(), std::slice::from_ref(&region)).unwrap();
+    let result = run(&dir, &fasta, &["--regions", bed.to_str().unwrap()]);
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(result.settings.regions, Some(vec![region.clone()]));
+    // Only the duplications having an arm in the region are searched for
+    let probed = |sd: &SD| {
+        region.overlap(&sd.chr_left, sd.chr_left_position, sd.left_length) > 0
+            || region.overlap(&sd.chr_right, sd.chr_right_position, sd.right_length) > 0
+    };
+    assert!(!result.families.is_empty());
+    assert!(result.families.iter().flatten().all(probed));
+    let truth = compare::filter_result(&simulation.truth, probed);
+    let e = Evaluation::new(&truth, &result, false, false, MIN_OVERLAP);
+    check(&e, 0.9, 0.9);
+}
+
+#[test]
+fn excluded_regions() {
+    let (simulation, dir, fasta) = simulate("exclude", &settings(9));
+    let planted = &simulation.truth.families[0][0];
+    let region = Region {
+        chr: planted.chr_left.clone(),
+        start: planted.chr_left_position,
+        end: planted.chr_left_position + planted.left_length,
+    };
+    let bed = dir.join("excluded.bed");
+    regions::write_bed(bed.to_str().unwrap(), std::slice::from_ref(&region)).unwrap();
+    let result = run(&dir, &fasta, &["--exclude", bed.to_str().unwrap()]);
+
+    // Excluding a fragment missing from the genome is an error
+    let unknown = dir.join("unknown.bed");
+    fs::write(&unknown, "chrUn\t0\t100\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_asgart"))
+        .arg(&fasta)
+        .arg("--exclude")
+        .arg(&unknown)
+        .arg("--out")
+        .arg(dir.join("unknown.json"))
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("chrUn"));
+
+    assert_eq!(result.settings.excluded, Some(vec![region.clone()]));
+    // The masked copy is neither probed nor matched, the others are still found
+    let clear = |sd: &SD| {
+        region.overlap(&sd.chr_left, sd.chr_left_position, sd.left_length) == 0
+            && region.overlap(&sd.chr_right, sd.chr_right_position, sd.right_length) == 0
+    };
+    assert!(result.families.iter().flatten().all(clear));
+    let truth = compare::filter_result(&simulation.truth, clear);
+    let e = Evaluation::new(&truth, &result, false, false, MIN_OVERLAP);
+    check(&e, 0.9, 0.9);
+}
+
+#[test]
+fn incremental_search() {
+    let (_, dir, fasta) = simulate("incremental", &settings(10));
+    let mut previous = run(&dir, &fasta, &[]);
+    assert!(previous.strand.map.iter().all(|c| c.checksum.is_some()));
+
+    // Tag the previous duplications to recognize the reused ones
+    previous
+        .families
+        .iter_mut()
+        .flatten()
+        .for_each(|sd| sd.identity = 12.34);
+    let save = |result: &RunResult, name: &str| {
+        let path = dir.join(name);
+        JSONExporter
+            .save(result, &mut fs::File::create(&path).unwrap())
+            .unwrap();
+        path.to_str().unwrap().to_owned()
+    };
+    let tagged = save(&previous, "previous.json");
+
+    // Change a single nucleotide of the last fragment
+    let changed = previous.strand.map.last().unwrap().name.clone();
+    let mut genome = fs::read(&fasta).unwrap();
+    let header = genome.iter().rposition(|&c| c == b'>').unwrap();
+    let i = header + genome[header..].iter().position(|&c| c == b'\n').unwrap() + 10;
+    genome[i] = if genome[i] == b'A' { b'C' } else { b'A' };
+    fs::write(&fasta, genome).unwrap();
+
+    let result = run(&dir, &fasta, &["--incremental", &tagged]);
+    let on_changed = |sd: &SD| sd.chr_left == changed || sd.chr_right == changed;
+    let (touching, kept): (Vec<&SD>, Vec<&SD>) = previous
+        .families
+        .iter()
+        .flatten()
+        .partition(|sd| on_changed(sd));
+    assert!(!touching.is_empty() && !kept.is_empty());
+    // The duplications on unchanged fragments are reused as they were, the
+    // others are searched for again
+    let (found, reused): (Vec<&SD>, Vec<&SD>) = result
+        .families
+        .iter()
+        .flatten()
+        .partition(|sd| sd.identity != 12.34);
+    assert_eq!(reused.len(), kept.len());
+    assert!(reused.iter().all(|sd| !on_changed(sd)));
+    assert!(found.iter().any(|sd| on_changed(sd)));
+
+    // Reused duplications follow their fragments when the layout changes
+    let mut genome = format!(
+        ">chrNew\n{}\n",
+        String::from_utf8(common::random_dna(5_000, 11)).unwrap()
+    )
+    .into_bytes();
+    genome.extend(fs::read(&fasta).unwrap());
+    fs::write(&fasta, genome).unwrap();
+    let result = run(&dir, &fasta, &["--incremental", &tagged]);
+    assert_eq!(result.strand.map[0].name, "chrNew");
+    let located = |sd: &SD, shift: usize| {
+        (
+            sd.chr_left.clone(),
+            sd.chr_left_position,
+            sd.global_left_position + shift,
+            sd.chr_right.clone(),
+            sd.chr_right_position,
+            sd.global_right_position + shift,
+        )
+    };
+    let mut expected = kept.iter().map(|sd| located(sd, 5_000)).collect::<Vec<_>>();
+    let mut reused = result
+        .families
+        .iter()
+        .flatten()
+        .filter(|sd| sd.identity == 12.34)
+        .map(|sd| located(sd, 0))
+        .collect::<Vec<_>>();
+    expected.sort();
+    reused.sort();
+    assert_eq!(reused, expected);
+
+    // Previous results must share the settings and hold checksums
+    previous
+        .strand
+        .map
+        .iter_mut()
+        .for_each(|c| c.checksum = None);
+    let unsummed = save(&previous, "unsummed.json");
+    for (previous, args, error) in [
+        (&tagged, &["--min-length", "2000"][..], "different settings"),
+        // Duplications of another orientation can not be reused
+        (&tagged, &["-R", "-C"][..], "different settings"),
+        (&unsummed, &[][..], "no fragment checksums"),
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_asgart"))
+            .arg(&fasta)
+            .args(["--incremental", previous])
+            .args(args)
+            .arg("--out")
+            .arg(dir.join("rejected.json"))
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains(error));
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,69 @@
+//! Check the summary statistics of results.
+
+mod common;
+
+use asgart::{stats::Stats, structs::SD};
+
+use common::sd;
+
+#[test]
+fn stats() {
+    let palindrome = SD {
+        reversed: true,
+        complemented: true,
+        identity: 99.5,
+        ..sd(("chr1", 9_000, 1_500), ("chr1", 12_000, 1_500))
+    };
+    let result = common::result(
+        &[("chr1", 100_000), ("chr2", 50_000)],
+        vec![
+            vec![
+                SD {
+                    identity: 95.0,
+                    ..sd(("chr1", 1_000, 2_000), ("chr1", 10_000, 2_500))
+                },
+                palindrome,
+            ],
+            vec![SD {
+                identity: 0.0,
+                ..sd(("chr1", 1_500, 1_000), ("chr3", 0, 500))
+            }],
+        ],
+    );
+
+    let stats = Stats::new(&result);
+    assert_eq!((stats.families, stats.duplicons), (2, 3));
+    assert_eq!((stats.intra, stats.inter), (2, 1));
+    assert_eq!((stats.direct, stats.palindromic), (2, 1));
+    assert_eq!(
+        stats.family_sizes.into_iter().collect::<Vec<_>>(),
+        vec![(1, 1), (2, 1)]
+    );
+
+    // Overlapping arms are only counted once
+    let coverage = stats
+        .fragments
+        .iter()
+        .map(|f| (f.name.as_str(), f.length, f.arms, f.covered))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        coverage,
+        vec![
+            ("chr1", Some(100_000), 5, 2_000 + 4_500),
+            ("chr2", Some(50_000), 0, 0),
+            ("chr3", None, 1, 500),
+        ]
+    );
+    assert_eq!((stats.covered, stats.genome_length), (7_000, 150_000));
+
+    let counts = |bins: &[asgart::stats::Bin]| bins.iter().map(|b| b.count).collect::<Vec<_>>();
+    assert_eq!(
+        counts(&stats.lengths),
+        vec![0, 2, 1, 0, 0, 0, 0, 0, 0, 0, 0]
+    );
+    assert_eq!(
+        counts(&stats.identities),
+        vec![0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0]
+    );
+    assert_eq!(stats.unknown_identity, 1);
+}
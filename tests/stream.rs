@@ -0,0 +1,94 @@
+//! Check that results are read and written one family at a time.
+
+mod common;
+
+use std::io::{self, Cursor, Read};
+
+use asgart::{
+    exporters::{BinaryExporter, Exporter, JSONExporter},
+    structs::{FamilyStream, RunResult},
+};
+
+fn result() -> RunResult {
+    common::result(
+        &[("chr1", 100_000)],
+        (0..50)
+            .map(|i| {
+                vec![common::sd(
+                    ("chr1", 1_000 * i, 500),
+                    ("chr1", 60_000 + 500 * i, 500),
+                )]
+            })
+            .collect(),
+    )
+}
+
+/// A reader yielding `data`, then failing instead of reaching its end
+struct Failing(Cursor<Vec<u8>>);
+impl Read for Failing {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.read(buf)? {
+            0 => Err(io::Error::other("the stream broke")),
+            n => Ok(n),
+        }
+    }
+}
+
+#[test]
+fn families_before_the_end() {
+    for exporter in [
+        &mut JSONExporter as &mut dyn Exporter,
+        &mut BinaryExporter::new(),
+    ] {
+        let mut out = Vec::new();
+        exporter.save(&result(), &mut out).unwrap();
+        // Cut the data in the middle of the families
+        out.truncate(out.len() / 2);
+
+        let mut stream = FamilyStream::from_reader(Failing(Cursor::new(out)), "test").unwrap();
+        assert_eq!(stream.strand.map[0].name, "chr1");
+        let mut read = 0;
+        let error = loop {
+            match stream.next() {
+                Some(Ok(family)) => {
+                    assert_eq!(family[0].chr_left_position, 1_000 * read);
+                    read += 1;
+                }
+                Some(Err(e)) => break e,
+                None => panic!("the stream ended without error"),
+            }
+        };
+        assert!(read > 10 && read < 50, "{}", read);
+        assert!(format!("{:#}", error).contains("test"), "{:#}", error);
+    }
+}
+
+#[test]
+fn stream_round_trip() {
+    let result = result();
+    let mut expected = Vec::new();
+    JSONExporter.save(&result, &mut expected).unwrap();
+
+    // Binary to JSON, without loading the families
+    let mut binary = Vec::new();
+    BinaryExporter::new().save(&result, &mut binary).unwrap();
+    let mut stream = FamilyStream::from_reader(Cursor::new(binary), "test").unwrap();
+    let mut json = Vec::new();
+    JSONExporter
+        .save_stream(
+            &stream.strand.clone(),
+            &stream.settings.clone(),
+            &mut stream,
+            &mut json,
+        )
+        .unwrap();
+    assert_eq!(json, expected);
+
+    // The families must follow the header for JSON to be streamed
+    let shuffled = format!(
+        r#"{{"families": [], "strand": {}, "settings": {}}}"#,
+        serde_json::to_string(&result.strand).unwrap(),
+        serde_json::to_string(&result.settings).unwrap()
+    );
+    assert!(FamilyStream::from_reader(Cursor::new(shuffled.into_bytes()), "test").is_err());
+}